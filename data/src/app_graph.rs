@@ -1,9 +1,9 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 
 use hardware::Hardware;
 
 use crate::config::Config;
-use crate::config::{control::Control, fan::Fan, temp::Temp};
+use crate::config::{control::Control, fan::Fan, sensor::Sensor, temp::Temp};
 
 use crate::id::{Id, IdGenerator};
 use crate::node::{self, Node, NodeType, NodeTypeLight, ToNode};
@@ -12,7 +12,7 @@ use crate::utils::RemoveElem;
 pub type Nodes = BTreeMap<Id, Node>;
 pub type RootNodes = Vec<Id>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AppGraph {
     pub nodes: Nodes,
     pub id_generator: IdGenerator,
@@ -96,6 +96,17 @@ impl AppGraph {
             app_graph.insert_node(node);
         }
 
+        for sensor_h in &hardware.sensors {
+            let sensor = Sensor {
+                name: sensor_h.name.clone(),
+                hardware_id: Some(sensor_h.hardware_id.clone()),
+                sensor_h: Some(sensor_h.clone()),
+            };
+
+            let node = Node::new(NodeType::Sensor(sensor), &mut app_graph);
+            app_graph.insert_node(node);
+        }
+
         app_graph
     }
 
@@ -103,7 +114,7 @@ impl AppGraph {
         self.nodes.clear();
         self.root_nodes.clear();
 
-        // order: fan -> temp -> custom_temp -> behavior -> control
+        // order: fan -> temp -> sensor -> command_temp -> custom_temp -> behavior -> control
 
         for fan in config.fans {
             let node = fan.to_node(self, hardware);
@@ -115,11 +126,26 @@ impl AppGraph {
             self.insert_node(node);
         }
 
+        for sensor in config.sensors {
+            let node = sensor.to_node(self, hardware);
+            self.insert_node(node);
+        }
+
+        for command_temp in config.command_temps {
+            let node = command_temp.to_node(self, hardware);
+            self.insert_node(node);
+        }
+
         for custom_temp in config.custom_temps {
             let node = custom_temp.to_node(self, hardware);
             self.insert_node(node);
         }
 
+        for smooth in config.smooths {
+            let node = smooth.to_node(self, hardware);
+            self.insert_node(node);
+        }
+
         for flat in config.flats {
             let node = flat.to_node(self, hardware);
             self.insert_node(node);
@@ -135,8 +161,22 @@ impl AppGraph {
             self.insert_node(node);
         }
 
+        for pid in config.pids {
+            let node = pid.to_node(self, hardware);
+            self.insert_node(node);
+        }
+
         for graph in config.graphs {
             let node = graph.to_node(self, hardware);
+            let id = node.id;
+            self.insert_node(node);
+            // like a graph bound to a temp in the user interface
+            self.fit_graph_to_input(&id);
+        }
+
+        // after the behaviors, its inputs
+        for mix in config.mixes {
+            let node = mix.to_node(self, hardware);
             self.insert_node(node);
         }
 
@@ -160,11 +200,16 @@ impl AppGraph {
             NodeTypeLight::Control => fl!("default_control"),
             NodeTypeLight::Fan => fl!("default_fan"),
             NodeTypeLight::Temp => fl!("default_temp"),
+            NodeTypeLight::Sensor => fl!("default_sensor"),
+            NodeTypeLight::CommandTemp => fl!("default_command_temp"),
             NodeTypeLight::CustomTemp => fl!("default_custom_temp"),
+            NodeTypeLight::Smooth => fl!("default_smooth"),
             NodeTypeLight::Graph => fl!("default_graph"),
             NodeTypeLight::Flat => fl!("default_flat"),
             NodeTypeLight::Linear => fl!("default_linear"),
             NodeTypeLight::Target => fl!("default_target"),
+            NodeTypeLight::Pid => fl!("default_pid"),
+            NodeTypeLight::Mix => fl!("default_mix"),
         };
 
         Self::find_unused_name(&self.nodes, &default_name, 1)
@@ -188,11 +233,16 @@ impl AppGraph {
             NodeTypeLight::Control => NodeType::Control(Default::default()),
             NodeTypeLight::Fan => NodeType::Fan(Default::default()),
             NodeTypeLight::Temp => NodeType::Temp(Default::default()),
+            NodeTypeLight::Sensor => NodeType::Sensor(Default::default()),
+            NodeTypeLight::CommandTemp => NodeType::CommandTemp(Default::default()),
             NodeTypeLight::CustomTemp => NodeType::CustomTemp(Default::default()),
+            NodeTypeLight::Smooth => NodeType::Smooth(Default::default()),
             NodeTypeLight::Graph => NodeType::Graph(Default::default()),
             NodeTypeLight::Flat => NodeType::Flat(Default::default()),
             NodeTypeLight::Linear => NodeType::Linear(Default::default()),
             NodeTypeLight::Target => NodeType::Target(Default::default()),
+            NodeTypeLight::Pid => NodeType::Pid(Default::default()),
+            NodeTypeLight::Mix => NodeType::Mix(Default::default()),
         };
 
         let new_name = self.generate_default_name(node_type_light);
@@ -201,6 +251,93 @@ impl AppGraph {
         Node::new(node_type, self)
     }
 
+    /// Bind the nodes to the items of a new hardware, by id. Nodes whose item
+    /// disappeared keep their id and become invalid until it comes back.
+    pub fn rebind(&mut self, hardware: &Hardware) {
+        // release the old items first, they can be returned again
+        for node in self.nodes.values_mut() {
+            if let NodeType::Control(control) = &mut node.node_type {
+                control.control_h.take();
+            }
+        }
+
+        let mut bound_controls = HashSet::new();
+
+        for node in self.nodes.values_mut() {
+            let bound = match &mut node.node_type {
+                NodeType::Control(control) => {
+                    control.control_h = control.hardware_id.as_ref().and_then(|hardware_id| {
+                        hardware
+                            .controls
+                            .iter()
+                            .find(|control_h| &control_h.hardware_id == hardware_id)
+                            .filter(|_| bound_controls.insert(hardware_id.clone()))
+                            .cloned()
+                    });
+                    // the mode must be set again on the new item
+                    control.mode_set = None;
+                    control.control_h.is_some()
+                }
+                NodeType::Fan(fan) => {
+                    fan.fan_h = fan.hardware_id.as_ref().and_then(|hardware_id| {
+                        hardware
+                            .fans
+                            .iter()
+                            .find(|fan_h| &fan_h.hardware_id == hardware_id)
+                            .cloned()
+                    });
+                    fan.fan_h.is_some()
+                }
+                NodeType::Temp(temp) => {
+                    temp.temp_h = temp.hardware_id.as_ref().and_then(|hardware_id| {
+                        hardware
+                            .temps
+                            .iter()
+                            .find(|temp_h| &temp_h.hardware_id == hardware_id)
+                            .cloned()
+                    });
+                    temp.temp_h.is_some()
+                }
+                NodeType::Sensor(sensor) => {
+                    sensor.sensor_h = sensor.hardware_id.as_ref().and_then(|hardware_id| {
+                        hardware
+                            .sensors
+                            .iter()
+                            .find(|sensor_h| &sensor_h.hardware_id == hardware_id)
+                            .cloned()
+                    });
+                    sensor.sensor_h.is_some()
+                }
+                _ => continue,
+            };
+
+            if !bound {
+                if let Some(hardware_id) = node.hardware_id() {
+                    warn!("{hardware_id} of {} is gone.", node.name());
+                }
+                node.value.take();
+            }
+        }
+    }
+
+    /// Make a graph which still has the default curve top out at the
+    /// limit of its input, like the crit temperature of the chip.
+    pub fn fit_graph_to_input(&mut self, id: &Id) {
+        let Some(limits) = self
+            .get(id)
+            .inputs
+            .first()
+            .and_then(|input| self.nodes.get(&input.id))
+            .and_then(|input| input.node_type.sensor_limits())
+        else {
+            return;
+        };
+
+        if let NodeType::Graph(graph) = &mut self.get_mut(id).node_type {
+            graph.fit_to_limits(&limits);
+        }
+    }
+
     pub fn sanitize_inputs(&mut self, log: bool) {
         let mut sanitizes = Vec::new();
 
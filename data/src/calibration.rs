@@ -0,0 +1,435 @@
+use std::{fmt::Display, sync::Arc, time::Duration};
+
+use hardware::{ControlKind, HControl, HSensor, HardwareBridge, HardwareError, Mode, Value};
+use thiserror::Error;
+
+use crate::{
+    app_graph::AppGraph,
+    config::control::{Calibration, Control},
+    id::Id,
+    node::NodeType,
+};
+
+/// Time given to a fan to reach its new speed before reading it.
+pub const SETTLE_TIME: Duration = Duration::from_secs(5);
+
+/// Number of values tested between the min and the max of a control.
+const STEPS: Value = 20;
+
+/// Part of the full speed, in percent, under which the fan
+/// is considered slower.
+const EFFECTIVE_THRESHOLD: Value = 98;
+
+#[derive(Error, Debug)]
+pub enum CalibrationError {
+    #[error("Node {0} is not a control bound to a hardware")]
+    NotAControl(String),
+    #[error("Control {0} doesn't take a duty cycle")]
+    NotPwm(String),
+    #[error("No fan reacted to {0}")]
+    NoFan(String),
+    #[error("The fan of {0} doesn't start")]
+    FanDoesntStart(String),
+    #[error(transparent)]
+    Hardware(#[from] HardwareError),
+}
+
+type Result<T> = std::result::Result<T, CalibrationError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Full speed, to know the speed of every fan
+    Max,
+    /// Lowest value, to find the fan which stops
+    Stop,
+    /// Raising the value until the fan starts
+    Start,
+    /// Full speed again, before lowering it
+    Restart,
+    /// Lowering the value until the fan slows down
+    Effective,
+    /// Lowering the value until the fan stops
+    Stay,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Phase::Max | Phase::Restart => fl!("calibration_max"),
+            Phase::Stop => fl!("calibration_stop"),
+            Phase::Start => fl!("calibration_start"),
+            Phase::Effective | Phase::Stay => fl!("calibration_lower"),
+        };
+        write!(f, "{str}")
+    }
+}
+
+/// Sweep the value of a control while watching the speed of the fans,
+/// like `pwmconfig` does.
+///
+/// The hardware of the control is taken from its node during the calibration,
+/// so the update loop leaves it alone. [`Calibrator::tick`] must be called
+/// once per update cycle, and always give the hardware back in auto mode.
+pub struct Calibrator {
+    id: Id,
+    name: String,
+    control_h: Arc<HControl>,
+    /// Fans which could be driven by the control
+    fans: Vec<Arc<HSensor>>,
+    settle_ticks: u32,
+    ticks: u32,
+    phase: Phase,
+    value: Value,
+    step: Value,
+    max_rpms: Vec<Value>,
+    /// Index in fans of the fan driven by the control
+    fan: Option<usize>,
+    full_rpm: Value,
+    min_start: Value,
+    min_stay: Value,
+    max_effective: Value,
+}
+
+impl Calibrator {
+    /// Number of update cycles covering [`SETTLE_TIME`].
+    pub fn settle_ticks(update_delay: Duration) -> u32 {
+        let update_delay = update_delay.max(Duration::from_millis(50));
+        SETTLE_TIME.as_millis().div_ceil(update_delay.as_millis()) as u32
+    }
+
+    /// Set the control to its max, and start watching its fan,
+    /// or all the fans when it is not paired.
+    pub fn start<H: HardwareBridge>(
+        app_graph: &mut AppGraph,
+        bridge: &mut H,
+        id: Id,
+        settle_ticks: u32,
+    ) -> Result<Self> {
+        let (name, control_h, fan) = {
+            let node = app_graph.get(&id);
+            match &node.node_type {
+                NodeType::Control(control) => (
+                    node.name().clone(),
+                    control.control_h.clone(),
+                    control.fan.clone(),
+                ),
+                _ => (node.name().clone(), None, None),
+            }
+        };
+
+        let Some(control_h) = control_h else {
+            return Err(CalibrationError::NotAControl(name));
+        };
+
+        if control_h.kind != ControlKind::Pwm {
+            return Err(CalibrationError::NotPwm(name));
+        }
+
+        let mut fans = bridge.hardware().fans.clone();
+        if let Some(fan) = fan
+            && fans.iter().any(|fan_h| fan_h.hardware_id == fan)
+        {
+            fans.retain(|fan_h| fan_h.hardware_id == fan);
+        }
+
+        let range = control_h.range;
+        let calibrator = Self {
+            id,
+            name,
+            control_h,
+            fans,
+            settle_ticks,
+            ticks: 0,
+            phase: Phase::Max,
+            value: range.max,
+            step: ((range.max - range.min) / STEPS).max(range.step).max(1),
+            max_rpms: Vec::new(),
+            fan: None,
+            full_rpm: 0,
+            min_start: range.min,
+            min_stay: range.min,
+            max_effective: range.max,
+        };
+
+        take_control(
+            app_graph,
+            bridge,
+            &id,
+            &calibrator.control_h,
+            calibrator.value,
+        )?;
+
+        info!("Calibration of {} started.", calibrator.name);
+        Ok(calibrator)
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Value currently written to the control.
+    pub fn value(&self) -> Value {
+        self.value
+    }
+
+    /// Return the result when the calibration is over. The control is
+    /// back in auto mode, and the calibration is stored in its node.
+    pub fn tick<H: HardwareBridge>(
+        &mut self,
+        app_graph: &mut AppGraph,
+        bridge: &mut H,
+    ) -> Option<Result<Calibration>> {
+        self.ticks += 1;
+        if self.ticks < self.settle_ticks {
+            return None;
+        }
+        self.ticks = 0;
+
+        let rpms = read_rpms(bridge, &self.fans);
+
+        let result = match self.next(&rpms) {
+            Ok(None) => match bridge.set_value(&self.control_h, self.value) {
+                Ok(()) => return None,
+                Err(e) => Err(e.into()),
+            },
+            Ok(Some(calibration)) => Ok(calibration),
+            Err(e) => Err(e),
+        };
+
+        self.finish(app_graph, bridge, result.as_ref().ok().copied());
+
+        match &result {
+            Ok(calibration) => info!("Calibration of {} done: {calibration:?}.", self.name),
+            Err(e) => error!("Calibration of {} failed: {e}.", self.name),
+        }
+
+        Some(result)
+    }
+
+    /// Stop the calibration, and leave the control in auto mode.
+    pub fn abort<H: HardwareBridge>(mut self, app_graph: &mut AppGraph, bridge: &mut H) {
+        info!("Calibration of {} aborted.", self.name);
+        self.finish(app_graph, bridge, None);
+    }
+
+    /// Decide the next value to write from the speed of the fans.
+    fn next(&mut self, rpms: &[Value]) -> Result<Option<Calibration>> {
+        let range = self.control_h.range;
+
+        match self.phase {
+            Phase::Max => {
+                self.max_rpms = rpms.to_vec();
+                self.value = range.min;
+                self.phase = Phase::Stop;
+            }
+            Phase::Stop => {
+                let fan = reacting_fan(&self.max_rpms, rpms)
+                    .ok_or_else(|| CalibrationError::NoFan(self.name.clone()))?;
+
+                info!(
+                    "Calibration of {}: driving {}.",
+                    self.name, self.fans[fan].name
+                );
+                self.fan = Some(fan);
+
+                if rpms[fan] > 0 {
+                    // the fan never stops
+                    self.value = range.max;
+                    self.phase = Phase::Restart;
+                } else {
+                    self.value = self.lower_step(range.min, -1);
+                    self.phase = Phase::Start;
+                }
+            }
+            Phase::Start => {
+                if self.rpm(rpms) > 0 {
+                    self.min_start = self.value;
+                    self.value = range.max;
+                    self.phase = Phase::Restart;
+                } else if self.value >= range.max {
+                    return Err(CalibrationError::FanDoesntStart(self.name.clone()));
+                } else {
+                    self.value = self.lower_step(self.value, -1);
+                }
+            }
+            Phase::Restart => {
+                self.full_rpm = self.rpm(rpms);
+                self.max_effective = range.max;
+                self.min_stay = range.max;
+
+                if self.value <= range.min {
+                    return Ok(Some(self.calibration()));
+                }
+                self.value = self.lower_step(self.value, 1);
+                self.phase = Phase::Effective;
+            }
+            Phase::Effective | Phase::Stay => {
+                let rpm = self.rpm(rpms);
+
+                if self.phase == Phase::Effective {
+                    if rpm * 100 >= self.full_rpm * EFFECTIVE_THRESHOLD {
+                        self.max_effective = self.value;
+                    } else {
+                        self.phase = Phase::Stay;
+                    }
+                }
+
+                if rpm == 0 {
+                    return Ok(Some(self.calibration()));
+                }
+
+                self.min_stay = self.value;
+                if self.value <= range.min {
+                    return Ok(Some(self.calibration()));
+                }
+                self.value = self.lower_step(self.value, 1);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn rpm(&self, rpms: &[Value]) -> Value {
+        self.fan.map(|fan| rpms[fan]).unwrap_or(0)
+    }
+
+    /// Move the value by a step, down for a positive direction.
+    fn lower_step(&self, value: Value, direction: Value) -> Value {
+        self.control_h.range.clamp(value - direction * self.step)
+    }
+
+    fn calibration(&self) -> Calibration {
+        let min_stay = self.min_stay.min(self.max_effective);
+
+        Calibration {
+            min_start: self.min_start.clamp(min_stay, self.max_effective),
+            min_stay,
+            max_effective: self.max_effective,
+        }
+    }
+
+    fn finish<H: HardwareBridge>(
+        &mut self,
+        app_graph: &mut AppGraph,
+        bridge: &mut H,
+        calibration: Option<Calibration>,
+    ) {
+        if let Some(control) = give_back_control(app_graph, bridge, &self.id, &self.control_h)
+            && calibration.is_some()
+        {
+            control.calibration = calibration;
+        }
+    }
+}
+
+/// Drive a control directly, and take its hardware from its node
+/// so it is invalid and the update loop leaves it alone.
+pub(crate) fn take_control<H: HardwareBridge>(
+    app_graph: &mut AppGraph,
+    bridge: &mut H,
+    id: &Id,
+    control_h: &HControl,
+    value: Value,
+) -> std::result::Result<(), HardwareError> {
+    if let Err(e) = bridge
+        .set_mode(control_h, &Mode::Manual)
+        .and_then(|_| bridge.set_value(control_h, value))
+    {
+        if let Err(e) = bridge.set_mode(control_h, &Mode::Auto) {
+            error!("Can't set {} back to auto: {e}.", control_h.name);
+        }
+        return Err(e);
+    }
+
+    if let NodeType::Control(control) = &mut app_graph.get_mut(id).node_type {
+        control.control_h.take();
+        control.mode_set = Some(Mode::Auto);
+    }
+    Ok(())
+}
+
+/// Set a control taken by [`take_control`] to auto, and give it back to its node.
+/// The next update of the node set it to manual again.
+pub(crate) fn give_back_control<'a, H: HardwareBridge>(
+    app_graph: &'a mut AppGraph,
+    bridge: &mut H,
+    id: &Id,
+    control_h: &Arc<HControl>,
+) -> Option<&'a mut Control> {
+    if let Err(e) = bridge.set_mode(control_h, &Mode::Auto) {
+        error!("Can't set {} back to auto: {e}.", control_h.name);
+    }
+
+    // the node can have been removed meanwhile
+    let NodeType::Control(control) = &mut app_graph.nodes.get_mut(id)?.node_type else {
+        return None;
+    };
+
+    if control.control_h.is_none() && control.hardware_id.as_ref() == Some(&control_h.hardware_id) {
+        control.control_h = Some(control_h.clone());
+    }
+    control.mode_set = Some(Mode::Auto);
+    control.last_raw = None;
+
+    Some(control)
+}
+
+/// A fan which can't be read is considered stopped.
+pub(crate) fn read_rpms<H: HardwareBridge>(bridge: &mut H, fans: &[Arc<HSensor>]) -> Vec<Value> {
+    fans.iter()
+        .map(|fan_h| {
+            bridge
+                .get_sensor_value(fan_h)
+                .map_or(0, |rpm| rpm.round() as Value)
+        })
+        .collect()
+}
+
+/// Index of the fan that slowed down the most between two readings,
+/// if it lost at least half its speed.
+pub(crate) fn reacting_fan(max_rpms: &[Value], rpms: &[Value]) -> Option<usize> {
+    max_rpms
+        .iter()
+        .zip(rpms)
+        .enumerate()
+        .filter(|(_, (max_rpm, rpm))| **max_rpm > 0 && *max_rpm - *rpm > *max_rpm / 2)
+        .max_by_key(|(_, (max_rpm, rpm))| *max_rpm - *rpm)
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod test {
+    use hardware::ControlRange;
+
+    use crate::config::control::Calibration;
+
+    #[test]
+    fn test_remap() {
+        let calibration = Calibration {
+            min_start: 120,
+            min_stay: 80,
+            max_effective: 230,
+        };
+        let range = ControlRange {
+            min: 0,
+            max: 255,
+            step: 1,
+        };
+
+        assert_eq!(calibration.to_raw(&range, 0, None), 0);
+        // a stopped fan is kicked
+        assert_eq!(calibration.to_raw(&range, 10, None), 120);
+        assert_eq!(calibration.to_raw(&range, 10, Some(120)), 95);
+        assert_eq!(calibration.to_raw(&range, 100, Some(120)), 230);
+        assert_eq!(calibration.from_raw(230), 100);
+        assert_eq!(calibration.from_raw(50), 0);
+    }
+}
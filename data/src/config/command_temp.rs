@@ -0,0 +1,410 @@
+use std::{
+    io::Read,
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use hardware::Hardware;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    app_graph::AppGraph,
+    node::{IsValid, Node, NodeType, ToNode},
+    update::UpdateError,
+};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    #[error("Can't run {0}: {1}")]
+    Spawn(String, String),
+    #[error("{0} didn't finish in {1:?}")]
+    Timeout(String, Duration),
+    #[error("{0} exited with {1}")]
+    Status(String, String),
+    #[error("Can't parse the output of {0}: {1}")]
+    Parse(String, String),
+}
+
+/// How a value is extracted from the standard output of a command.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Parse {
+    /// The whole output, without surrounding whitespaces
+    #[default]
+    Whole,
+    /// The first capture group of the pattern, or the whole match
+    Regex { pattern: String },
+    /// The number found at this JSON pointer (RFC 6901)
+    Json { pointer: String },
+}
+
+/// A temperature read from the output of a program.
+/// The program run on its own thread: an update start a new run if the
+/// last one is done and `interval` elapsed, and use the last value read.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CommandTemp {
+    pub name: String,
+    /// Program followed by its arguments. No shell is involved.
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub parse: Parse,
+    /// The parsed number is multiplied by this factor,
+    /// ex: 0.001 for millidegrees
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    /// In seconds
+    #[serde(default = "default_timeout")]
+    pub timeout: f64,
+    /// Minimum time between the start of two runs, in seconds
+    #[serde(default = "default_interval")]
+    pub interval: f64,
+
+    /// Compiled pattern of `Parse::Regex`
+    #[serde(skip)]
+    regex: Option<Regex>,
+    #[serde(skip)]
+    state: Arc<Mutex<RunState>>,
+}
+
+#[derive(Debug, Default)]
+struct RunState {
+    running: bool,
+    started: Option<Instant>,
+    last: Option<Result<f64, CommandError>>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+fn default_timeout() -> f64 {
+    5.0
+}
+
+fn default_interval() -> f64 {
+    2.0
+}
+
+/// Out of range values give a zero duration, instead of a panic.
+fn duration(seconds: f64) -> Duration {
+    Duration::try_from_secs_f64(seconds).unwrap_or_default()
+}
+
+impl PartialEq for CommandTemp {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.command == other.command
+            && self.parse == other.parse
+            && self.scale == other.scale
+            && self.timeout == other.timeout
+            && self.interval == other.interval
+    }
+}
+
+impl Eq for CommandTemp {}
+
+impl CommandTemp {
+    pub fn new(name: String, command: Vec<String>, parse: Parse) -> Self {
+        let mut command_temp = Self {
+            name,
+            command,
+            parse,
+            scale: default_scale(),
+            timeout: default_timeout(),
+            interval: default_interval(),
+            regex: None,
+            state: Default::default(),
+        };
+        command_temp.compile();
+        command_temp
+    }
+
+    /// Compile the pattern once, instead of at each run.
+    fn compile(&mut self) {
+        self.regex = match &self.parse {
+            Parse::Regex { pattern } => match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    warn!("invalid pattern {pattern}: {e}");
+                    None
+                }
+            },
+            _ => None,
+        };
+    }
+
+    /// Start a run if none is in progress and the interval elapsed,
+    /// and return the result of the last one.
+    pub fn get_value(&self) -> Result<f64, UpdateError> {
+        let mut state = self.state.lock().unwrap();
+
+        let due = state
+            .started
+            .is_none_or(|started| started.elapsed() >= duration(self.interval));
+        if !state.running && due {
+            state.running = true;
+            state.started = Some(Instant::now());
+            let command = self.clone();
+            thread::spawn(move || {
+                let result = command.run();
+                let mut state = command.state.lock().unwrap();
+                state.running = false;
+                state.last = Some(result);
+            });
+        }
+
+        match &state.last {
+            Some(Ok(value)) => Ok(*value),
+            Some(Err(e)) => Err(UpdateError::Command(e.clone())),
+            None => Err(UpdateError::ValueIsNone),
+        }
+    }
+
+    fn program(&self) -> String {
+        self.command.first().cloned().unwrap_or_default()
+    }
+
+    fn run(&self) -> Result<f64, CommandError> {
+        let output = self.output()?;
+        self.parse_output(&output)
+    }
+
+    fn output(&self) -> Result<String, CommandError> {
+        let program = self.program();
+        let timeout = duration(self.timeout);
+
+        let mut child = Command::new(&program)
+            .args(&self.command[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| CommandError::Spawn(program.clone(), e.to_string()))?;
+
+        // read concurrently, so a full pipe can't block the program
+        let mut stdout = child.stdout.take().unwrap();
+        let reader = thread::spawn(move || {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).map(|_| output)
+        });
+
+        let deadline = Instant::now() + timeout;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) => {
+                    if Instant::now() >= deadline {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Err(CommandError::Timeout(program, timeout));
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => return Err(CommandError::Spawn(program, e.to_string())),
+            }
+        };
+
+        if !status.success() {
+            return Err(CommandError::Status(program, status.to_string()));
+        }
+
+        match reader.join() {
+            Ok(Ok(output)) => Ok(output),
+            Ok(Err(e)) => Err(CommandError::Parse(program, e.to_string())),
+            Err(_) => Err(CommandError::Parse(program, "reader panicked".into())),
+        }
+    }
+
+    fn parse_output(&self, output: &str) -> Result<f64, CommandError> {
+        let parse_error = |msg: String| CommandError::Parse(self.program(), msg);
+
+        let number = match &self.parse {
+            Parse::Whole => parse_number(output.trim()).map_err(parse_error)?,
+            Parse::Regex { pattern } => {
+                let regex = self
+                    .regex
+                    .as_ref()
+                    .ok_or_else(|| parse_error(format!("invalid pattern {pattern}")))?;
+                let captures = regex
+                    .captures(output)
+                    .ok_or_else(|| parse_error(format!("no match for {pattern}")))?;
+                let text = captures.get(1).or(captures.get(0)).unwrap().as_str();
+                parse_number(text.trim()).map_err(parse_error)?
+            }
+            Parse::Json { pointer } => {
+                let json: serde_json::Value =
+                    serde_json::from_str(output).map_err(|e| parse_error(e.to_string()))?;
+                match json.pointer(pointer) {
+                    Some(serde_json::Value::Number(n)) => n.as_f64().unwrap(),
+                    Some(serde_json::Value::String(s)) => parse_number(s).map_err(parse_error)?,
+                    Some(other) => return Err(parse_error(format!("{other} is not a number"))),
+                    None => return Err(parse_error(format!("nothing at {pointer}"))),
+                }
+            }
+        };
+
+        let value = number * self.scale;
+        if !value.is_finite() {
+            return Err(parse_error(format!("{value} is not a temperature")));
+        }
+        Ok(value)
+    }
+}
+
+fn parse_number(text: &str) -> Result<f64, String> {
+    text.parse::<f64>()
+        .map_err(|e| format!("\"{text}\" is not a number: {e}"))
+}
+
+impl IsValid for CommandTemp {
+    fn is_valid(&self) -> bool {
+        !self.command.is_empty()
+            && self.timeout.is_finite()
+            && self.timeout > 0.0
+            && self.interval.is_finite()
+            && self.interval >= 0.0
+            && (self.regex.is_some() || !matches!(self.parse, Parse::Regex { .. }))
+    }
+}
+
+impl ToNode for CommandTemp {
+    fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
+        if !self.timeout.is_finite() || self.timeout <= 0.0 {
+            warn!("timeout {} must be a positive number", self.timeout);
+            self.timeout = default_timeout();
+        }
+        if !self.interval.is_finite() || self.interval < 0.0 {
+            warn!("interval {} must be a positive number", self.interval);
+            self.interval = default_interval();
+        }
+        self.compile();
+
+        Node::new(NodeType::CommandTemp(self), app_graph)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command_temp(command: &[&str], parse: Parse) -> CommandTemp {
+        CommandTemp::new(
+            "command".into(),
+            command.iter().map(|s| s.to_string()).collect(),
+            parse,
+        )
+    }
+
+    /// Poll like the update loop until the run is done.
+    fn poll(command: &CommandTemp) -> Result<f64, UpdateError> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            match command.get_value() {
+                Err(UpdateError::ValueIsNone) if Instant::now() < deadline => {
+                    thread::yield_now();
+                }
+                result => return result,
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let command = command_temp(&["unused"], Parse::Whole);
+        assert_eq!(command.parse_output(" 42.4\n"), Ok(42.4));
+
+        let mut command = command_temp(&["unused"], Parse::Whole);
+        command.scale = 0.001;
+        assert_eq!(command.parse_output("45500"), Ok(45.5));
+
+        let command = command_temp(
+            &["unused"],
+            Parse::Regex {
+                pattern: r"CPU Temp\s*\|\s*([\d.]+)".into(),
+            },
+        );
+        assert_eq!(
+            command.parse_output("Fan1 | 1200\nCPU Temp | 51.000 | degrees C\n"),
+            Ok(51.0)
+        );
+        assert!(command.parse_output("nothing").is_err());
+
+        let command = command_temp(
+            &["unused"],
+            Parse::Json {
+                pointer: "/sensors/1/value".into(),
+            },
+        );
+        assert_eq!(
+            command.parse_output(r#"{"sensors": [{"value": 30}, {"value": -5.2}]}"#),
+            Ok(-5.2)
+        );
+        assert!(command.parse_output(r#"{"sensors": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let command = command_temp(&["echo", "37"], Parse::Whole);
+        assert_eq!(poll(&command).unwrap(), 37.0);
+
+        let command = command_temp(&["false"], Parse::Whole);
+        assert!(matches!(
+            poll(&command),
+            Err(UpdateError::Command(CommandError::Status(..)))
+        ));
+
+        let command = command_temp(&["/nonexistent/program"], Parse::Whole);
+        assert!(matches!(
+            poll(&command),
+            Err(UpdateError::Command(CommandError::Spawn(..)))
+        ));
+
+        let mut command = command_temp(&["sleep", "10"], Parse::Whole);
+        command.timeout = 0.1;
+        // the first update doesn't wait for the command
+        assert!(matches!(command.get_value(), Err(UpdateError::ValueIsNone)));
+        assert!(matches!(
+            poll(&command),
+            Err(UpdateError::Command(CommandError::Timeout(..)))
+        ));
+    }
+
+    #[test]
+    fn test_interval() {
+        let runs = std::env::temp_dir().join("fan-control-command-interval");
+        let _ = std::fs::remove_file(&runs);
+        let script = format!("echo >> {0}; wc -l < {0}", runs.display());
+
+        let mut command = command_temp(&["sh", "-c", &script], Parse::Whole);
+        command.interval = 60.0;
+        assert_eq!(poll(&command).unwrap(), 1.0);
+
+        // the program doesn't run again before the interval
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(command.get_value().unwrap(), 1.0);
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(std::fs::read_to_string(&runs).unwrap().lines().count(), 1);
+
+        std::fs::remove_file(runs).unwrap();
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let mut command = command_temp(&["echo", "37"], Parse::Whole);
+        assert!(command.is_valid());
+        command.timeout = f64::INFINITY;
+        assert!(!command.is_valid());
+
+        let command = command_temp(
+            &["echo", "37"],
+            Parse::Regex {
+                pattern: "(unclosed".into(),
+            },
+        );
+        assert!(!command.is_valid());
+    }
+}
@@ -1,15 +1,15 @@
-use std::rc::Rc;
+use std::{sync::Arc, time::Duration};
 
-use hardware::{HControl, Hardware, HardwareBridge, Mode, Value};
+use hardware::{ControlRange, HControl, Hardware, HardwareBridge, Mode, OutputMode, Value};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     app_graph::AppGraph,
-    node::{IsValid, Node, NodeType, ToNode},
+    node::{self, IsValid, Node, NodeType, ToNode},
     update::UpdateError,
 };
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Control {
     // unique
     pub name: String,
@@ -18,13 +18,129 @@ pub struct Control {
     pub hardware_id: Option<String>,
     pub input: Option<String>,
     pub active: bool,
+    /// How the value of the input is interpreted
+    #[serde(default)]
+    pub unit: ControlUnit,
+    /// Pwm frequency in Hz, applied when the control takes over
+    pub frequency: Option<Value>,
+    /// Applied when the control takes over
+    #[serde(rename = "outputMode", alias = "output_mode")]
+    pub output_mode: Option<OutputMode>,
+    /// Hardware id of the fan driven by this control
+    pub fan: Option<String>,
+    /// Duty cycles measured by a calibration, used to map a percentage
+    /// to the range where the fan is actually spinning
+    pub calibration: Option<Calibration>,
+    /// Max rise of the value, in percent of the range per second
+    #[serde(rename = "rampUp", alias = "ramp_up")]
+    pub ramp_up: Option<f64>,
+    /// Max fall of the value, in percent of the range per second
+    #[serde(rename = "rampDown", alias = "ramp_down")]
+    pub ramp_down: Option<f64>,
+    /// Changes of the input smaller than this, in percent of the range,
+    /// are ignored
+    #[serde(rename = "minStep", alias = "min_step")]
+    pub min_step: Option<f64>,
 
     // E hardware.controls, only one ref in all controls
     #[serde(skip)]
-    pub control_h: Option<Rc<HControl>>,
+    pub control_h: Option<Arc<HControl>>,
 
     #[serde(skip)]
     pub mode_set: Option<Mode>,
+
+    /// Last raw value written, to know if the fan is stopped
+    #[serde(skip)]
+    pub last_raw: Option<Value>,
+
+    /// Forgotten when the control goes back to auto
+    #[serde(skip)]
+    pub ramp_state: Option<RampState>,
+}
+
+/// Where [`Control::ramp`] was at its last call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RampState {
+    pub time: Duration,
+    pub value: f64,
+    /// Input the value is going to
+    pub target: f64,
+}
+
+/// Raw values of a control found by [`crate::calibration::Calibrator`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration {
+    /// Lowest value starting a stopped fan
+    #[serde(rename = "minStart", alias = "min_start")]
+    pub min_start: Value,
+    /// Lowest value keeping a spinning fan running
+    #[serde(rename = "minStay", alias = "min_stay")]
+    pub min_stay: Value,
+    /// Value above which the fan doesn't spin faster
+    #[serde(rename = "maxEffective", alias = "max_effective")]
+    pub max_effective: Value,
+}
+
+impl Calibration {
+    /// 0% stops the fan, and the rest of the percentages are spread
+    /// between min_stay and max_effective. A stopped fan is kicked
+    /// with min_start first.
+    pub fn to_raw(&self, range: &ControlRange, value: Value, last_raw: Option<Value>) -> Value {
+        if value <= 0 {
+            return range.min;
+        }
+
+        let span = self.max_effective - self.min_stay;
+        let raw = self.min_stay + (span * value.min(100) + 50) / 100;
+
+        let stopped = last_raw.is_none_or(|last_raw| last_raw < self.min_stay);
+        if stopped && raw < self.min_start {
+            return range.clamp(self.min_start);
+        }
+
+        range.clamp(raw)
+    }
+
+    pub fn from_raw(&self, raw: Value) -> Value {
+        if raw < self.min_stay {
+            return 0;
+        }
+
+        let span = self.max_effective - self.min_stay;
+        if span <= 0 {
+            return 100;
+        }
+
+        ((raw - self.min_stay) * 100 / span).clamp(1, 100)
+    }
+}
+
+/// Unit of the value a control receive from its behavior.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ControlUnit {
+    /// Converted to the native range of the hardware,
+    /// which is the speed range for a fan target
+    #[default]
+    Percent,
+    /// Written as is, for fans whose useful range is only a few raw steps
+    Raw,
+}
+
+impl ControlUnit {
+    pub fn to_raw(&self, range: &ControlRange, value: Value) -> Value {
+        match self {
+            ControlUnit::Percent => range.from_percent(value),
+            ControlUnit::Raw => range.clamp(value),
+        }
+    }
+
+    pub fn from_raw(&self, range: &ControlRange, raw: Value) -> Value {
+        match self {
+            ControlUnit::Percent => range.to_percent(raw),
+            ControlUnit::Raw => raw,
+        }
+    }
 }
 
 impl PartialEq for Control {
@@ -33,6 +149,14 @@ impl PartialEq for Control {
             && self.hardware_id == other.hardware_id
             && self.input == other.input
             && self.active == other.active
+            && self.unit == other.unit
+            && self.frequency == other.frequency
+            && self.output_mode == other.output_mode
+            && self.fan == other.fan
+            && self.calibration == other.calibration
+            && self.ramp_up == other.ramp_up
+            && self.ramp_down == other.ramp_down
+            && self.min_step == other.min_step
     }
 }
 
@@ -42,18 +166,101 @@ impl Control {
         hardware_id: Option<String>,
         input: Option<String>,
         active: bool,
-        control_h: Option<Rc<HControl>>,
+        control_h: Option<Arc<HControl>>,
     ) -> Self {
         Self {
             name: name.clone(),
             hardware_id,
             input,
             active,
+            unit: ControlUnit::default(),
+            frequency: None,
+            output_mode: None,
+            fan: None,
+            calibration: None,
+            ramp_up: None,
+            ramp_down: None,
+            min_step: None,
             control_h,
             mode_set: None,
+            last_raw: None,
+            ramp_state: None,
+        }
+    }
+
+    /// Value to write at `now` on the way to `input`, limited by the ramp
+    /// rates. An input within `min_step` of the current target doesn't
+    /// change the target. The first value after the control takes over
+    /// is not limited.
+    pub fn ramp(&mut self, input: f64, now: Duration) -> f64 {
+        let Some(state) = self.ramp_state else {
+            self.ramp_state = Some(RampState {
+                time: now,
+                value: input,
+                target: input,
+            });
+            return input;
+        };
+
+        // rates and step are in percent of the range
+        let span = match (&self.control_h, self.unit) {
+            (Some(control_h), ControlUnit::Raw) => {
+                f64::from(control_h.range.max - control_h.range.min)
+            }
+            _ => 100.0,
+        };
+
+        let min_step = self.min_step.unwrap_or(0.0) * span / 100.0;
+        let target = if (input - state.target).abs() < min_step {
+            state.target
+        } else {
+            input
+        };
+
+        let elapsed = now.saturating_sub(state.time).as_secs_f64();
+        let max_change =
+            |rate: Option<f64>| rate.map_or(f64::INFINITY, |rate| rate * span / 100.0 * elapsed);
+
+        let value = if target > state.value {
+            target.min(state.value + max_change(self.ramp_up))
+        } else {
+            target.max(state.value - max_change(self.ramp_down))
+        };
+
+        self.ramp_state = Some(RampState {
+            time: now,
+            value,
+            target,
+        });
+        value
+    }
+
+    fn raw_value(&self, range: &ControlRange, value: Value) -> Value {
+        match (&self.calibration, self.unit) {
+            (Some(calibration), ControlUnit::Percent) => {
+                calibration.to_raw(range, value, self.last_raw)
+            }
+            _ => self.unit.to_raw(range, value),
         }
     }
 
+    fn unit_value(&self, range: &ControlRange, raw: Value) -> Value {
+        match (&self.calibration, self.unit) {
+            (Some(calibration), ControlUnit::Percent) => calibration.from_raw(raw),
+            _ => self.unit.from_raw(range, raw),
+        }
+    }
+
+    /// Value the hardware will report after setting `value`,
+    /// in the unit of the control.
+    pub fn reported_value(&self, value: Value) -> Option<Value> {
+        self.control_h.as_ref().map(|control_h| {
+            let raw = self.raw_value(&control_h.range, value);
+            self.unit_value(&control_h.range, raw)
+        })
+    }
+
+    /// Return the value the hardware will report, in the unit of the control.
     pub fn set_value<H: HardwareBridge>(
         &mut self,
         value: Value,
@@ -65,8 +272,10 @@ impl Control {
 
         match &self.control_h {
             Some(control_h) => {
-                bridge.set_value(control_h, value)?;
-                Ok(value)
+                let raw = self.raw_value(&control_h.range, value);
+                bridge.set_value(control_h, raw)?;
+                self.last_raw = Some(raw);
+                Ok(self.unit_value(&control_h.range, raw))
             }
             None => Err(UpdateError::NodeIsInvalid(self.name.clone())),
         }
@@ -85,19 +294,49 @@ impl Control {
         }
 
         match &self.control_h {
-            Some(control_h) => bridge.set_mode(control_h, &mode)?,
+            Some(control_h) => {
+                bridge.set_mode(control_h, &mode)?;
+                if mode == Mode::Manual {
+                    self.apply_settings(control_h, bridge);
+                }
+            }
             None => return Err(UpdateError::NodeIsInvalid(self.name.clone())),
         };
 
         info!("Mode {} succefuly set for {}.", mode, self.name);
+        if mode == Mode::Auto {
+            self.ramp_state = None;
+        }
         self.mode_set = Some(mode);
+        self.last_raw = None;
         Ok(())
     }
 
+    /// Apply the pwm frequency and output mode chosen in the config.
+    /// The bridge restores the original ones in auto mode.
+    fn apply_settings<H: HardwareBridge>(&self, control_h: &HControl, bridge: &mut H) {
+        if let Some(frequency) = self.frequency {
+            if !control_h.capabilities.frequency {
+                warn!("{} doesn't have a pwm frequency to set.", self.name);
+            } else if let Err(e) = bridge.set_frequency(control_h, frequency) {
+                error!("Can't set the pwm frequency of {}: {e}.", self.name);
+            }
+        }
+
+        if let Some(output_mode) = self.output_mode {
+            if !control_h.capabilities.output_mode {
+                warn!("{} doesn't have an output mode to set.", self.name);
+            } else if let Err(e) = bridge.set_output_mode(control_h, output_mode) {
+                error!("Can't set the output mode of {}: {e}.", self.name);
+            }
+        }
+    }
+
     pub fn get_value<H: HardwareBridge>(&self, bridge: &mut H) -> Result<Value, UpdateError> {
         match &self.control_h {
             Some(control_h) => bridge
                 .get_control_value(control_h)
+                .map(|raw| self.unit_value(&control_h.range, raw))
                 .map_err(UpdateError::Hardware),
             None => Err(UpdateError::NodeIsInvalid(self.name.clone())),
         }
@@ -115,6 +354,24 @@ impl IsValid for Control {
 
 impl ToNode for Control {
     fn to_node(mut self, app_graph: &mut AppGraph, hardware: &Hardware) -> Node {
+        for (setting, value) in [
+            ("ramp_up", &mut self.ramp_up),
+            ("ramp_down", &mut self.ramp_down),
+        ] {
+            if value.is_some_and(|value| !value.is_finite() || value <= 0.0) {
+                warn!("{setting} of {} must be a number above 0.", self.name);
+                value.take();
+            }
+        }
+
+        if self
+            .min_step
+            .is_some_and(|min_step| !min_step.is_finite() || min_step < 0.0)
+        {
+            warn!("min_step of {} must be a positive number.", self.name);
+            self.min_step.take();
+        }
+
         match &self.hardware_id {
             Some(hardware_id) => {
                 match hardware
@@ -123,7 +380,7 @@ impl ToNode for Control {
                     .find(|control_h| &control_h.hardware_id == hardware_id)
                 {
                     Some(control_h) => {
-                        if Rc::strong_count(control_h) > 1 {
+                        if node::is_control_bound(&app_graph.nodes, hardware_id) {
                             warn!(
                                 "Control to Node, hardware id \"{}\" is already use by another control. {}.",
                                 hardware_id, self.name
@@ -158,3 +415,64 @@ impl ToNode for Control {
         Node::new(NodeType::Control(self), app_graph)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::Control;
+
+    fn run(control: &mut Control, inputs: &[(u64, f64)]) -> Vec<f64> {
+        inputs
+            .iter()
+            .map(|(secs, input)| control.ramp(*input, Duration::from_secs(*secs)))
+            .collect()
+    }
+
+    #[test]
+    fn test_ramp() {
+        let mut control = Control::new("control".into(), None, None, true, None);
+        control.ramp_up = Some(10.0);
+        control.ramp_down = Some(20.0);
+
+        assert_eq!(
+            run(
+                &mut control,
+                &[
+                    (0, 30.0),
+                    (1, 100.0),
+                    (2, 100.0),
+                    (4, 100.0),
+                    (5, 0.0),
+                    (6, 0.0)
+                ]
+            ),
+            [30.0, 40.0, 50.0, 70.0, 50.0, 30.0]
+        );
+
+        // without limit, the input is followed
+        let mut control = Control::new("control".into(), None, None, true, None);
+        assert_eq!(
+            run(&mut control, &[(0, 30.0), (1, 100.0), (2, 0.0)]),
+            [30.0, 100.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_min_step() {
+        let mut control = Control::new("control".into(), None, None, true, None);
+        control.min_step = Some(5.0);
+
+        assert_eq!(
+            run(&mut control, &[(0, 30.0), (1, 33.0), (2, 27.0), (3, 36.0)]),
+            [30.0, 30.0, 30.0, 36.0]
+        );
+
+        // the ramp reaches its target, even closer than min_step
+        control.ramp_up = Some(4.0);
+        assert_eq!(
+            run(&mut control, &[(4, 45.0), (5, 43.0), (6, 45.0)]),
+            [40.0, 44.0, 45.0]
+        );
+    }
+}
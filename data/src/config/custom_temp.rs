@@ -1,4 +1,4 @@
-use hardware::{Hardware, Value};
+use hardware::Hardware;
 use light_enum::Values;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
@@ -29,14 +29,14 @@ impl CustomTemp {
         Self { name, kind, inputs }
     }
 
-    pub fn get_value(&self, values: &[Value]) -> Result<Value, UpdateError> {
+    pub fn get_value(&self, values: &[f64]) -> Result<f64, UpdateError> {
         let value = match self.kind {
-            CustomTempKind::Min => match values.iter().min() {
-                Some(min) => *min,
+            CustomTempKind::Min => match values.iter().copied().reduce(f64::min) {
+                Some(min) => min,
                 None => return Err(UpdateError::NoInputData),
             },
-            CustomTempKind::Max => match values.iter().max() {
-                Some(max) => *max,
+            CustomTempKind::Max => match values.iter().copied().reduce(f64::max) {
+                Some(max) => max,
                 None => return Err(UpdateError::NoInputData),
             },
             CustomTempKind::Average => {
@@ -44,7 +44,7 @@ impl CustomTemp {
                     return Err(UpdateError::NoInputData);
                 }
 
-                values.iter().sum::<i32>() / (values.len() as i32)
+                values.iter().sum::<f64>() / values.len() as f64
             }
         };
 
@@ -74,3 +74,30 @@ impl Display for CustomTempKind {
         write!(f, "{str}")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{CustomTemp, CustomTempKind};
+
+    #[test]
+    fn test_get_value() {
+        let values = [45.5, 40.5, -2.0];
+        let custom_temp = |kind| CustomTemp::new("custom".into(), kind, vec![]);
+
+        assert_eq!(
+            custom_temp(CustomTempKind::Average)
+                .get_value(&values)
+                .unwrap(),
+            28.0
+        );
+        assert_eq!(
+            custom_temp(CustomTempKind::Min).get_value(&values).unwrap(),
+            -2.0
+        );
+        assert_eq!(
+            custom_temp(CustomTempKind::Max).get_value(&values).unwrap(),
+            45.5
+        );
+        assert!(custom_temp(CustomTempKind::Max).get_value(&[]).is_err());
+    }
+}
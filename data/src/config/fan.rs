@@ -1,11 +1,11 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
     app_graph::AppGraph,
     node::{IsValid, Node, NodeType, ToNode},
     update::UpdateError,
 };
-use hardware::{HSensor, Hardware, HardwareBridge, Value};
+use hardware::{HSensor, Hardware, HardwareBridge, SensorValue};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, Eq)]
@@ -18,7 +18,7 @@ pub struct Fan {
 
     // E hardware.fans
     #[serde(skip)]
-    pub fan_h: Option<Rc<HSensor>>,
+    pub fan_h: Option<Arc<HSensor>>,
 }
 
 impl PartialEq for Fan {
@@ -28,7 +28,7 @@ impl PartialEq for Fan {
 }
 
 impl Fan {
-    pub fn get_value<H: HardwareBridge>(&self, bridge: &mut H) -> Result<Value, UpdateError> {
+    pub fn get_value<H: HardwareBridge>(&self, bridge: &mut H) -> Result<SensorValue, UpdateError> {
         match &self.fan_h {
             Some(fan_h) => bridge
                 .get_sensor_value(fan_h)
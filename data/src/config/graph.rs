@@ -1,6 +1,6 @@
 use std::{collections::BTreeSet, hash::Hash, vec};
 
-use hardware::{Hardware, Value};
+use hardware::{Hardware, SensorLimits};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -9,26 +9,29 @@ use crate::{
     update::UpdateError,
 };
 
-use super::utils::affine::Affine;
+use super::{hysteresis::Hysteresis, utils::affine::Affine};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct Coord {
-    pub temp: u8,
+    /// Can be fractional or negative. Integers of older configs are read as is.
+    pub temp: f64,
     pub percent: u8,
 }
 
 impl Hash for Coord {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.temp.hash(state);
+        self.temp.to_bits().hash(state);
     }
 }
 
 impl PartialEq for Coord {
     fn eq(&self, other: &Self) -> bool {
-        self.temp == other.temp
+        self.cmp(other).is_eq()
     }
 }
 
+impl Eq for Coord {}
+
 impl PartialOrd for Coord {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -37,7 +40,7 @@ impl PartialOrd for Coord {
 
 impl Ord for Coord {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.temp.cmp(&other.temp)
+        self.temp.total_cmp(&other.temp)
     }
 }
 
@@ -47,7 +50,7 @@ impl Coord {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Graph {
     // unique
     pub name: String,
@@ -57,12 +60,15 @@ pub struct Graph {
     #[serde(rename = "coord")]
     pub coords: BTreeSet<Coord>,
     pub input: Option<String>, // Temp or CustomTemp
+    #[serde(default, skip_serializing_if = "Hysteresis::is_unset")]
+    pub hysteresis: Hysteresis,
 }
 
 impl PartialEq for Graph {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name
             && self.input == other.input
+            && self.hysteresis == other.hysteresis
             && self.coords.len() == other.coords.len()
             && self
                 .coords
@@ -78,17 +84,18 @@ impl Default for Graph {
             name: Default::default(),
             coords: vec![
                 Coord {
-                    temp: 10,
+                    temp: 10.0,
                     percent: 10,
                 },
                 Coord {
-                    temp: 70,
+                    temp: 70.0,
                     percent: 100,
                 },
             ]
             .into_iter()
             .collect(),
             input: Default::default(),
+            hysteresis: Default::default(),
         }
     }
 }
@@ -108,6 +115,7 @@ impl ToNode for Graph {
         }
 
         self.coords = deduplicator.into_iter().collect();
+        self.hysteresis.sanitize();
 
         Node::new(NodeType::Graph(self), app_graph)
     }
@@ -120,12 +128,24 @@ impl IsValid for Graph {
 }
 
 impl Graph {
+    /// Keep the memory of the hysteresis before an edit of the graph,
+    /// unless the behavior changed.
+    pub fn inherit(&mut self, previous: &Self) {
+        if self == previous {
+            self.hysteresis.inherit(&previous.hysteresis);
+        }
+    }
+
     pub fn try_new_coord(
         &self,
         temp: &str,
         percent: &str,
     ) -> Result<Coord, Box<dyn std::error::Error>> {
-        let temp = temp.parse::<u8>()?;
+        let temp = temp.parse::<f64>()?;
+
+        if !temp.is_finite() {
+            return Err("Temp is not a number".into());
+        }
 
         let percent = percent.parse::<u8>()?;
 
@@ -145,34 +165,40 @@ impl Graph {
         Ok(coord)
     }
 
-    pub fn get_value(&self, value: Value) -> Result<Value, UpdateError> {
+    pub fn get_value(&mut self, value: f64) -> Result<f64, UpdateError> {
+        let rising = self.curve(value);
+        let falling = self.curve(value + self.hysteresis.falling_offset);
+
+        Ok(self.hysteresis.apply(value, rising, falling))
+    }
+
+    /// Output of the curve alone, without hysteresis.
+    fn curve(&self, value: f64) -> f64 {
         let dummy_coord = Coord {
-            temp: value as u8,
+            temp: value,
             percent: 0,
         };
 
-        let res = match self.coords.get(&dummy_coord) {
-            Some(c) => c.percent as Value,
+        match self.coords.get(&dummy_coord) {
+            Some(c) => c.percent.into(),
             None => {
                 let lower_bound = self.coords.range(..=dummy_coord).next_back();
                 let upper_bound = self.coords.range(dummy_coord..).next();
 
                 match (lower_bound, upper_bound) {
-                    (Some(coord), None) | (None, Some(coord)) => coord.percent as Value,
+                    (Some(coord), None) | (None, Some(coord)) => coord.percent.into(),
                     (Some(coord1), Some(coord2)) => Affine {
-                        xa: coord1.temp.into(),
+                        xa: coord1.temp,
                         ya: coord1.percent.into(),
-                        xb: coord2.temp.into(),
+                        xb: coord2.temp,
                         yb: coord2.percent.into(),
                     }
-                    .calcule(value) as Value,
+                    .calcule(value),
 
                     _ => panic!("internal error: no value for graph"),
                 }
             }
-        };
-
-        Ok(res)
+        }
     }
 
     pub fn add_coord(&mut self, new: Coord) {
@@ -181,6 +207,31 @@ impl Graph {
     pub fn remove_coord(&mut self, coord: &Coord) {
         self.coords.remove(coord);
     }
+    /// Move the hottest coord of the default curve to the highest temperature
+    /// the sensor should reach. A curve edited by the user is kept as is.
+    pub fn fit_to_limits(&mut self, limits: &SensorLimits) {
+        let default = Graph::default();
+        let is_default = self.coords.len() == default.coords.len()
+            && self
+                .coords
+                .iter()
+                .zip(&default.coords)
+                .all(|(a, b)| a.exact_same(b));
+
+        let Some(upper) = limits.upper() else {
+            return;
+        };
+
+        if !is_default {
+            return;
+        }
+
+        let first = *self.coords.first().unwrap();
+        let mut last = self.coords.pop_last().unwrap();
+        last.temp = upper.max(first.temp + 1.0);
+        self.coords.insert(last);
+    }
+
     pub fn replace_coord(&mut self, prev: &Coord, new: Coord) {
         self.remove_coord(prev);
         self.add_coord(new);
@@ -189,48 +240,144 @@ impl Graph {
 
 #[cfg(test)]
 mod test {
+    use hardware::SensorLimits;
+
     use crate::{config::graph::Coord, node::IsValid};
 
     use super::Graph;
 
     #[test]
     fn test_logic() {
-        let graph = Graph {
+        let mut graph = Graph {
             name: "name".into(),
             coords: vec![
                 Coord {
-                    temp: 10,
+                    temp: 10.0,
                     percent: 10,
                 },
                 Coord {
-                    temp: 20,
+                    temp: 20.0,
                     percent: 30,
                 },
                 Coord {
-                    temp: 25,
+                    temp: 25.0,
                     percent: 20,
                 },
                 Coord {
-                    temp: 30,
+                    temp: 30.0,
                     percent: 25,
                 },
                 Coord {
-                    temp: 40,
+                    temp: 40.0,
                     percent: 5,
                 },
             ]
             .into_iter()
             .collect(),
             input: None,
+            hysteresis: Default::default(),
         };
 
         graph.is_valid();
 
-        assert_eq!(graph.get_value(9).unwrap(), 10);
-        assert_eq!(graph.get_value(50).unwrap(), 5);
+        assert_eq!(graph.get_value(9.0).unwrap(), 10.0);
+        assert_eq!(graph.get_value(50.0).unwrap(), 5.0);
+
+        assert_eq!(graph.get_value(22.0).unwrap(), 26.0);
+        assert_eq!(graph.get_value(27.0).unwrap(), 22.0);
+        assert_eq!(graph.get_value(35.0).unwrap(), 15.0);
+
+        assert_eq!(graph.get_value(22.5).unwrap(), 25.0);
+    }
+
+    #[test]
+    fn test_below_zero() {
+        let mut graph = Graph {
+            name: "name".into(),
+            coords: vec![
+                Coord {
+                    temp: -20.0,
+                    percent: 0,
+                },
+                Coord {
+                    temp: 0.0,
+                    percent: 40,
+                },
+            ]
+            .into_iter()
+            .collect(),
+            input: None,
+            hysteresis: Default::default(),
+        };
+
+        assert_eq!(graph.get_value(-30.0).unwrap(), 0.0);
+        assert_eq!(graph.get_value(-10.0).unwrap(), 20.0);
+        assert_eq!(graph.get_value(-0.5).unwrap(), 39.0);
+
+        assert_eq!(graph.try_new_coord("-7.5", "10").unwrap().temp, -7.5);
+        assert!(graph.try_new_coord("-20", "10").is_err());
+        assert!(graph.try_new_coord("NaN", "10").is_err());
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let mut graph = Graph::default();
+        graph.hysteresis.deadband = 1.0;
+        graph.hysteresis.falling_offset = 3.0;
+
+        // 10 °C -> 10 %, 70 °C -> 100 %: 1.5 % per °C
+        assert_eq!(graph.get_value(50.0).unwrap(), 70.0);
+        assert_eq!(graph.get_value(50.5).unwrap(), 70.0);
+        assert_eq!(graph.get_value(52.0).unwrap(), 73.0);
+        assert_eq!(graph.get_value(50.0).unwrap(), 73.0);
+        assert_eq!(graph.get_value(48.0).unwrap(), 71.5);
+
+        // without hysteresis, the output follows the curve
+        let mut graph = Graph::default();
+        assert_eq!(graph.get_value(50.5).unwrap(), 70.75);
+    }
+
+    #[test]
+    fn test_inherit() {
+        let mut graph = Graph::default();
+        graph.hysteresis.falling_offset = 3.0;
+        assert_eq!(graph.get_value(52.0).unwrap(), 73.0);
+
+        // the copy of the user interface continues where the engine was
+        let mut edited = graph.clone();
+        edited.hysteresis.reset();
+        edited.inherit(&graph);
+        assert_eq!(edited.get_value(51.0).unwrap(), 73.0);
+
+        // a new curve starts over
+        let mut edited = graph.clone();
+        edited.hysteresis.reset();
+        edited.hysteresis.falling_offset = 2.0;
+        edited.inherit(&graph);
+        assert_eq!(edited.get_value(51.0).unwrap(), 71.5);
+    }
+
+    #[test]
+    fn test_fit_to_limits() {
+        let limits = SensorLimits {
+            max: Some(80.0),
+            crit: Some(95.0),
+            ..Default::default()
+        };
 
-        assert_eq!(graph.get_value(22).unwrap(), 26);
-        assert_eq!(graph.get_value(27).unwrap(), 22);
-        assert_eq!(graph.get_value(35).unwrap(), 15);
+        let mut graph = Graph::default();
+        graph.fit_to_limits(&limits);
+        assert_eq!(graph.coords.last().unwrap().temp, 95.0);
+        assert_eq!(graph.get_value(95.0).unwrap(), 100.0);
+
+        // a curve edited by the user is kept
+        let mut graph = Graph::default();
+        graph.add_coord(Coord {
+            temp: 50.0,
+            percent: 60,
+        });
+        let coords = graph.coords.clone();
+        graph.fit_to_limits(&limits);
+        assert_eq!(graph.coords, coords);
     }
 }
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+
+/// Memory of a behavior, so the fans don't hunt up and down when the
+/// temperature hovers around a knee of the curve.
+/// Both values at 0, the output follows the curve.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct Hysteresis {
+    /// The output changes only when the input has moved at least
+    /// this much since the last change, in °C.
+    #[serde(default)]
+    pub deadband: f64,
+    /// Rising, the output follows the curve. Falling, it follows the curve
+    /// shifted by this much, in °C: the fans slow down only when the
+    /// temperature is this much below where they sped up.
+    #[serde(default)]
+    pub falling_offset: f64,
+
+    /// Last input and output, when the output changed
+    #[serde(skip)]
+    last: Option<(f64, f64)>,
+}
+
+impl PartialEq for Hysteresis {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadband == other.deadband && self.falling_offset == other.falling_offset
+    }
+}
+
+impl Hysteresis {
+    pub fn new(deadband: f64, falling_offset: f64) -> Self {
+        Self {
+            deadband,
+            falling_offset,
+            last: None,
+        }
+    }
+
+    pub fn is_unset(&self) -> bool {
+        self.deadband == 0.0 && self.falling_offset == 0.0
+    }
+
+    /// Negative or non finite values of the config are reset to 0.
+    pub fn sanitize(&mut self) {
+        for value in [&mut self.deadband, &mut self.falling_offset] {
+            if !value.is_finite() || *value < 0.0 {
+                warn!("hysteresis {value} must be a positive number");
+                *value = 0.0;
+            }
+        }
+    }
+
+    /// Forget the last output, when the input is lost.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// Keep the last output of the behavior before an edit of the graph.
+    /// The caller checks that the behavior is unchanged.
+    pub fn inherit(&mut self, previous: &Self) {
+        self.last = previous.last;
+    }
+
+    /// Output for `input`, given the output of the curve at `input`
+    /// (`rising`) and at `input + falling_offset` (`falling`).
+    pub fn apply(&mut self, input: f64, rising: f64, falling: f64) -> f64 {
+        let output = match self.last {
+            Some((last_input, last_output)) if (input - last_input).abs() < self.deadband => {
+                return last_output;
+            }
+            // keep the last output while it lies between both curves
+            Some((_, last_output)) => last_output.clamp(rising.min(falling), rising.max(falling)),
+            None => rising,
+        };
+
+        self.last = Some((input, output));
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Hysteresis;
+
+    /// Linear from 40 °C (0 %) to 60 °C (100 %).
+    fn curve(temp: f64) -> f64 {
+        ((temp - 40.0) * 5.0).clamp(0.0, 100.0)
+    }
+
+    fn run(hysteresis: &mut Hysteresis, temps: &[f64]) -> Vec<f64> {
+        temps
+            .iter()
+            .map(|temp| {
+                hysteresis.apply(*temp, curve(*temp), curve(temp + hysteresis.falling_offset))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unset() {
+        let mut hysteresis = Hysteresis::default();
+        assert!(hysteresis.is_unset());
+        assert_eq!(
+            run(&mut hysteresis, &[50.0, 50.5, 49.5, 45.0]),
+            [50.0, 52.5, 47.5, 25.0]
+        );
+    }
+
+    #[test]
+    fn test_deadband() {
+        let mut hysteresis = Hysteresis::new(2.0, 0.0);
+        // hovering around 50 °C doesn't move the output
+        assert_eq!(
+            run(&mut hysteresis, &[50.0, 51.0, 49.0, 51.5, 52.0, 51.0, 49.5]),
+            [50.0, 50.0, 50.0, 50.0, 60.0, 60.0, 47.5]
+        );
+    }
+
+    #[test]
+    fn test_falling_offset() {
+        let mut hysteresis = Hysteresis::new(0.0, 4.0);
+        // rising follows the curve, falling waits 4 °C before slowing down
+        assert_eq!(
+            run(&mut hysteresis, &[50.0, 52.0, 50.0, 48.0, 47.0, 49.0, 53.0]),
+            [50.0, 60.0, 60.0, 60.0, 55.0, 55.0, 65.0]
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut hysteresis = Hysteresis::new(0.0, 4.0);
+        assert_eq!(run(&mut hysteresis, &[52.0, 50.0]), [60.0, 60.0]);
+
+        let mut edited = Hysteresis::new(0.0, 4.0);
+        edited.inherit(&hysteresis);
+        assert_eq!(run(&mut edited, &[49.0]), [60.0]);
+
+        hysteresis.reset();
+        assert_eq!(run(&mut hysteresis, &[50.0]), [50.0]);
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut hysteresis = Hysteresis::new(-1.0, f64::NAN);
+        hysteresis.sanitize();
+        assert!(hysteresis.is_unset());
+    }
+}
@@ -3,23 +3,25 @@ use crate::{
     node::{IsValid, Node, NodeType, ToNode},
     update::UpdateError,
 };
-use hardware::{Hardware, Value};
+use hardware::Hardware;
 use serde::{Deserialize, Serialize};
 
-use super::utils::affine::Affine;
+use super::{hysteresis::Hysteresis, utils::affine::Affine};
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Linear {
     pub name: String,
     #[serde(rename = "minTemp", alias = "min_temp")]
-    pub min_temp: u8,
+    pub min_temp: f64,
     #[serde(rename = "minSpeed", alias = "min_speed")]
     pub min_speed: u8,
     #[serde(rename = "maxTemp", alias = "max_temp")]
-    pub max_temp: u8,
+    pub max_temp: f64,
     #[serde(rename = "maxSpeed", alias = "max_speed")]
     pub max_speed: u8,
     pub input: Option<String>,
+    #[serde(default, skip_serializing_if = "Hysteresis::is_unset")]
+    pub hysteresis: Hysteresis,
 }
 
 impl IsValid for Linear {
@@ -29,24 +31,38 @@ impl IsValid for Linear {
 }
 
 impl Linear {
-    pub fn get_value(&self, value: Value) -> Result<Value, UpdateError> {
-        if value <= self.min_temp.into() {
-            return Ok(self.min_speed.into());
+    /// Keep the memory of the hysteresis before an edit of the graph,
+    /// unless the behavior changed.
+    pub fn inherit(&mut self, previous: &Self) {
+        if self == previous {
+            self.hysteresis.inherit(&previous.hysteresis);
         }
+    }
+
+    pub fn get_value(&mut self, value: f64) -> Result<f64, UpdateError> {
+        let rising = self.curve(value);
+        let falling = self.curve(value + self.hysteresis.falling_offset);
+
+        Ok(self.hysteresis.apply(value, rising, falling))
+    }
 
-        if value >= self.max_temp.into() {
-            return Ok(self.max_speed.into());
+    /// Output of the line alone, without hysteresis.
+    fn curve(&self, value: f64) -> f64 {
+        if value <= self.min_temp {
+            return self.min_speed.into();
         }
 
-        let res = Affine {
-            xa: self.min_temp.into(),
+        if value >= self.max_temp {
+            return self.max_speed.into();
+        }
+
+        Affine {
+            xa: self.min_temp,
             ya: self.min_speed.into(),
-            xb: self.max_temp.into(),
+            xb: self.max_temp,
             yb: self.max_speed.into(),
         }
-        .calcule(value) as Value;
-
-        Ok(res)
+        .calcule(value)
     }
 }
 
@@ -54,6 +70,11 @@ impl ToNode for Linear {
     fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
         let default = Self::default();
 
+        if !self.min_temp.is_finite() || !self.max_temp.is_finite() {
+            self.min_temp = default.min_temp;
+            self.max_temp = default.max_temp;
+        }
+
         if self.max_temp < self.min_temp {
             self.min_temp = default.min_temp;
             self.max_temp = default.max_temp;
@@ -64,19 +85,15 @@ impl ToNode for Linear {
             self.max_speed = default.max_speed;
         }
 
-        if self.min_temp > 100 {
-            self.min_temp = default.min_temp;
-        }
         if self.min_speed > 100 {
             self.min_speed = default.min_speed;
         }
-        if self.max_temp > 100 {
-            self.max_temp = default.max_temp;
-        }
         if self.max_speed > 100 {
             self.max_speed = default.max_speed;
         }
 
+        self.hysteresis.sanitize();
+
         Node::new(NodeType::Linear(self), app_graph)
     }
 }
@@ -85,11 +102,12 @@ impl Default for Linear {
     fn default() -> Self {
         Self {
             name: Default::default(),
-            min_temp: 10,
+            min_temp: 10.0,
             min_speed: 10,
-            max_temp: 70,
+            max_temp: 70.0,
             max_speed: 100,
             input: Default::default(),
+            hysteresis: Default::default(),
         }
     }
 }
@@ -98,23 +116,57 @@ impl Default for Linear {
 mod test {
     use crate::utils::init_test_logging;
 
-    use super::Linear;
+    use super::{Hysteresis, Linear};
 
     #[test]
     fn test_update() {
         init_test_logging();
 
-        let linear = Linear {
+        let mut linear = Linear {
             name: "Linear".into(),
-            min_temp: 10,
+            min_temp: 10.0,
             min_speed: 10,
-            max_temp: 70,
+            max_temp: 70.0,
+            max_speed: 100,
+            input: Some("temp1".into()),
+            hysteresis: Default::default(),
+        };
+
+        assert!(linear.get_value(9.0).unwrap() == 10.0);
+        assert!(linear.get_value(70.0).unwrap() == 100.0);
+        assert!(linear.get_value(40.0).unwrap() == 55.0);
+        assert!(linear.get_value(40.5).unwrap() == 55.75);
+    }
+
+    #[test]
+    fn test_below_zero() {
+        let mut linear = Linear {
+            name: "Linear".into(),
+            min_temp: -10.0,
+            min_speed: 0,
+            max_temp: 10.0,
             max_speed: 100,
             input: Some("temp1".into()),
+            hysteresis: Default::default(),
+        };
+
+        assert!(linear.get_value(-15.0).unwrap() == 0.0);
+        assert!(linear.get_value(-5.0).unwrap() == 25.0);
+        assert!(linear.get_value(0.0).unwrap() == 50.0);
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let mut linear = Linear {
+            hysteresis: Hysteresis::new(0.0, 5.0),
+            ..Default::default()
         };
 
-        assert!(linear.get_value(9).unwrap() == 10);
-        assert!(linear.get_value(70).unwrap() == 100);
-        assert!(linear.get_value(40).unwrap() == 55);
+        // 10 °C -> 10 %, 70 °C -> 100 %
+        assert!(linear.get_value(60.0).unwrap() == 85.0);
+        assert!(linear.get_value(56.0).unwrap() == 85.0);
+        assert!(linear.get_value(50.0).unwrap() == 77.5);
+        assert!(linear.get_value(52.0).unwrap() == 77.5);
+        assert!(linear.get_value(64.0).unwrap() == 91.0);
     }
 }
@@ -0,0 +1,168 @@
+use hardware::Hardware;
+use light_enum::Values;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::{
+    app_graph::AppGraph,
+    node::{IsValid, Node, NodeType, ToNode},
+    update::UpdateError,
+};
+
+/// Combine the outputs of several behaviors, like the max of
+/// the curve of the CPU and the one of the GPU.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Mix {
+    pub name: String,
+    #[serde(default)]
+    pub kind: MixKind,
+    pub inputs: Vec<String>,
+    /// Weight of each input, in the same order.
+    /// Only used by [`MixKind::WeightedSum`], 1 when missing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weights: Vec<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Values, Default, PartialEq, Eq)]
+pub enum MixKind {
+    #[default]
+    Max,
+    Min,
+    Average,
+    Sum,
+    WeightedSum,
+}
+
+impl Mix {
+    pub fn new(name: String, kind: MixKind, inputs: Vec<String>) -> Self {
+        Self {
+            name,
+            kind,
+            inputs,
+            weights: Vec::new(),
+        }
+    }
+
+    pub fn weight(&self, index: usize) -> f64 {
+        self.weights.get(index).copied().unwrap_or(1.0)
+    }
+
+    pub fn set_weight(&mut self, index: usize, weight: f64) {
+        if self.weights.len() <= index {
+            self.weights.resize(index + 1, 1.0);
+        }
+        self.weights[index] = weight;
+    }
+
+    /// Keep the weights with their input. A list of the same length
+    /// is a rename, which doesn't move the inputs.
+    pub fn set_inputs(&mut self, inputs: Vec<String>) {
+        if inputs.len() != self.inputs.len() {
+            self.weights = inputs
+                .iter()
+                .map(|name| match self.inputs.iter().position(|n| n == name) {
+                    Some(index) => self.weight(index),
+                    None => 1.0,
+                })
+                .collect();
+        }
+        self.inputs = inputs;
+    }
+
+    /// `values` are in the order of the inputs.
+    pub fn get_value(&self, values: &[f64]) -> Result<f64, UpdateError> {
+        if values.is_empty() {
+            return Err(UpdateError::NoInputData);
+        }
+
+        let value = match self.kind {
+            MixKind::Max => values.iter().copied().fold(f64::MIN, f64::max),
+            MixKind::Min => values.iter().copied().fold(f64::MAX, f64::min),
+            MixKind::Average => values.iter().sum::<f64>() / values.len() as f64,
+            MixKind::Sum => values.iter().sum(),
+            MixKind::WeightedSum => values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| self.weight(index) * value)
+                .sum(),
+        };
+
+        Ok(value)
+    }
+}
+
+impl IsValid for Mix {
+    fn is_valid(&self) -> bool {
+        !self.inputs.is_empty()
+    }
+}
+
+impl ToNode for Mix {
+    fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
+        for weight in &mut self.weights {
+            if !weight.is_finite() {
+                warn!("weight {weight} must be a number");
+                *weight = 1.0;
+            }
+        }
+
+        Node::new(NodeType::Mix(self), app_graph)
+    }
+}
+
+impl Display for MixKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            MixKind::Max => fl!("max"),
+            MixKind::Min => fl!("min"),
+            MixKind::Average => fl!("average"),
+            MixKind::Sum => fl!("sum"),
+            MixKind::WeightedSum => fl!("weighted_sum"),
+        };
+        write!(f, "{str}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mix, MixKind};
+
+    #[test]
+    fn test_get_value() {
+        let values = [30.0, 80.0, 40.0];
+        let mix = |kind| Mix::new("mix".into(), kind, vec![]);
+
+        assert_eq!(mix(MixKind::Max).get_value(&values).unwrap(), 80.0);
+        assert_eq!(mix(MixKind::Min).get_value(&values).unwrap(), 30.0);
+        assert_eq!(mix(MixKind::Average).get_value(&values).unwrap(), 50.0);
+        assert_eq!(mix(MixKind::Sum).get_value(&values).unwrap(), 150.0);
+        assert!(mix(MixKind::Max).get_value(&[]).is_err());
+
+        let mut weighted = mix(MixKind::WeightedSum);
+        weighted.weights = vec![0.5, 0.25];
+        // the last weight is missing
+        assert_eq!(weighted.get_value(&values).unwrap(), 75.0);
+    }
+
+    #[test]
+    fn test_set_inputs() {
+        let mut mix = Mix::new(
+            "mix".into(),
+            MixKind::WeightedSum,
+            vec!["cpu".into(), "gpu".into(), "disk".into()],
+        );
+        mix.weights = vec![0.5, 0.3, 0.2];
+
+        // renamed
+        mix.set_inputs(vec!["cpu".into(), "graphic".into(), "disk".into()]);
+        assert_eq!(mix.weights, [0.5, 0.3, 0.2]);
+
+        // removed
+        mix.set_inputs(vec!["cpu".into(), "disk".into()]);
+        assert_eq!(mix.weights, [0.5, 0.2]);
+
+        // added
+        mix.set_inputs(vec!["cpu".into(), "disk".into(), "gpu".into()]);
+        assert_eq!(mix.weights, [0.5, 0.2, 1.0]);
+    }
+}
@@ -1,9 +1,15 @@
+pub mod command_temp;
 pub mod control;
 pub mod custom_temp;
 pub mod fan;
 pub mod flat;
 pub mod graph;
+pub mod hysteresis;
 pub mod linear;
+pub mod mix;
+pub mod pid;
+pub mod sensor;
+pub mod smooth;
 pub mod target;
 pub mod temp;
 
@@ -15,15 +21,17 @@ mod serde_test;
 use crate::{
     app_graph::AppGraph,
     config::{
-        control::Control, custom_temp::CustomTemp, fan::Fan, flat::Flat, graph::Graph,
-        linear::Linear, target::Target, temp::Temp,
+        command_temp::CommandTemp, control::Control, custom_temp::CustomTemp, fan::Fan, flat::Flat,
+        graph::Graph, linear::Linear, mix::Mix, pid::Pid, sensor::Sensor, smooth::Smooth,
+        target::Target, temp::Temp,
     },
     node::{self},
 };
 
+use hardware::Hardware;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct Config {
     #[serde(default, rename = "Control")]
     pub controls: Vec<Control>,
@@ -31,8 +39,14 @@ pub struct Config {
     pub fans: Vec<Fan>,
     #[serde(default, rename = "Temp")]
     pub temps: Vec<Temp>,
+    #[serde(default, rename = "Sensor")]
+    pub sensors: Vec<Sensor>,
+    #[serde(default, rename = "CommandTemp")]
+    pub command_temps: Vec<CommandTemp>,
     #[serde(default, rename = "CustomTemp")]
     pub custom_temps: Vec<CustomTemp>,
+    #[serde(default, rename = "Smooth")]
+    pub smooths: Vec<Smooth>,
     #[serde(default, rename = "Graph")]
     pub graphs: Vec<Graph>,
     #[serde(default, rename = "Flat")]
@@ -41,6 +55,10 @@ pub struct Config {
     pub linears: Vec<Linear>,
     #[serde(default, rename = "Target")]
     pub targets: Vec<Target>,
+    #[serde(default, rename = "Pid")]
+    pub pids: Vec<Pid>,
+    #[serde(default, rename = "Mix")]
+    pub mixes: Vec<Mix>,
 }
 
 impl Config {
@@ -51,15 +69,50 @@ impl Config {
                 node::NodeType::Control(control) => config.controls.push(control.clone()),
                 node::NodeType::Fan(fan) => config.fans.push(fan.clone()),
                 node::NodeType::Temp(temp) => config.temps.push(temp.clone()),
+                node::NodeType::Sensor(sensor) => config.sensors.push(sensor.clone()),
+                node::NodeType::CommandTemp(command_temp) => {
+                    config.command_temps.push(command_temp.clone())
+                }
                 node::NodeType::CustomTemp(custom_temp) => {
                     config.custom_temps.push(custom_temp.clone())
                 }
+                node::NodeType::Smooth(smooth) => config.smooths.push(smooth.clone()),
                 node::NodeType::Graph(graph) => config.graphs.push(graph.clone()),
                 node::NodeType::Flat(flat) => config.flats.push(flat.clone()),
                 node::NodeType::Linear(linear, ..) => config.linears.push(linear.clone()),
                 node::NodeType::Target(target, ..) => config.targets.push(target.clone()),
+                node::NodeType::Pid(pid) => config.pids.push(pid.clone()),
+                node::NodeType::Mix(mix) => config.mixes.push(mix.clone()),
             }
         }
         config
     }
+
+    /// Replace the hardware ids given by previous versions by the current ones.
+    /// Return true if an id was replaced.
+    pub fn migrate_hardware_ids(&mut self, hardware: &Hardware) -> bool {
+        let mut migrated = false;
+
+        let hardware_ids = self
+            .controls
+            .iter_mut()
+            .flat_map(|control| [&mut control.hardware_id, &mut control.fan])
+            .chain(self.fans.iter_mut().map(|fan| &mut fan.hardware_id))
+            .chain(self.temps.iter_mut().map(|temp| &mut temp.hardware_id))
+            .chain(
+                self.sensors
+                    .iter_mut()
+                    .map(|sensor| &mut sensor.hardware_id),
+            );
+
+        for hardware_id in hardware_ids.flatten() {
+            if let Some(new_id) = hardware.legacy_ids.get(hardware_id.as_str()) {
+                info!("Hardware id \"{hardware_id}\" migrated to \"{new_id}\".");
+                *hardware_id = new_id.clone();
+                migrated = true;
+            }
+        }
+
+        migrated
+    }
 }
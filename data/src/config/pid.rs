@@ -0,0 +1,295 @@
+use std::time::Duration;
+
+use hardware::Hardware;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_graph::AppGraph,
+    node::{IsValid, Node, NodeType, ToNode},
+    update::UpdateError,
+};
+
+/// Hold the input at a setpoint temperature: the speed rises while the
+/// input is above it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pid {
+    pub name: String,
+    pub setpoint: f64,
+    /// In % per °C above the setpoint
+    pub kp: f64,
+    /// In % per °C above the setpoint, per second
+    pub ki: f64,
+    /// In % per °C/s of rise of the input
+    pub kd: f64,
+    #[serde(rename = "minSpeed", alias = "min_speed")]
+    pub min_speed: u8,
+    #[serde(rename = "maxSpeed", alias = "max_speed")]
+    pub max_speed: u8,
+    pub input: Option<String>,
+
+    #[serde(skip)]
+    pub state: Option<PidState>,
+}
+
+/// What [`Pid`] remembers of its last call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidState {
+    pub time: Duration,
+    pub input: f64,
+    pub integral: f64,
+}
+
+impl PartialEq for Pid {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.setpoint == other.setpoint
+            && self.kp == other.kp
+            && self.ki == other.ki
+            && self.kd == other.kd
+            && self.min_speed == other.min_speed
+            && self.max_speed == other.max_speed
+            && self.input == other.input
+    }
+}
+
+impl Pid {
+    /// Forget the integral and the previous input.
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+
+    /// Keep the state of this PID before an edit of the graph,
+    /// unless its config changed.
+    pub fn inherit(&mut self, previous: &Self) {
+        if self == previous {
+            self.state = previous.state;
+        }
+    }
+
+    /// `now` must not go backward between two calls.
+    pub fn get_value(&mut self, value: f64, now: Duration) -> Result<f64, UpdateError> {
+        let min = f64::from(self.min_speed);
+        let max = f64::from(self.max_speed);
+        let error = value - self.setpoint;
+
+        let (elapsed, derivative, integral) = match self.state {
+            Some(state) => {
+                let elapsed = now.saturating_sub(state.time).as_secs_f64();
+                // on the input rather than the error, so a change of
+                // the setpoint doesn't kick the output
+                let derivative = if elapsed > 0.0 {
+                    (value - state.input) / elapsed
+                } else {
+                    0.0
+                };
+                (elapsed, derivative, state.integral)
+            }
+            None => (0.0, 0.0, 0.0),
+        };
+
+        let proportional = self.kp * error;
+        let derivative = self.kd * derivative;
+
+        // anti-windup: the integral stops growing while the output
+        // is saturated in the same direction
+        let saturated = proportional + integral + derivative;
+        let integral = if (saturated >= max && error > 0.0) || (saturated <= min && error < 0.0) {
+            integral
+        } else {
+            integral + self.ki * error * elapsed
+        }
+        .clamp(min, max);
+
+        self.state = Some(PidState {
+            time: now,
+            input: value,
+            integral,
+        });
+
+        Ok((proportional + integral + derivative).clamp(min, max))
+    }
+}
+
+impl IsValid for Pid {
+    fn is_valid(&self) -> bool {
+        self.input.is_some()
+    }
+}
+
+impl ToNode for Pid {
+    fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
+        let default = Self::default();
+
+        if !self.setpoint.is_finite() {
+            self.setpoint = default.setpoint;
+        }
+
+        for gain in [&mut self.kp, &mut self.ki, &mut self.kd] {
+            if !gain.is_finite() || *gain < 0.0 {
+                warn!("gain {gain} must be a positive number");
+                *gain = 0.0;
+            }
+        }
+
+        if self.max_speed < self.min_speed {
+            self.min_speed = default.min_speed;
+            self.max_speed = default.max_speed;
+        }
+
+        if self.min_speed > 100 {
+            self.min_speed = default.min_speed;
+        }
+        if self.max_speed > 100 {
+            self.max_speed = default.max_speed;
+        }
+
+        self.reset();
+
+        Node::new(NodeType::Pid(self), app_graph)
+    }
+}
+
+impl Default for Pid {
+    fn default() -> Self {
+        Self {
+            name: Default::default(),
+            setpoint: 50.0,
+            kp: 10.0,
+            ki: 0.2,
+            kd: 0.0,
+            min_speed: 0,
+            max_speed: 100,
+            input: Default::default(),
+            state: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::Pid;
+
+    /// A heat source, cooled by a fan
+    struct Plant {
+        temp: f64,
+    }
+
+    impl Plant {
+        const AMBIENT: f64 = 25.0;
+        /// In W
+        const HEAT: f64 = 100.0;
+        /// In J/°C
+        const THERMAL_MASS: f64 = 200.0;
+
+        /// From 225 °C with the fan stopped to 45 °C at full speed
+        fn step(&mut self, speed: f64, dt: f64) {
+            let conductance = 0.5 + 4.5 * speed / 100.0;
+            self.temp +=
+                (Self::HEAT - conductance * (self.temp - Self::AMBIENT)) * dt / Self::THERMAL_MASS;
+        }
+    }
+
+    /// Temperatures and outputs, each `dt` seconds
+    fn run(
+        pid: &mut Pid,
+        plant: &mut Plant,
+        start: Duration,
+        dt: f64,
+        steps: usize,
+    ) -> Vec<(f64, f64)> {
+        (1..=steps)
+            .map(|i| {
+                let now = start + Duration::from_secs_f64(dt * i as f64);
+                let output = pid.get_value(plant.temp, now).unwrap();
+                plant.step(output, dt);
+                (plant.temp, output)
+            })
+            .collect()
+    }
+
+    fn pid(setpoint: f64) -> Pid {
+        Pid {
+            name: "pid".into(),
+            setpoint,
+            kp: 10.0,
+            ki: 0.2,
+            kd: 0.0,
+            input: Some("temp".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hold_setpoint() {
+        for dt in [0.5, 1.0, 2.0] {
+            let mut pid = pid(55.0);
+            let mut plant = Plant { temp: 40.0 };
+
+            let steps = (1200.0 / dt) as usize;
+            let samples = run(&mut pid, &mut plant, Duration::ZERO, dt, steps);
+
+            let max_temp = samples.iter().map(|(temp, _)| *temp).fold(0.0, f64::max);
+            assert!(max_temp < 60.0, "overshoot to {max_temp} with dt {dt}");
+
+            let steady = &samples[samples.len() - (300.0 / dt) as usize..];
+            for (temp, output) in steady {
+                assert!((temp - 55.0).abs() < 0.2, "{temp} with dt {dt}");
+                // the speed which holds 55 °C
+                assert!((output - 63.3).abs() < 1.0, "{output} with dt {dt}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_anti_windup() {
+        // out of reach: the fan at full speed gives 45 °C
+        let mut pid = pid(40.0);
+        let mut plant = Plant { temp: 45.0 };
+        let samples = run(&mut pid, &mut plant, Duration::ZERO, 1.0, 600);
+        assert!(samples[300..].iter().all(|(_, output)| *output == 100.0));
+
+        // the output leaves saturation right away
+        pid.setpoint = 60.0;
+        let samples = run(&mut pid, &mut plant, Duration::from_secs(600), 1.0, 60);
+        assert!(samples[0].1 < 50.0, "{}", samples[0].1);
+        assert!(samples.iter().all(|(temp, _)| *temp < 62.0));
+    }
+
+    #[test]
+    fn test_derivative() {
+        let mut pid = Pid {
+            kp: 0.0,
+            ki: 0.0,
+            kd: 10.0,
+            ..pid(50.0)
+        };
+
+        // a rise of 1 °C/s, whatever the sample time
+        for dt in [0.5, 1.0, 3.0] {
+            pid.reset();
+            let outputs = (0..4)
+                .map(|i| {
+                    let time = dt * i as f64;
+                    pid.get_value(40.0 + time, Duration::from_secs_f64(time))
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(outputs, [0.0, 10.0, 10.0, 10.0], "dt {dt}");
+        }
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut pid = pid(50.0);
+
+        pid.get_value(55.0, Duration::ZERO).unwrap();
+        // P and I
+        assert_eq!(pid.get_value(55.0, Duration::from_secs(10)).unwrap(), 60.0);
+
+        pid.reset();
+        // P only
+        assert_eq!(pid.get_value(55.0, Duration::from_secs(20)).unwrap(), 50.0);
+    }
+}
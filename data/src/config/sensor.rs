@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use hardware::{HSensor, Hardware, HardwareBridge, SensorKind, SensorValue};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_graph::AppGraph,
+    node::{IsValid, Node, NodeType, ToNode},
+    update::UpdateError,
+};
+
+/// A voltage, current, power, energy or humidity, in the unit of its kind.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq)]
+pub struct Sensor {
+    pub name: String,
+    #[serde(rename = "id")]
+    pub hardware_id: Option<String>,
+
+    #[serde(skip)]
+    pub sensor_h: Option<Arc<HSensor>>,
+}
+
+impl PartialEq for Sensor {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.hardware_id == other.hardware_id
+    }
+}
+
+impl Sensor {
+    pub fn get_value<H: HardwareBridge>(&self, bridge: &mut H) -> Result<SensorValue, UpdateError> {
+        match &self.sensor_h {
+            Some(sensor_h) => bridge
+                .get_sensor_value(sensor_h)
+                .map_err(UpdateError::Hardware),
+            None => Err(UpdateError::NodeIsInvalid(self.name.clone())),
+        }
+    }
+
+    /// What the bound sensor measures.
+    pub fn kind(&self) -> Option<SensorKind> {
+        self.sensor_h.as_ref().and_then(|sensor_h| sensor_h.kind)
+    }
+}
+
+impl IsValid for Sensor {
+    fn is_valid(&self) -> bool {
+        self.hardware_id.is_some() && self.sensor_h.is_some()
+    }
+}
+
+impl ToNode for Sensor {
+    fn to_node(mut self, app_graph: &mut AppGraph, hardware: &Hardware) -> Node {
+        match &self.hardware_id {
+            Some(hardware_id) => {
+                match hardware
+                    .sensors
+                    .iter()
+                    .find(|sensor_h| &sensor_h.hardware_id == hardware_id)
+                {
+                    Some(sensor_h) => self.sensor_h = Some(sensor_h.clone()),
+                    None => {
+                        warn!(
+                            "Sensor to Node, hardware_id not found. {hardware_id} from config not found. Fall back to no id"
+                        );
+                        self.hardware_id.take();
+                        self.sensor_h.take();
+                    }
+                }
+            }
+            None => {
+                if self.sensor_h.is_some() {
+                    warn!("Sensor to Node: inconsistent internal index");
+                    self.sensor_h.take();
+                }
+            }
+        }
+
+        Node::new(NodeType::Sensor(self), app_graph)
+    }
+}
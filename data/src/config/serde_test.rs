@@ -2,7 +2,7 @@
 #![allow(unused_imports)]
 
 use const_format::formatcp;
-use hardware::{HControl, HSensor, Hardware};
+use hardware::{HControl, HSensor, Hardware, OutputMode};
 use serial_test::serial;
 use std::fmt::Debug;
 use std::fs::{self, File};
@@ -13,13 +13,19 @@ use crate::config::Config;
 
 use crate::settings::Settings;
 
-use super::control::Control;
+use super::command_temp::{CommandTemp, Parse};
+use super::control::{Calibration, Control};
 use super::custom_temp::{CustomTemp, CustomTempKind};
 
 use super::fan::Fan;
 use super::flat::Flat;
 use super::graph::{Coord, Graph};
+use super::hysteresis::Hysteresis;
 use super::linear::Linear;
+use super::mix::{Mix, MixKind};
+use super::pid::Pid;
+use super::sensor::Sensor;
+use super::smooth::{Smooth, SmoothKind};
 use super::target::Target;
 use super::temp::Temp;
 
@@ -142,6 +148,18 @@ fn config1() -> Config {
             hardware_id: Some("temp".into()),
             temp_h: None,
         }],
+        sensors: vec![Sensor {
+            name: "Power".into(),
+            hardware_id: Some("amdgpu/pci:0000:03:00.0/power1_average".into()),
+            sensor_h: None,
+        }],
+        command_temps: vec![CommandTemp::new(
+            "CommandTemp".into(),
+            vec!["ipmitool".into(), "sdr".into()],
+            Parse::Regex {
+                pattern: r"CPU Temp\s*\|\s*(\d+)".into(),
+            },
+        )],
         fans: vec![Fan {
             name: "Fan".into(),
             hardware_id: None,
@@ -152,21 +170,28 @@ fn config1() -> Config {
             CustomTempKind::Max,
             vec!["temp1".into(), "temp2".into()],
         )],
+        smooths: vec![Smooth::new(
+            "Smooth".into(),
+            SmoothKind::Average,
+            3.0,
+            Some("CustomTemp".into()),
+        )],
         graphs: vec![Graph {
             name: "Graph".into(),
             coords: vec![
                 Coord {
-                    temp: 10,
+                    temp: 10.0,
                     percent: 10,
                 },
                 Coord {
-                    temp: 50,
+                    temp: 50.0,
                     percent: 30,
                 },
             ]
             .into_iter()
             .collect(),
             input: Some("max".into()),
+            hysteresis: Hysteresis::new(2.0, 3.0),
         }],
         flats: vec![Flat {
             name: "flat1".into(),
@@ -174,20 +199,185 @@ fn config1() -> Config {
         }],
         linears: vec![Linear {
             name: "Linear".into(),
-            min_temp: 10,
+            min_temp: 10.0,
             min_speed: 10,
-            max_temp: 70,
+            max_temp: 70.0,
             max_speed: 100,
             input: Some("temp1".into()),
+            hysteresis: Default::default(),
         }],
         targets: vec![Target {
             name: "Target".into(),
-            idle_temp: 40,
+            idle_temp: 40.0,
             idle_speed: 10,
-            load_temp: 70,
+            load_temp: 70.0,
             load_speed: 100,
             input: Some("temp3".into()),
             idle_has_been_reatch: false,
         }],
+        pids: vec![Pid {
+            name: "Pid".into(),
+            setpoint: 45.0,
+            input: Some("temp1".into()),
+            ..Default::default()
+        }],
+        mixes: vec![Mix {
+            name: "Mix".into(),
+            kind: MixKind::WeightedSum,
+            inputs: vec!["Graph".into(), "Linear".into()],
+            weights: vec![0.7, 0.3],
+        }],
+    }
+}
+
+#[test]
+fn test_migrate_hardware_ids() {
+    let mut hardware = Hardware::default();
+    hardware.legacy_ids.insert(
+        "edge-amdgpu-pci-0300-temp1_input".into(),
+        "amdgpu/pci:0000:03:00.0/temp1_input".into(),
+    );
+    hardware.legacy_ids.insert(
+        "fan2-nct6775-isa-0290-fan2_input".into(),
+        "nct6775/platform:nct6775.656/fan2_input".into(),
+    );
+
+    let mut fan_control = Control::new(
+        "fan".into(),
+        Some("nct6775/platform:nct6775.656/pwm2".into()),
+        None,
+        true,
+        None,
+    );
+    fan_control.fan = Some("fan2-nct6775-isa-0290-fan2_input".into());
+
+    let mut config = Config {
+        controls: vec![fan_control],
+        temps: vec![
+            Temp {
+                name: "gpu".into(),
+                hardware_id: Some("edge-amdgpu-pci-0300-temp1_input".into()),
+                temp_h: None,
+            },
+            Temp {
+                name: "cpu".into(),
+                hardware_id: Some("k10temp/pci:0000:00:18.3/temp1_input".into()),
+                temp_h: None,
+            },
+        ],
+        ..Default::default()
+    };
+
+    assert!(config.migrate_hardware_ids(&hardware));
+    assert_eq!(
+        config.temps[0].hardware_id.as_deref(),
+        Some("amdgpu/pci:0000:03:00.0/temp1_input")
+    );
+    assert_eq!(
+        config.temps[1].hardware_id.as_deref(),
+        Some("k10temp/pci:0000:00:18.3/temp1_input")
+    );
+    assert_eq!(
+        config.controls[0].fan.as_deref(),
+        Some("nct6775/platform:nct6775.656/fan2_input")
+    );
+
+    // already migrated
+    assert!(!config.migrate_hardware_ids(&hardware));
+}
+
+#[test]
+fn test_integer_temps() {
+    let content = r#"
+[[Graph]]
+name = "Graph"
+input = "CPU"
+
+[[Graph.coord]]
+temp = -5
+percent = 0
+
+[[Graph.coord]]
+temp = 62.5
+percent = 100
+
+[[Linear]]
+name = "Linear"
+minTemp = 10
+minSpeed = 10
+maxTemp = 70
+maxSpeed = 100
+
+[[Target]]
+name = "Target"
+idleTemp = 40
+idleSpeed = 10
+loadTemp = 70.5
+loadSpeed = 100
+"#;
+    let config = toml::from_str::<Config>(content).unwrap();
+
+    let temps = config.graphs[0]
+        .coords
+        .iter()
+        .map(|coord| coord.temp)
+        .collect::<Vec<_>>();
+    assert_eq!(temps, [-5.0, 62.5]);
+    assert_eq!(config.linears[0].min_temp, 10.0);
+    assert_eq!(config.linears[0].max_temp, 70.0);
+    assert_eq!(config.targets[0].idle_temp, 40.0);
+    assert_eq!(config.targets[0].load_temp, 70.5);
+    assert!(config.graphs[0].hysteresis.is_unset());
+
+    let content = toml::to_string(&config).unwrap();
+    assert_eq!(toml::from_str::<Config>(&content).unwrap(), config);
+}
+
+#[test]
+fn test_control_fields() {
+    let content = r#"
+[[Control]]
+name = "Control"
+active = true
+outputMode = "dc"
+rampUp = 10
+rampDown = 5
+minStep = 2
+calibration = { minStart = 90, minStay = 60, maxEffective = 220 }
+
+# written by previous versions
+[[Control]]
+name = "Legacy"
+active = true
+output_mode = "pwm"
+ramp_up = 10
+ramp_down = 5
+min_step = 2
+calibration = { min_start = 90, min_stay = 60, max_effective = 220 }
+"#;
+    let config = toml::from_str::<Config>(content).unwrap();
+
+    assert_eq!(config.controls[0].output_mode, Some(OutputMode::Dc));
+    assert_eq!(config.controls[1].output_mode, Some(OutputMode::Pwm));
+    for control in &config.controls {
+        assert_eq!(control.ramp_up, Some(10.0));
+        assert_eq!(control.ramp_down, Some(5.0));
+        assert_eq!(control.min_step, Some(2.0));
+        assert_eq!(
+            control.calibration,
+            Some(Calibration {
+                min_start: 90,
+                min_stay: 60,
+                max_effective: 220,
+            })
+        );
+    }
+
+    let content = toml::to_string(&config).unwrap();
+    for field in ["outputMode", "rampUp", "rampDown", "minStep"] {
+        assert!(content.contains(field));
+    }
+    for field in ["output_mode", "ramp_up", "ramp_down", "min_step"] {
+        assert!(!content.contains(field));
     }
 }
@@ -0,0 +1,261 @@
+use hardware::Hardware;
+use light_enum::Values;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt::Display, time::Duration};
+
+use crate::{
+    app_graph::AppGraph,
+    node::{IsValid, Node, NodeType, ToNode},
+    update::UpdateError,
+};
+
+/// Smooth the value of a temperature over a time window,
+/// so short spikes don't reach the behaviors.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Smooth {
+    pub name: String,
+    #[serde(default)]
+    pub kind: SmoothKind,
+    /// In seconds. For [`SmoothKind::Exponential`], this is the time constant.
+    #[serde(default = "default_window")]
+    pub window: f64,
+    /// Only used by [`SmoothKind::Percentile`], between 0 and 100.
+    #[serde(default = "default_percentile")]
+    pub percentile: f64,
+    pub input: Option<String>,
+    /// (time, value), oldest first.
+    #[serde(skip)]
+    samples: VecDeque<(Duration, f64)>,
+    #[serde(skip)]
+    ema: Option<(Duration, f64)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Values, Default, PartialEq, Eq)]
+pub enum SmoothKind {
+    #[default]
+    Exponential,
+    Average,
+    Max,
+    Percentile,
+}
+
+fn default_window() -> f64 {
+    5.0
+}
+
+fn default_percentile() -> f64 {
+    90.0
+}
+
+impl Default for Smooth {
+    fn default() -> Self {
+        Self::new(
+            Default::default(),
+            Default::default(),
+            default_window(),
+            None,
+        )
+    }
+}
+
+impl PartialEq for Smooth {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.kind == other.kind
+            && self.window == other.window
+            && self.percentile == other.percentile
+            && self.input == other.input
+    }
+}
+
+impl Smooth {
+    pub fn new(name: String, kind: SmoothKind, window: f64, input: Option<String>) -> Self {
+        Self {
+            name,
+            kind,
+            window,
+            percentile: default_percentile(),
+            input,
+            samples: VecDeque::new(),
+            ema: None,
+        }
+    }
+
+    /// Forget the past values, after a change of the parameters.
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.ema = None;
+    }
+
+    /// Keep the past values of this node before an edit of the graph,
+    /// unless its config changed.
+    pub fn inherit(&mut self, previous: &Self) {
+        if self == previous {
+            self.samples = previous.samples.clone();
+            self.ema = previous.ema;
+        }
+    }
+
+    fn window(&self) -> Duration {
+        Duration::from_secs_f64(self.window)
+    }
+
+    /// `now` must not go backward between two calls.
+    pub fn get_value(&mut self, value: f64, now: Duration) -> Result<f64, UpdateError> {
+        if let SmoothKind::Exponential = self.kind {
+            let smoothed = match self.ema {
+                Some((last, ema)) if self.window > 0.0 => {
+                    let dt = now.saturating_sub(last).as_secs_f64();
+                    let alpha = 1.0 - (-dt / self.window).exp();
+                    ema + alpha * (value - ema)
+                }
+                _ => value,
+            };
+            self.ema = Some((now, smoothed));
+            return Ok(smoothed);
+        }
+
+        self.samples.push_back((now, value));
+
+        // keep at least the newest value
+        let window = self.window();
+        while self.samples.len() > 1
+            && self
+                .samples
+                .front()
+                .is_some_and(|(time, _)| now.saturating_sub(*time) > window)
+        {
+            self.samples.pop_front();
+        }
+
+        let values = self.samples.iter().map(|(_, value)| *value);
+
+        let value = match self.kind {
+            SmoothKind::Exponential => unreachable!(),
+            SmoothKind::Average => values.sum::<f64>() / self.samples.len() as f64,
+            SmoothKind::Max => values.reduce(f64::max).ok_or(UpdateError::NoInputData)?,
+            SmoothKind::Percentile => {
+                let mut values = values.collect::<Vec<_>>();
+                values.sort_by(f64::total_cmp);
+                // nearest rank
+                let rank = (self.percentile / 100.0 * values.len() as f64).ceil() as usize;
+                values[rank.clamp(1, values.len()) - 1]
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+impl IsValid for Smooth {
+    fn is_valid(&self) -> bool {
+        self.input.is_some()
+    }
+}
+
+impl ToNode for Smooth {
+    fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
+        if !self.window.is_finite() || self.window < 0.0 {
+            warn!("window {} must be a positive number", self.window);
+            self.window = default_window();
+        }
+
+        if !(0.0..=100.0).contains(&self.percentile) {
+            warn!("percentile {} must be between 0 and 100", self.percentile);
+            self.percentile = default_percentile();
+        }
+
+        self.reset();
+
+        Node::new(NodeType::Smooth(self), app_graph)
+    }
+}
+
+impl Display for SmoothKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            SmoothKind::Exponential => fl!("exponential"),
+            SmoothKind::Average => fl!("average"),
+            SmoothKind::Max => fl!("max"),
+            SmoothKind::Percentile => fl!("percentile"),
+        };
+        write!(f, "{str}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{Smooth, SmoothKind};
+
+    fn run(smooth: &mut Smooth, samples: &[(u64, f64)]) -> Vec<f64> {
+        samples
+            .iter()
+            .map(|(ms, value)| {
+                smooth
+                    .get_value(*value, Duration::from_millis(*ms))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    const SPIKE: [(u64, f64); 5] = [
+        (0, 40.0),
+        (1000, 40.0),
+        (1500, 90.0),
+        (2000, 40.0),
+        (3000, 40.0),
+    ];
+
+    #[test]
+    fn test_exponential() {
+        let mut smooth = Smooth::new("s".into(), SmoothKind::Exponential, 2.0, None);
+        let values = run(&mut smooth, &SPIKE);
+
+        assert_eq!(values[..2], [40.0, 40.0]);
+        // alpha = 1 - e^(-0.25)
+        assert!((values[2] - 51.06).abs() < 0.01, "{}", values[2]);
+        assert!(values[3] < values[2]);
+        assert!(values[4] < values[3] && values[4] > 40.0);
+
+        // a window of 0 doesn't smooth
+        let mut smooth = Smooth::new("s".into(), SmoothKind::Exponential, 0.0, None);
+        assert_eq!(run(&mut smooth, &SPIKE), SPIKE.map(|(_, value)| value));
+    }
+
+    #[test]
+    fn test_average() {
+        let mut smooth = Smooth::new("s".into(), SmoothKind::Average, 1.0, None);
+
+        assert_eq!(
+            run(&mut smooth, &SPIKE),
+            [40.0, 40.0, 65.0, 170.0 / 3.0, 40.0]
+        );
+    }
+
+    #[test]
+    fn test_max() {
+        let mut smooth = Smooth::new("s".into(), SmoothKind::Max, 1.0, None);
+
+        assert_eq!(run(&mut smooth, &SPIKE), [40.0, 40.0, 90.0, 90.0, 40.0]);
+    }
+
+    #[test]
+    fn test_percentile() {
+        let mut smooth = Smooth::new("s".into(), SmoothKind::Percentile, 10.0, None);
+        smooth.percentile = 50.0;
+        assert_eq!(run(&mut smooth, &SPIKE), [40.0; 5]);
+
+        smooth.reset();
+        smooth.percentile = 100.0;
+        assert_eq!(run(&mut smooth, &SPIKE), [40.0, 40.0, 90.0, 90.0, 90.0]);
+    }
+
+    #[test]
+    fn test_keep_last_value() {
+        let mut smooth = Smooth::new("s".into(), SmoothKind::Average, 1.0, None);
+
+        assert_eq!(run(&mut smooth, &[(0, 40.0), (60_000, 50.0)]), [40.0, 50.0]);
+    }
+}
@@ -3,18 +3,18 @@ use crate::{
     node::{IsValid, Node, NodeType, ToNode},
     update::UpdateError,
 };
-use hardware::{Hardware, Value};
+use hardware::Hardware;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Target {
     pub name: String,
     #[serde(rename = "idleTemp", alias = "idle_temp")]
-    pub idle_temp: u8,
+    pub idle_temp: f64,
     #[serde(rename = "idleSpeed", alias = "idle_speed")]
     pub idle_speed: u8,
     #[serde(rename = "loadTemp", alias = "load_temp")]
-    pub load_temp: u8,
+    pub load_temp: f64,
     #[serde(rename = "loadSpeed", alias = "load_speed")]
     pub load_speed: u8,
     pub input: Option<String>,
@@ -35,9 +35,16 @@ impl PartialEq for Target {
 }
 
 impl Target {
-    pub fn get_value(&mut self, value: Value) -> Result<Value, UpdateError> {
+    /// Stay idle after an edit of the graph, unless the config changed.
+    pub fn inherit(&mut self, previous: &Self) {
+        if self == previous {
+            self.idle_has_been_reatch = previous.idle_has_been_reatch;
+        }
+    }
+
+    pub fn get_value(&mut self, value: f64) -> Result<f64, UpdateError> {
         if self.idle_has_been_reatch {
-            if value < self.load_temp.into() {
+            if value < self.load_temp {
                 return Ok(self.idle_speed.into());
             }
 
@@ -45,7 +52,7 @@ impl Target {
             return Ok(self.load_speed.into());
         }
 
-        if value > self.idle_temp.into() {
+        if value > self.idle_temp {
             return Ok(self.load_speed.into());
         }
 
@@ -64,13 +71,13 @@ impl ToNode for Target {
     fn to_node(mut self, app_graph: &mut AppGraph, _hardware: &Hardware) -> Node {
         let default = Self::default();
 
-        if self.idle_temp > 100 {
+        if !self.idle_temp.is_finite() {
             self.idle_temp = default.idle_temp;
         }
         if self.idle_speed > 100 {
             self.idle_speed = default.idle_speed;
         }
-        if self.load_temp > 100 {
+        if !self.load_temp.is_finite() {
             self.load_temp = default.load_temp;
         }
         if self.load_speed > 100 {
@@ -85,9 +92,9 @@ impl Default for Target {
     fn default() -> Self {
         Self {
             name: Default::default(),
-            idle_temp: 40,
+            idle_temp: 40.0,
             idle_speed: 10,
-            load_temp: 70,
+            load_temp: 70.0,
             load_speed: 100,
             input: Default::default(),
             idle_has_been_reatch: false,
@@ -109,16 +116,19 @@ mod test {
         let mut target = Target {
             name: "linear".to_string(),
             input: Some("temp1".into()),
-            idle_temp: 40,
+            idle_temp: 40.0,
             idle_speed: 10,
-            load_temp: 70,
+            load_temp: 70.0,
             load_speed: 100,
             idle_has_been_reatch: false,
         };
 
-        assert!(target.get_value(55).unwrap() == 100);
-        assert!(target.get_value(30).unwrap() == 10);
-        assert!(target.get_value(55).unwrap() == 10);
-        assert!(target.get_value(70).unwrap() == 100);
+        assert!(target.get_value(55.0).unwrap() == 100.0);
+        assert!(target.get_value(30.0).unwrap() == 10.0);
+        assert!(target.get_value(55.0).unwrap() == 10.0);
+        assert!(target.get_value(69.9).unwrap() == 10.0);
+        assert!(target.get_value(70.0).unwrap() == 100.0);
+        assert!(target.get_value(40.1).unwrap() == 100.0);
+        assert!(target.get_value(40.0).unwrap() == 10.0);
     }
 }
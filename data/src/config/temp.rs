@@ -1,6 +1,6 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use hardware::{HSensor, Hardware, HardwareBridge, Value};
+use hardware::{HSensor, Hardware, HardwareBridge, SensorValue};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -16,7 +16,7 @@ pub struct Temp {
     pub hardware_id: Option<String>,
 
     #[serde(skip)]
-    pub temp_h: Option<Rc<HSensor>>,
+    pub temp_h: Option<Arc<HSensor>>,
 }
 
 impl PartialEq for Temp {
@@ -26,7 +26,7 @@ impl PartialEq for Temp {
 }
 
 impl Temp {
-    pub fn get_value<H: HardwareBridge>(&self, bridge: &mut H) -> Result<Value, UpdateError> {
+    pub fn get_value<H: HardwareBridge>(&self, bridge: &mut H) -> Result<SensorValue, UpdateError> {
         match &self.temp_h {
             Some(temp_h) => bridge
                 .get_sensor_value(temp_h)
@@ -1,18 +1,16 @@
-use hardware::Value;
-
 #[derive(Debug)]
 pub struct Affine {
-    pub xa: f32,
-    pub ya: f32,
-    pub xb: f32,
-    pub yb: f32,
+    pub xa: f64,
+    pub ya: f64,
+    pub xb: f64,
+    pub yb: f64,
 }
 
 impl Affine {
-    pub fn calcule(&self, value: Value) -> f32 {
+    pub fn calcule(&self, value: f64) -> f64 {
         let a = (self.yb - self.ya) / (self.xb - self.xa);
         let b = self.ya - a * self.xa;
 
-        a * value as f32 + b
+        a * value + b
     }
 }
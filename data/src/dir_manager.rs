@@ -4,7 +4,7 @@ use std::{
 };
 
 use directories::ProjectDirs;
-use hardware::Hardware;
+use hardware::{Hardware, file::FileEndpoints};
 
 use common::{APP, ORG, QUALIFIER};
 use thiserror::Error;
@@ -50,6 +50,7 @@ type Result<T> = std::result::Result<T, ConfigError>;
 static SETTINGS_FILENAME: &str = "settings.toml";
 static STATE_FILENAME: &str = "state.toml";
 static HARDWARE_FILENAME: &str = "hardware.toml";
+static FILES_FILENAME: &str = "files.toml";
 static CACHED_CONFIG_FILENAME: &str = "cached_config.toml";
 
 impl DirManager {
@@ -242,6 +243,57 @@ impl DirManager {
         Ok(())
     }
 
+    /// Sensors and controls declared by the user, read from `files.toml`.
+    pub fn file_endpoints(&self) -> FileEndpoints {
+        let path = self.config_dir_path.join(FILES_FILENAME);
+        if !path.exists() {
+            return FileEndpoints::default();
+        }
+
+        match deserialize::<FileEndpoints>(&path) {
+            Ok(files) => files,
+            Err(e) => {
+                warn!("can't deserialize {}: {e}", path.display());
+                FileEndpoints::default()
+            }
+        }
+    }
+
+    /// Rewrite the configs which still use the hardware ids of previous versions.
+    pub fn migrate_hardware_ids(&self, hardware: &Hardware) {
+        if hardware.legacy_ids.is_empty() {
+            return;
+        }
+
+        let paths = self
+            .config_names
+            .data
+            .iter()
+            .map(|name| self.config_file_path(name))
+            .chain([self.cached_config_file_path()]);
+
+        for path in paths {
+            if !path.exists() {
+                continue;
+            }
+
+            let mut config = match deserialize::<Config>(&path) {
+                Ok(config) => config,
+                Err(e) => {
+                    warn!("can't deserialize {}: {e}", path.display());
+                    continue;
+                }
+            };
+
+            if config.migrate_hardware_ids(hardware) {
+                match serialize(&path, &config) {
+                    Ok(()) => info!("{} rewritten with the new hardware ids", path.display()),
+                    Err(e) => error!("can't rewrite {}: {e}", path.display()),
+                }
+            }
+        }
+    }
+
     pub fn serialize_hardware(&self, hardware: &Hardware) {
         let hardware_file_path = self.hardware_file_path();
 
@@ -364,7 +416,10 @@ impl ConfigNames {
 
             let file_name = file.file_name();
 
-            if file_name == SETTINGS_FILENAME || file_name == HARDWARE_FILENAME {
+            if file_name == SETTINGS_FILENAME
+                || file_name == HARDWARE_FILENAME
+                || file_name == FILES_FILENAME
+            {
                 continue;
             }
 
@@ -0,0 +1,667 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    mem,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use hardware::{Hardware, HardwareBridge, HardwareDiff, Mode, Value};
+
+use crate::{
+    AppState,
+    app_graph::AppGraph,
+    calibration::{CalibrationError, Calibrator, Phase},
+    config::control::{Calibration, Control},
+    dir_manager::DirManager,
+    id::Id,
+    node::NodeType,
+    pairing::{Pair, Pairing},
+    settings::Settings,
+    update::Update,
+};
+
+/// Shortest time between two update cycles.
+const MIN_UPDATE_DELAY: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineSettings {
+    pub update_delay: Duration,
+    /// Leave the controls in auto mode
+    pub inactive: bool,
+    pub rescan_interval: Option<Duration>,
+    /// Update the nodes which don't drive a control too, to show their value
+    pub update_all: bool,
+}
+
+impl EngineSettings {
+    pub fn new(settings: &Settings, update_all: bool) -> Self {
+        Self {
+            update_delay: Duration::from_millis(settings.update_delay),
+            inactive: settings.inactive,
+            rescan_interval: settings.rescan_interval.map(Duration::from_secs),
+            update_all,
+        }
+    }
+
+    fn cycle_delay(&self) -> Duration {
+        self.update_delay.max(MIN_UPDATE_DELAY)
+    }
+}
+
+pub enum Command {
+    /// Replace the graph after an edit. Controls still driving
+    /// the same hardware keep their state, the others go back to auto.
+    Graph(AppGraph),
+    Settings(EngineSettings),
+    /// Deferred to the end of the calibration or the fan detection
+    Rescan,
+    /// Calibrate the controls one after another
+    Calibrate(Vec<Id>),
+    DetectFans(Vec<Id>),
+    /// Stop the calibration or the fan detection
+    AbortCalibration,
+    /// End the thread, after shutting down the bridge
+    Stop,
+}
+
+#[derive(Debug)]
+pub enum Event {
+    Rescanned {
+        diff: HardwareDiff,
+        /// Items of the bridge after the rescan
+        hardware: Hardware,
+    },
+    CalibrationStarted {
+        id: Id,
+        name: String,
+    },
+    Calibrated {
+        id: Id,
+        name: String,
+        result: Result<Calibration, CalibrationError>,
+    },
+    FansDetected(Vec<Pair>),
+}
+
+/// What the engine is busy with, besides driving the controls.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Job {
+    #[default]
+    Idle,
+    Calibration {
+        id: Id,
+        phase: Phase,
+        value: Value,
+    },
+    /// Id of the control being tested, if any
+    Pairing(Option<Id>),
+}
+
+impl Job {
+    pub fn is_running(&self) -> bool {
+        *self != Job::Idle
+    }
+}
+
+/// State of the graph at the end of an update cycle.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub values: HashMap<Id, Option<f64>>,
+    pub job: Job,
+}
+
+/// Runs the control loop on its own thread, so it keeps driving the fans
+/// whatever the user interface is doing. The engine owns the bridge and its
+/// own graph, and talks over channels: a [`Snapshot`] is sent after each
+/// update cycle, and dropped if the previous one was not received yet.
+pub struct Engine<H> {
+    commands: Sender<Command>,
+    snapshots: Receiver<Snapshot>,
+    events: Receiver<Event>,
+    thread: Option<JoinHandle<H>>,
+}
+
+impl<H: HardwareBridge + 'static> Engine<H> {
+    pub fn spawn(bridge: H, app_graph: AppGraph, update: Update, settings: EngineSettings) -> Self {
+        let (commands_tx, commands_rx) = mpsc::channel();
+        let (snapshots_tx, snapshots_rx) = mpsc::sync_channel(1);
+        let (events_tx, events_rx) = mpsc::channel();
+
+        let worker = Worker {
+            bridge,
+            app_graph,
+            update,
+            settings,
+            commands: commands_rx,
+            snapshots: snapshots_tx,
+            events: events_tx,
+            last_rescan: Instant::now(),
+            rescan_pending: false,
+            calibrator: None,
+            to_calibrate: VecDeque::new(),
+            pairing: None,
+        };
+
+        Self {
+            commands: commands_tx,
+            snapshots: snapshots_rx,
+            events: events_rx,
+            thread: Some(thread::spawn(move || worker.run())),
+        }
+    }
+}
+
+impl<H> Engine<H> {
+    pub fn send(&self, command: Command) {
+        if self.commands.send(command).is_err() {
+            error!("Can't send a command, the engine is stopped.");
+        }
+    }
+
+    /// Most recent snapshot, if one was sent since the last call.
+    pub fn snapshot(&self) -> Option<Snapshot> {
+        self.snapshots.try_iter().last()
+    }
+
+    /// Wait for the end of the next update cycle.
+    pub fn wait_snapshot(&self, timeout: Duration) -> Option<Snapshot> {
+        self.snapshots.recv_timeout(timeout).ok()
+    }
+
+    /// Events sent since the last call.
+    pub fn events(&self) -> Vec<Event> {
+        self.events.try_iter().collect()
+    }
+
+    /// Stop the control loop, shutdown the bridge and return it.
+    pub fn stop(&mut self) -> Option<H> {
+        let thread = self.thread.take()?;
+        // the thread is already over if the channel is closed
+        let _ = self.commands.send(Command::Stop);
+        match thread.join() {
+            Ok(bridge) => Some(bridge),
+            Err(_) => {
+                error!("The engine panicked.");
+                None
+            }
+        }
+    }
+}
+
+struct Worker<H> {
+    bridge: H,
+    app_graph: AppGraph,
+    update: Update,
+    settings: EngineSettings,
+    commands: Receiver<Command>,
+    snapshots: SyncSender<Snapshot>,
+    events: Sender<Event>,
+    last_rescan: Instant,
+    /// Asked during a job, which holds handles a rescan can make stale
+    rescan_pending: bool,
+    calibrator: Option<Calibrator>,
+    to_calibrate: VecDeque<Id>,
+    pairing: Option<Pairing>,
+}
+
+impl<H: HardwareBridge> Worker<H> {
+    fn run(mut self) -> H {
+        let mut next_cycle = Instant::now();
+
+        loop {
+            // commands can't delay the cycles
+            let now = Instant::now();
+            if now >= next_cycle {
+                self.cycle();
+                next_cycle = now + self.settings.cycle_delay();
+            }
+
+            match self
+                .commands
+                .recv_timeout(next_cycle.saturating_duration_since(Instant::now()))
+            {
+                Ok(Command::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+                Ok(command) => self.handle(command),
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+        }
+
+        self.abort_jobs();
+        if let Err(e) = self.bridge.shutdown() {
+            error!("Can't shutdown hardware: {e}.");
+        }
+        self.bridge
+    }
+
+    fn cycle(&mut self) {
+        let rescan_due = self
+            .settings
+            .rescan_interval
+            .is_some_and(|interval| self.last_rescan.elapsed() >= interval);
+        if (self.rescan_pending || rescan_due) && !self.job().is_running() {
+            self.rescan();
+        }
+
+        if let Err(e) = self.bridge.update() {
+            error!("Can't update hardware: {e}.");
+            return;
+        }
+
+        let nodes = &mut self.app_graph.nodes;
+        let result = if self.settings.update_all {
+            self.update
+                .all(nodes, &mut self.bridge, self.settings.inactive)
+                .and_then(|_| {
+                    self.update
+                        .nodes_which_update_can_change(nodes, &mut self.bridge)
+                })
+        } else {
+            self.update.optimized(
+                nodes,
+                &self.app_graph.root_nodes,
+                &mut self.bridge,
+                self.settings.inactive,
+            )
+        };
+        if let Err(e) = result {
+            error!("{e}");
+        }
+
+        self.tick_jobs();
+
+        let snapshot = Snapshot {
+            values: self
+                .app_graph
+                .nodes
+                .values()
+                .map(|node| (node.id, node.value))
+                .collect(),
+            job: self.job(),
+        };
+        // the user interface is busy, it will get the next one
+        let _ = self.snapshots.try_send(snapshot);
+    }
+
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Graph(app_graph) => self.replace_graph(app_graph),
+            Command::Settings(settings) => {
+                if settings.inactive && !self.settings.inactive {
+                    self.update.set_valid_root_nodes_to_auto(
+                        &mut self.app_graph.nodes,
+                        &self.app_graph.root_nodes,
+                        &mut self.bridge,
+                    );
+                }
+                self.settings = settings;
+            }
+            Command::Rescan => {
+                if self.job().is_running() {
+                    self.rescan_pending = true;
+                } else {
+                    self.rescan();
+                }
+            }
+            Command::Calibrate(ids) => {
+                if self.job().is_running() {
+                    warn!("A calibration is already running.");
+                    return;
+                }
+                self.to_calibrate = ids.into();
+                self.start_calibration();
+            }
+            Command::DetectFans(ids) => {
+                if self.job().is_running() {
+                    warn!("A calibration is already running.");
+                    return;
+                }
+                self.pairing = Some(Pairing::start(
+                    &mut self.app_graph,
+                    &mut self.bridge,
+                    ids,
+                    Calibrator::settle_ticks(self.settings.cycle_delay()),
+                ));
+            }
+            Command::AbortCalibration => self.abort_jobs(),
+            Command::Stop => {}
+        }
+    }
+
+    fn send(&self, event: Event) {
+        // nobody listens anymore, the engine still drives the controls
+        let _ = self.events.send(event);
+    }
+
+    fn job(&self) -> Job {
+        if let Some(calibrator) = &self.calibrator {
+            return Job::Calibration {
+                id: calibrator.id(),
+                phase: calibrator.phase(),
+                value: calibrator.value(),
+            };
+        }
+        match &self.pairing {
+            Some(pairing) => Job::Pairing(pairing.current()),
+            None => Job::Idle,
+        }
+    }
+
+    /// Id of the control whose hardware is held by a job.
+    fn busy_control(&self) -> Option<Id> {
+        match self.job() {
+            Job::Calibration { id, .. } | Job::Pairing(Some(id)) => Some(id),
+            Job::Pairing(None) | Job::Idle => None,
+        }
+    }
+
+    /// Keep the update loop away from the hardware held by a job.
+    fn release_busy_control(&mut self) {
+        if let Some(id) = self.busy_control()
+            && let Some(node) = self.app_graph.nodes.get_mut(&id)
+            && let NodeType::Control(control) = &mut node.node_type
+        {
+            control.control_h.take();
+        }
+    }
+
+    /// Only called without a job: indices of the hardware can shift, and a job
+    /// would keep writing through its old handle.
+    fn rescan(&mut self) {
+        self.last_rescan = Instant::now();
+        self.rescan_pending = false;
+        let diff = crate::rescan(&mut self.bridge, &mut self.app_graph).unwrap_or_default();
+
+        self.send(Event::Rescanned {
+            diff,
+            hardware: self.bridge.hardware().clone(),
+        });
+    }
+
+    fn replace_graph(&mut self, mut app_graph: AppGraph) {
+        // controls currently driving a hardware, by hardware id
+        let mut previous: HashMap<String, (Option<f64>, Control)> = HashMap::new();
+        // the other nodes by name: the copy of the user interface has no state
+        let mut states: HashMap<String, NodeType> = HashMap::new();
+        for node in mem::take(&mut self.app_graph.nodes).into_values() {
+            match node.node_type {
+                NodeType::Control(control) => {
+                    if let Some(control_h) = &control.control_h {
+                        previous.insert(control_h.hardware_id.clone(), (node.value, control));
+                    }
+                }
+                node_type => {
+                    states.insert(node_type.name().clone(), node_type);
+                }
+            }
+        }
+
+        let hardware = self.bridge.hardware();
+        let mut bound_controls = HashSet::new();
+
+        for node in app_graph.nodes.values_mut() {
+            match &mut node.node_type {
+                NodeType::Control(control) => {
+                    control.control_h.take();
+                    let Some(hardware_id) = &control.hardware_id else {
+                        continue;
+                    };
+                    if !bound_controls.insert(hardware_id.clone()) {
+                        continue;
+                    }
+
+                    match previous.remove(hardware_id) {
+                        Some((value, previous)) => {
+                            node.value = value;
+                            control.control_h = previous.control_h;
+                            control.mode_set = previous.mode_set;
+                            control.last_raw = previous.last_raw;
+                            control.ramp_state = previous.ramp_state;
+                        }
+                        None => {
+                            control.control_h = hardware
+                                .controls
+                                .iter()
+                                .find(|control_h| &control_h.hardware_id == hardware_id)
+                                .cloned();
+                            control.mode_set = None;
+                            control.last_raw = None;
+                            control.ramp_state = None;
+                        }
+                    }
+                }
+                NodeType::Fan(fan) => {
+                    fan.fan_h = fan.hardware_id.as_ref().and_then(|hardware_id| {
+                        hardware
+                            .fans
+                            .iter()
+                            .find(|fan_h| &fan_h.hardware_id == hardware_id)
+                            .cloned()
+                    });
+                }
+                NodeType::Temp(temp) => {
+                    temp.temp_h = temp.hardware_id.as_ref().and_then(|hardware_id| {
+                        hardware
+                            .temps
+                            .iter()
+                            .find(|temp_h| &temp_h.hardware_id == hardware_id)
+                            .cloned()
+                    });
+                }
+                NodeType::Sensor(sensor) => {
+                    sensor.sensor_h = sensor.hardware_id.as_ref().and_then(|hardware_id| {
+                        hardware
+                            .sensors
+                            .iter()
+                            .find(|sensor_h| &sensor_h.hardware_id == hardware_id)
+                            .cloned()
+                    });
+                }
+                NodeType::Pid(pid) => {
+                    if let Some(NodeType::Pid(previous)) = states.get(&pid.name) {
+                        pid.inherit(previous);
+                    }
+                }
+                NodeType::Smooth(smooth) => {
+                    if let Some(NodeType::Smooth(previous)) = states.get(&smooth.name) {
+                        smooth.inherit(previous);
+                    }
+                }
+                NodeType::Graph(graph) => {
+                    if let Some(NodeType::Graph(previous)) = states.get(&graph.name) {
+                        graph.inherit(previous);
+                    }
+                }
+                NodeType::Linear(linear) => {
+                    if let Some(NodeType::Linear(previous)) = states.get(&linear.name) {
+                        linear.inherit(previous);
+                    }
+                }
+                NodeType::Target(target) => {
+                    if let Some(NodeType::Target(previous)) = states.get(&target.name) {
+                        target.inherit(previous);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // removed, or bound to another hardware
+        for (_, mut control) in previous.into_values() {
+            if control.mode_set == Some(Mode::Manual)
+                && let Err(e) = control.set_mode(Mode::Auto, &mut self.bridge)
+            {
+                error!("Can't set {} to auto: {e}.", control.name);
+            }
+        }
+
+        self.app_graph = app_graph;
+        self.release_busy_control();
+
+        self.update.set_invalid_root_nodes_to_auto(
+            &mut self.app_graph.nodes,
+            &self.app_graph.root_nodes,
+            &mut self.bridge,
+        );
+    }
+
+    /// Start the calibration of the next control which can be calibrated.
+    fn start_calibration(&mut self) {
+        self.calibrator = None;
+
+        while let Some(id) = self.to_calibrate.pop_front() {
+            let Some(node) = self.app_graph.nodes.get(&id) else {
+                continue;
+            };
+            let name = node.name().clone();
+
+            match Calibrator::start(
+                &mut self.app_graph,
+                &mut self.bridge,
+                id,
+                Calibrator::settle_ticks(self.settings.cycle_delay()),
+            ) {
+                Ok(calibrator) => {
+                    self.calibrator = Some(calibrator);
+                    self.send(Event::CalibrationStarted { id, name });
+                    return;
+                }
+                Err(e) => self.send(Event::Calibrated {
+                    id,
+                    name,
+                    result: Err(e),
+                }),
+            }
+        }
+    }
+
+    fn tick_jobs(&mut self) {
+        if let Some(calibrator) = &mut self.calibrator
+            && let Some(result) = calibrator.tick(&mut self.app_graph, &mut self.bridge)
+        {
+            let event = Event::Calibrated {
+                id: calibrator.id(),
+                name: calibrator.name().clone(),
+                result,
+            };
+            self.send(event);
+            self.start_calibration();
+        }
+
+        if let Some(pairing) = &mut self.pairing
+            && let Some(pairs) = pairing.tick(&mut self.app_graph, &mut self.bridge)
+        {
+            self.pairing = None;
+            self.send(Event::FansDetected(pairs));
+        }
+    }
+
+    fn abort_jobs(&mut self) {
+        self.to_calibrate.clear();
+        if let Some(calibrator) = self.calibrator.take() {
+            calibrator.abort(&mut self.app_graph, &mut self.bridge);
+        }
+        if let Some(pairing) = self.pairing.take() {
+            pairing.abort(&mut self.app_graph, &mut self.bridge);
+        }
+    }
+}
+
+/// The side of the app which shows the graph and edits it, while
+/// an [`Engine`] drives the hardware.
+pub struct Frontend<H> {
+    pub dir_manager: DirManager,
+    /// Copy of the graph of the engine, edited by the user
+    pub app_graph: AppGraph,
+    /// Items of the bridge, as of the last rescan
+    pub hardware: Hardware,
+    pub job: Job,
+    pub engine: Engine<H>,
+    update_all: bool,
+}
+
+impl<H: HardwareBridge + 'static> Frontend<H> {
+    /// Start the engine with the state built at startup.
+    pub fn new(app_state: AppState<H>, update_all: bool) -> Self {
+        let AppState {
+            dir_manager,
+            bridge,
+            app_graph,
+            update,
+        } = app_state;
+
+        let hardware = bridge.hardware().clone();
+        let settings = EngineSettings::new(dir_manager.settings(), update_all);
+        let engine = Engine::spawn(bridge, app_graph.clone(), update, settings);
+
+        Self {
+            dir_manager,
+            app_graph,
+            hardware,
+            job: Job::Idle,
+            engine,
+            update_all,
+        }
+    }
+}
+
+impl<H> Frontend<H> {
+    /// Make the engine use the graph edited by the user.
+    pub fn send_graph(&self) {
+        self.engine.send(Command::Graph(self.app_graph.clone()));
+    }
+
+    /// Forward a change of the settings to the engine.
+    pub fn send_settings(&self) {
+        let settings = EngineSettings::new(self.dir_manager.settings(), self.update_all);
+        self.engine.send(Command::Settings(settings));
+    }
+
+    /// Apply what the engine sent since the last call to the copy of the
+    /// graph, and return the events to show them.
+    pub fn receive(&mut self) -> Vec<Event> {
+        let events = self.engine.events();
+
+        for event in &events {
+            match event {
+                Event::Rescanned { hardware, .. } => {
+                    self.hardware = hardware.clone();
+                    self.app_graph.rebind(hardware);
+                }
+                Event::Calibrated {
+                    id,
+                    result: Ok(calibration),
+                    ..
+                } => {
+                    if let Some(node) = self.app_graph.nodes.get_mut(id)
+                        && let NodeType::Control(control) = &mut node.node_type
+                    {
+                        control.calibration = Some(*calibration);
+                    }
+                }
+                Event::FansDetected(pairs) => {
+                    for pair in pairs {
+                        if let Some(fan_id) = &pair.fan_id
+                            && let Some(node) = self.app_graph.nodes.get_mut(&pair.id)
+                            && let NodeType::Control(control) = &mut node.node_type
+                        {
+                            control.fan = Some(fan_id.clone());
+                        }
+                    }
+                }
+                Event::CalibrationStarted { .. } | Event::Calibrated { .. } => {}
+            }
+        }
+
+        if let Some(snapshot) = self.engine.snapshot() {
+            for (id, value) in snapshot.values {
+                if let Some(node) = self.app_graph.nodes.get_mut(&id) {
+                    node.value = value;
+                }
+            }
+            self.job = snapshot.job;
+        }
+
+        events
+    }
+}
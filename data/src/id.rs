@@ -1,6 +1,6 @@
 pub type Id = u32;
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct IdGenerator {
     prec_id: Id,
 }
@@ -5,16 +5,20 @@ extern crate log;
 pub mod localize;
 
 pub mod app_graph;
+pub mod calibration;
+pub mod clock;
 pub mod config;
 pub mod dir_manager;
+pub mod engine;
 pub mod id;
 pub mod node;
+pub mod pairing;
 pub mod settings;
 pub mod update;
 pub mod utils;
 
 use crate::app_graph::AppGraph;
-use hardware::HardwareBridge;
+use hardware::{HardwareBridge, HardwareDiff, HardwareError};
 use update::Update;
 
 use crate::dir_manager::DirManager;
@@ -25,3 +29,33 @@ pub struct AppState<H: HardwareBridge> {
     pub app_graph: AppGraph,
     pub update: Update,
 }
+
+impl<H: HardwareBridge> AppState<H> {
+    /// Look for hardware plugged or unplugged, and bind the nodes to the new items.
+    /// Controls are set to manual again at their next update.
+    pub fn rescan(&mut self) -> Result<HardwareDiff, HardwareError> {
+        rescan(&mut self.bridge, &mut self.app_graph)
+    }
+}
+
+pub(crate) fn rescan<H: HardwareBridge>(
+    bridge: &mut H,
+    app_graph: &mut AppGraph,
+) -> Result<HardwareDiff, HardwareError> {
+    let result = bridge.rescan();
+
+    match &result {
+        Ok(diff) if !diff.is_empty() => {
+            info!(
+                "Hardware added: {:?}, removed: {:?}.",
+                diff.added, diff.removed
+            )
+        }
+        Ok(_) => debug!("No hardware change."),
+        Err(e) => error!("Can't rescan hardware: {e}."),
+    }
+
+    // the items of the bridge are replaced, even if the rescan failed partially
+    app_graph.rebind(bridge.hardware());
+    result
+}
@@ -2,14 +2,15 @@ use std::cmp::Ordering;
 use std::vec;
 
 use derive_more::{Display, Unwrap};
-use hardware::{Hardware, Value};
+use hardware::{Hardware, SensorKind, SensorLimits};
 use light_enum::LightEnum;
 use std::fmt::Display;
 
 use crate::app_graph::{AppGraph, Nodes};
 
 use crate::config::{
-    control::Control, custom_temp::CustomTemp, fan::Fan, flat::Flat, graph::Graph, linear::Linear,
+    command_temp::CommandTemp, control::Control, custom_temp::CustomTemp, fan::Fan, flat::Flat,
+    graph::Graph, linear::Linear, mix::Mix, pid::Pid, sensor::Sensor, smooth::Smooth,
     target::Target, temp::Temp,
 };
 
@@ -21,11 +22,16 @@ pub enum NodeType {
     Control(Control),
     Fan(Fan),
     Temp(Temp),
+    Sensor(Sensor),
+    CommandTemp(CommandTemp),
     CustomTemp(CustomTemp),
+    Smooth(Smooth),
     Graph(Graph),
     Flat(Flat),
     Linear(Linear),
     Target(Target),
+    Pid(Pid),
+    Mix(Mix),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -45,7 +51,7 @@ pub struct Node {
     pub id: Id,
     pub node_type: NodeType,
     pub inputs: Vec<Input>,
-    pub value: Option<Value>,
+    pub value: Option<f64>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Display)]
@@ -105,25 +111,19 @@ pub fn sanitize_inputs(node: &Node, nodes: &Nodes, log: bool) -> Sanitize {
 
     for name in node.node_type.get_inputs() {
         match nodes.values().find(|n| n.name() == &name) {
-            Some(n) => {
-                match node
-                    .node_type
-                    .allowed_dep()
-                    .contains(&n.node_type.to_light())
-                {
-                    true => {
-                        sanitize.add(n.id, &name);
-                    }
-                    false => {
-                        warn!(
-                            "sanitize_inputs {}: dep {} have an unauthorized node type: {:?}",
-                            node.name(),
-                            name,
-                            n.node_type.to_light(),
-                        );
-                    }
+            Some(n) => match node.node_type.accepts_input(&n.node_type) {
+                true => {
+                    sanitize.add(n.id, &name);
                 }
-            }
+                false => {
+                    warn!(
+                        "sanitize_inputs {}: dep {} have an unauthorized node type: {:?}",
+                        node.name(),
+                        name,
+                        n.node_type.to_light(),
+                    );
+                }
+            },
             None => {
                 if log {
                     warn!("sanitize_inputs {}: can't find node {}", node.name(), name);
@@ -142,6 +142,17 @@ pub fn validate_name(nodes: &Nodes, id: &Id, name: &String) -> bool {
     !nodes.values().any(|n| n.name() == name && &n.id != id)
 }
 
+/// Return true if a control node already holds this hardware control.
+pub fn is_control_bound(nodes: &Nodes, hardware_id: &str) -> bool {
+    nodes.values().any(|node| match &node.node_type {
+        NodeType::Control(control) => control
+            .control_h
+            .as_ref()
+            .is_some_and(|control_h| control_h.hardware_id == hardware_id),
+        _ => false,
+    })
+}
+
 pub trait ToNode {
     fn to_node(self, app_graph: &mut AppGraph, hardware: &Hardware) -> Node;
 }
@@ -184,21 +195,40 @@ impl Node {
             NodeType::Control(i) => &i.hardware_id,
             NodeType::Fan(i) => &i.hardware_id,
             NodeType::Temp(i) => &i.hardware_id,
+            NodeType::Sensor(i) => &i.hardware_id,
             _ => panic!(),
         }
     }
 
     pub fn value_text(&self, kind: &ValueKind) -> String {
         match self.value {
-            Some(val) => match kind {
-                ValueKind::Celsius => fl!("value_celsius", value = val),
-                ValueKind::Porcentage => fl!("value_percentage", value = val),
-                ValueKind::RPM => fl!("value_rpm", value = val),
-            },
+            Some(val) => kind.format(val),
             None => fl!("no_value"),
         }
     }
 
+    /// Limits reported by the hardware of a sensor, like "max 80 °C, crit 100 °C".
+    pub fn limits_text(&self, kind: &ValueKind) -> Option<String> {
+        let limits = self.node_type.sensor_limits()?;
+
+        let texts = [
+            limits
+                .min
+                .map(|min| fl!("limit_min", value = kind.format(min))),
+            limits
+                .max
+                .map(|max| fl!("limit_max", value = kind.format(max))),
+            limits
+                .crit
+                .map(|crit| fl!("limit_crit", value = kind.format(crit))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+        (!texts.is_empty()).then(|| texts.join(", "))
+    }
+
     pub fn is_root(&self) -> bool {
         self.node_type.is_root()
     }
@@ -208,19 +238,80 @@ pub enum ValueKind {
     Celsius,
     Porcentage,
     RPM,
+    /// Native value of a control
+    Raw,
+    Volt,
+    Ampere,
+    Watt,
+    Joule,
+    /// Relative humidity
+    Humidity,
+}
+
+impl ValueKind {
+    /// Temperatures are shown with one decimal, voltages and currents
+    /// with two, other values are rounded.
+    pub fn format(&self, value: f64) -> String {
+        let value = match self {
+            ValueKind::Celsius => (value * 10.0).round() / 10.0,
+            ValueKind::Volt | ValueKind::Ampere => (value * 100.0).round() / 100.0,
+            _ => value.round(),
+        };
+        // no "-0"
+        let value = value + 0.0;
+
+        match self {
+            ValueKind::Celsius => fl!("value_celsius", value = value),
+            ValueKind::Porcentage => fl!("value_percentage", value = value),
+            ValueKind::RPM => fl!("value_rpm", value = value),
+            ValueKind::Raw => value.to_string(),
+            ValueKind::Volt => fl!("value_volt", value = value),
+            ValueKind::Ampere => fl!("value_ampere", value = value),
+            ValueKind::Watt => fl!("value_watt", value = value),
+            ValueKind::Joule => fl!("value_joule", value = value),
+            ValueKind::Humidity => fl!("value_humidity", value = value),
+        }
+    }
+}
+
+impl From<SensorKind> for ValueKind {
+    fn from(kind: SensorKind) -> Self {
+        match kind {
+            SensorKind::Voltage => ValueKind::Volt,
+            SensorKind::Current => ValueKind::Ampere,
+            SensorKind::Power => ValueKind::Watt,
+            SensorKind::Energy => ValueKind::Joule,
+            SensorKind::Humidity => ValueKind::Humidity,
+        }
+    }
 }
 
 impl NodeType {
+    /// Limits of the hardware bound to a fan or a temp.
+    pub fn sensor_limits(&self) -> Option<SensorLimits> {
+        match self {
+            NodeType::Fan(fan) => fan.fan_h.as_ref().map(|fan_h| fan_h.limits),
+            NodeType::Temp(temp) => temp.temp_h.as_ref().map(|temp_h| temp_h.limits),
+            NodeType::Sensor(sensor) => sensor.sensor_h.as_ref().map(|sensor_h| sensor_h.limits),
+            _ => None,
+        }
+    }
+
     pub fn name(&self) -> &String {
         match self {
             NodeType::Control(control) => &control.name,
             NodeType::Fan(fan) => &fan.name,
             NodeType::Temp(temp) => &temp.name,
+            NodeType::Sensor(sensor) => &sensor.name,
+            NodeType::CommandTemp(command_temp) => &command_temp.name,
             NodeType::CustomTemp(custom_temp) => &custom_temp.name,
+            NodeType::Smooth(smooth) => &smooth.name,
             NodeType::Graph(graph) => &graph.name,
             NodeType::Flat(flat) => &flat.name,
             NodeType::Linear(linear, ..) => &linear.name,
             NodeType::Target(target, ..) => &target.name,
+            NodeType::Pid(pid) => &pid.name,
+            NodeType::Mix(mix) => &mix.name,
         }
     }
     pub fn set_name(&mut self, name: String) {
@@ -228,11 +319,16 @@ impl NodeType {
             NodeType::Control(i) => i.name = name,
             NodeType::Fan(i) => i.name = name,
             NodeType::Temp(i) => i.name = name,
+            NodeType::Sensor(i) => i.name = name,
+            NodeType::CommandTemp(i) => i.name = name,
             NodeType::CustomTemp(i) => i.name = name,
+            NodeType::Smooth(i) => i.name = name,
             NodeType::Graph(i) => i.name = name,
             NodeType::Flat(i) => i.name = name,
             NodeType::Linear(i, ..) => i.name = name,
             NodeType::Target(i, ..) => i.name = name,
+            NodeType::Pid(i) => i.name = name,
+            NodeType::Mix(i) => i.name = name,
         }
     }
 
@@ -241,11 +337,16 @@ impl NodeType {
             NodeType::Control(control) => control.is_valid(),
             NodeType::Fan(fan) => fan.is_valid(),
             NodeType::Temp(temp) => temp.is_valid(),
+            NodeType::Sensor(sensor) => sensor.is_valid(),
+            NodeType::CommandTemp(command_temp) => command_temp.is_valid(),
             NodeType::CustomTemp(custom_temp) => custom_temp.is_valid(),
+            NodeType::Smooth(smooth) => smooth.is_valid(),
             NodeType::Graph(graph) => graph.is_valid(),
             NodeType::Flat(flat) => flat.is_valid(),
             NodeType::Linear(linear, ..) => linear.is_valid(),
             NodeType::Target(target, ..) => target.is_valid(),
+            NodeType::Pid(pid) => pid.is_valid(),
+            NodeType::Mix(mix) => mix.is_valid(),
         }
     }
 
@@ -254,11 +355,16 @@ impl NodeType {
             NodeType::Control(i) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
             NodeType::Fan(_) => Vec::new(),
             NodeType::Temp(_) => Vec::new(),
+            NodeType::Sensor(_) => Vec::new(),
+            NodeType::CommandTemp(_) => Vec::new(),
             NodeType::CustomTemp(i) => i.inputs.clone(),
+            NodeType::Smooth(i) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
             NodeType::Graph(i) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
             NodeType::Flat(_) => Vec::new(),
             NodeType::Linear(i, ..) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
             NodeType::Target(i, ..) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
+            NodeType::Pid(i) => i.input.clone().map_or(Vec::new(), |i| vec![i]),
+            NodeType::Mix(i) => i.inputs.clone(),
         }
     }
 
@@ -275,6 +381,15 @@ impl NodeType {
             NodeType::CustomTemp(i) => {
                 i.inputs = inputs;
             }
+            NodeType::Mix(i) => i.set_inputs(inputs),
+            NodeType::Smooth(i) => match inputs.first() {
+                Some(input) => {
+                    let _ = i.input.insert(input.clone());
+                }
+                None => {
+                    i.input.take();
+                }
+            },
             NodeType::Graph(i) => match inputs.first() {
                 Some(input) => {
                     let _ = i.input.insert(input.clone());
@@ -299,12 +414,29 @@ impl NodeType {
                     i.input.take();
                 }
             },
+            NodeType::Pid(i) => match inputs.first() {
+                Some(input) => {
+                    let _ = i.input.insert(input.clone());
+                }
+                None => {
+                    i.input.take();
+                }
+            },
             NodeType::Fan(_) => {}
             NodeType::Temp(_) => {}
+            NodeType::Sensor(_) => {}
+            NodeType::CommandTemp(_) => {}
             NodeType::Flat(_) => {}
         };
     }
 
+    /// Whether `input` can be an input of this node. Energies only count up
+    /// since the chip started, so no node can use them.
+    pub fn accepts_input(&self, input: &NodeType) -> bool {
+        self.allowed_dep().contains(&input.to_light())
+            && !matches!(input, NodeType::Sensor(sensor) if sensor.kind() == Some(SensorKind::Energy))
+    }
+
     pub fn allowed_dep(&self) -> &'static [NodeTypeLight] {
         match self {
             NodeType::Control(..) => &[
@@ -312,14 +444,56 @@ impl NodeType {
                 NodeTypeLight::Graph,
                 NodeTypeLight::Target,
                 NodeTypeLight::Linear,
+                NodeTypeLight::Pid,
+                NodeTypeLight::Mix,
             ],
             NodeType::Fan(..) => &[],
             NodeType::Temp(..) => &[],
-            NodeType::CustomTemp(..) => &[NodeTypeLight::Temp],
-            NodeType::Graph(..) => &[NodeTypeLight::Temp, NodeTypeLight::CustomTemp],
+            NodeType::Sensor(..) => &[],
+            NodeType::CommandTemp(..) => &[],
+            NodeType::CustomTemp(..) => &[NodeTypeLight::Temp, NodeTypeLight::CommandTemp],
+            NodeType::Smooth(..) => &[
+                NodeTypeLight::Temp,
+                NodeTypeLight::CommandTemp,
+                NodeTypeLight::CustomTemp,
+                NodeTypeLight::Sensor,
+            ],
+            NodeType::Graph(..) => &[
+                NodeTypeLight::Temp,
+                NodeTypeLight::CommandTemp,
+                NodeTypeLight::CustomTemp,
+                NodeTypeLight::Sensor,
+                NodeTypeLight::Smooth,
+            ],
             NodeType::Flat(..) => &[],
-            NodeType::Linear(..) => &[NodeTypeLight::Temp, NodeTypeLight::CustomTemp],
-            NodeType::Target(..) => &[NodeTypeLight::Temp, NodeTypeLight::CustomTemp],
+            NodeType::Linear(..) => &[
+                NodeTypeLight::Temp,
+                NodeTypeLight::CommandTemp,
+                NodeTypeLight::CustomTemp,
+                NodeTypeLight::Sensor,
+                NodeTypeLight::Smooth,
+            ],
+            NodeType::Target(..) => &[
+                NodeTypeLight::Temp,
+                NodeTypeLight::CommandTemp,
+                NodeTypeLight::CustomTemp,
+                NodeTypeLight::Sensor,
+                NodeTypeLight::Smooth,
+            ],
+            NodeType::Pid(..) => &[
+                NodeTypeLight::Temp,
+                NodeTypeLight::CommandTemp,
+                NodeTypeLight::CustomTemp,
+                NodeTypeLight::Sensor,
+                NodeTypeLight::Smooth,
+            ],
+            NodeType::Mix(..) => &[
+                NodeTypeLight::Graph,
+                NodeTypeLight::Flat,
+                NodeTypeLight::Linear,
+                NodeTypeLight::Target,
+                NodeTypeLight::Pid,
+            ],
         }
     }
 
@@ -328,22 +502,37 @@ impl NodeType {
             NodeType::Control(..) => NbInput::One,
             NodeType::Fan(..) => NbInput::Zero,
             NodeType::Temp(..) => NbInput::Zero,
+            NodeType::Sensor(..) => NbInput::Zero,
+            NodeType::CommandTemp(..) => NbInput::Zero,
             NodeType::CustomTemp(..) => NbInput::Infinity,
+            NodeType::Smooth(..) => NbInput::One,
             NodeType::Graph(..) => NbInput::One,
             NodeType::Flat(..) => NbInput::Zero,
             NodeType::Linear(..) => NbInput::One,
             NodeType::Target(..) => NbInput::One,
+            NodeType::Pid(..) => NbInput::One,
+            NodeType::Mix(..) => NbInput::Infinity,
         }
     }
 
     pub fn is_sensor(&self) -> bool {
-        matches!(self, NodeType::Fan(..) | NodeType::Temp(..))
+        matches!(
+            self,
+            NodeType::Fan(..)
+                | NodeType::Temp(..)
+                | NodeType::Sensor(..)
+                | NodeType::CommandTemp(..)
+        )
     }
 
     pub fn is_behavior(&self) -> bool {
         matches!(
             self,
-            NodeType::Graph(..) | NodeType::Flat(..) | NodeType::Linear(..) | NodeType::Target(..)
+            NodeType::Graph(..)
+                | NodeType::Flat(..)
+                | NodeType::Linear(..)
+                | NodeType::Target(..)
+                | NodeType::Pid(..)
         )
     }
 
@@ -351,41 +540,66 @@ impl NodeType {
         matches!(self, NodeType::Control(..))
     }
 
-    pub fn compare_update_priority(&self, other: &Self) -> Ordering {
+    /// Nodes of a lower level are updated first, so the inputs of a node,
+    /// which can be behaviors for a mix, are up to date before it.
+    fn update_level(&self) -> u8 {
         match self {
-            NodeType::Control(_) => match other {
-                NodeType::Control(_) => Ordering::Equal,
-                _ => Ordering::Greater,
-            },
-            NodeType::Fan(_) => {
-                if other.is_sensor() {
-                    Ordering::Equal
-                } else {
-                    Ordering::Less
-                }
-            }
-            NodeType::Temp(_) => {
-                if other.is_sensor() {
-                    Ordering::Equal
-                } else {
-                    Ordering::Less
+            NodeType::Fan(_)
+            | NodeType::Temp(_)
+            | NodeType::Sensor(_)
+            | NodeType::CommandTemp(_) => 0,
+            NodeType::CustomTemp(_) => 1,
+            NodeType::Smooth(_) => 2,
+            NodeType::Graph(_)
+            | NodeType::Flat(_)
+            | NodeType::Linear(..)
+            | NodeType::Target(..)
+            | NodeType::Pid(..) => 3,
+            NodeType::Mix(_) => 4,
+            NodeType::Control(_) => 5,
+        }
+    }
+
+    pub fn compare_update_priority(&self, other: &Self) -> Ordering {
+        self.update_level().cmp(&other.update_level())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+
+    use crate::app_graph::AppGraph;
+
+    use super::NodeTypeLight;
+
+    #[test]
+    fn test_update_priority() {
+        let mut app_graph = AppGraph::new();
+        let node_types = [
+            NodeTypeLight::Control,
+            NodeTypeLight::Mix,
+            NodeTypeLight::Flat,
+            NodeTypeLight::Graph,
+            NodeTypeLight::Pid,
+            NodeTypeLight::Smooth,
+            NodeTypeLight::CustomTemp,
+            NodeTypeLight::Temp,
+            NodeTypeLight::Fan,
+        ]
+        .map(|node_type| app_graph.create_new_node(node_type).node_type);
+
+        // a consistent order, where the inputs of a node come first
+        for first in &node_types {
+            for other in &node_types {
+                assert_eq!(
+                    first.compare_update_priority(other),
+                    other.compare_update_priority(first).reverse()
+                );
+                if first.allowed_dep().contains(&other.to_light()) {
+                    assert_eq!(first.compare_update_priority(other), Ordering::Greater);
                 }
             }
-            NodeType::CustomTemp(_) => match other {
-                NodeType::CustomTemp(_) => Ordering::Equal,
-                NodeType::Fan(_) => Ordering::Greater,
-                NodeType::Temp(_) => Ordering::Greater,
-                _ => Ordering::Less,
-            },
-            NodeType::Flat(_) => Ordering::Equal,
-
-            NodeType::Graph(_) | NodeType::Linear(..) | NodeType::Target(..) => match other {
-                NodeType::Control(_) => Ordering::Less,
-                NodeType::Fan(_) => Ordering::Greater,
-                NodeType::Temp(_) => Ordering::Greater,
-                NodeType::CustomTemp(_) => Ordering::Greater,
-                _ => Ordering::Equal,
-            },
         }
     }
 }
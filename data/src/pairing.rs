@@ -0,0 +1,180 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use hardware::{HControl, HSensor, HardwareBridge, Value};
+
+use crate::{
+    app_graph::AppGraph,
+    calibration::{give_back_control, reacting_fan, read_rpms, take_control},
+    id::Id,
+    node::NodeType,
+};
+
+/// Fan found for a control.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pair {
+    /// Id of the control node
+    pub id: Id,
+    /// Name of the control node
+    pub control: String,
+    /// Name of the fan, None when no fan reacted
+    pub fan: Option<String>,
+    /// Hardware id of the fan
+    pub fan_id: Option<String>,
+}
+
+/// Find the fan driven by each control, by setting the control to its max
+/// then to its min while watching the speed of every fan.
+///
+/// Controls are tested one after another, their hardware taken from
+/// their node like during a calibration, and stored in the `fan` field
+/// of the node. [`Pairing::tick`] must be called once per update cycle.
+pub struct Pairing {
+    to_pair: VecDeque<Id>,
+    current: Option<Current>,
+    fans: Vec<Arc<HSensor>>,
+    settle_ticks: u32,
+    ticks: u32,
+    pairs: Vec<Pair>,
+}
+
+struct Current {
+    id: Id,
+    name: String,
+    control_h: Arc<HControl>,
+    /// Speed of the fans with the control at its max, once read
+    max_rpms: Option<Vec<Value>>,
+}
+
+impl Pairing {
+    pub fn start<H: HardwareBridge>(
+        app_graph: &mut AppGraph,
+        bridge: &mut H,
+        ids: impl IntoIterator<Item = Id>,
+        settle_ticks: u32,
+    ) -> Self {
+        let mut pairing = Self {
+            to_pair: ids.into_iter().collect(),
+            current: None,
+            fans: bridge.hardware().fans.clone(),
+            settle_ticks,
+            ticks: 0,
+            pairs: Vec::new(),
+        };
+        pairing.start_next(app_graph, bridge);
+        pairing
+    }
+
+    /// Id of the control being tested.
+    pub fn current(&self) -> Option<Id> {
+        self.current.as_ref().map(|current| current.id)
+    }
+
+    /// Return the fans found when every control has been tested.
+    pub fn tick<H: HardwareBridge>(
+        &mut self,
+        app_graph: &mut AppGraph,
+        bridge: &mut H,
+    ) -> Option<Vec<Pair>> {
+        if let Some(current) = &mut self.current {
+            self.ticks += 1;
+            if self.ticks < self.settle_ticks {
+                return None;
+            }
+            self.ticks = 0;
+
+            let rpms = read_rpms(bridge, &self.fans);
+
+            let fan = match current.max_rpms.take() {
+                None => {
+                    current.max_rpms = Some(rpms);
+
+                    let min = current.control_h.range.min;
+                    match bridge.set_value(&current.control_h, min) {
+                        Ok(()) => return None,
+                        Err(e) => {
+                            error!("Can't set {} to its min: {e}.", current.name);
+                            None
+                        }
+                    }
+                }
+                Some(max_rpms) => reacting_fan(&max_rpms, &rpms),
+            };
+            self.finish(app_graph, bridge, fan);
+            self.start_next(app_graph, bridge);
+        }
+
+        match self.current {
+            Some(_) => None,
+            None => Some(std::mem::take(&mut self.pairs)),
+        }
+    }
+
+    /// Stop testing, and leave the current control in auto mode.
+    pub fn abort<H: HardwareBridge>(mut self, app_graph: &mut AppGraph, bridge: &mut H) {
+        if let Some(current) = self.current.take() {
+            info!("Fan detection of {} aborted.", current.name);
+            give_back_control(app_graph, bridge, &current.id, &current.control_h);
+        }
+    }
+
+    fn start_next<H: HardwareBridge>(&mut self, app_graph: &mut AppGraph, bridge: &mut H) {
+        while let Some(id) = self.to_pair.pop_front() {
+            let Some(node) = app_graph.nodes.get(&id) else {
+                continue;
+            };
+            let NodeType::Control(control) = &node.node_type else {
+                continue;
+            };
+            let Some(control_h) = control.control_h.clone() else {
+                warn!("Can't detect the fan of {}: no hardware.", node.name());
+                continue;
+            };
+            let name = node.name().clone();
+
+            if let Err(e) = take_control(app_graph, bridge, &id, &control_h, control_h.range.max) {
+                error!("Can't detect the fan of {name}: {e}.");
+                continue;
+            }
+
+            info!("Detecting the fan of {name}.");
+            self.ticks = 0;
+            self.current = Some(Current {
+                id,
+                name,
+                control_h,
+                max_rpms: None,
+            });
+            return;
+        }
+    }
+
+    fn finish<H: HardwareBridge>(
+        &mut self,
+        app_graph: &mut AppGraph,
+        bridge: &mut H,
+        fan: Option<usize>,
+    ) {
+        let Some(current) = self.current.take() else {
+            return;
+        };
+
+        let fan = fan.map(|index| &self.fans[index]);
+        match fan {
+            Some(fan) => info!("{} drives {}.", current.name, fan.name),
+            None => warn!("No fan reacted to {}.", current.name),
+        }
+
+        if let Some(control) = give_back_control(app_graph, bridge, &current.id, &current.control_h)
+            && let Some(fan) = fan
+        {
+            control.fan = Some(fan.hardware_id.clone());
+        }
+
+        self.pairs.push(Pair {
+            id: current.id,
+            control: current.name,
+            fan: fan.map(|fan| fan.name.clone()),
+            fan_id: fan.map(|fan| fan.hardware_id.clone()),
+        });
+    }
+}
@@ -25,6 +25,8 @@ pub struct Settings {
     pub start_at_login: bool,
     pub inactive: bool,
     pub start_minimized: bool,
+    /// Seconds between two rescans of the hardware, only on demand when None
+    pub rescan_interval: Option<u64>,
 }
 
 // todo: find a better solution to expose themes
@@ -48,6 +50,7 @@ impl Default for Settings {
             start_at_login: false,
             inactive: false,
             start_minimized: false,
+            rescan_interval: None,
         }
     }
 }
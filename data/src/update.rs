@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, time::Duration};
 
 use hardware::{HardwareBridge, Mode, Value};
 
@@ -6,6 +6,7 @@ use thiserror::Error;
 
 use crate::{
     app_graph::{Nodes, RootNodes},
+    clock::{Clock, SystemClock},
     id::Id,
     node::{Node, NodeType},
 };
@@ -24,11 +25,15 @@ pub enum UpdateError {
     CantSetMode,
     #[error(transparent)]
     Hardware(#[from] hardware::HardwareError),
+    #[error(transparent)]
+    Command(#[from] crate::config::command_temp::CommandError),
 }
 
 type Result<T> = std::result::Result<T, UpdateError>;
 
-pub struct Update {}
+pub struct Update {
+    clock: Box<dyn Clock>,
+}
 
 impl Default for Update {
     fn default() -> Self {
@@ -38,7 +43,13 @@ impl Default for Update {
 
 impl Update {
     pub fn new() -> Self {
-        Self {}
+        Self::with_clock(SystemClock::default())
+    }
+
+    pub fn with_clock(clock: impl Clock + 'static) -> Self {
+        Self {
+            clock: Box::new(clock),
+        }
     }
 
     // todo: remember what nodes are valid
@@ -52,9 +63,10 @@ impl Update {
         bridge: &mut H,
         inative: bool,
     ) -> Result<()> {
+        let now = self.clock.now();
         let mut updated: HashSet<Id> = HashSet::new();
         for node_id in root_nodes {
-            if let Err(e) = Self::update_rec(nodes, node_id, &mut updated, bridge, inative) {
+            if let Err(e) = Self::update_rec(nodes, node_id, &mut updated, bridge, inative, now) {
                 error!("Can't update node: {e}.");
             }
         }
@@ -80,9 +92,10 @@ impl Update {
             ids_to_update_sorted = key_values.iter().map(|(id, _)| **id).collect();
         }
 
+        let now = self.clock.now();
         let mut updated = HashSet::new();
         for id in ids_to_update_sorted {
-            if let Err(e) = Self::update_rec(nodes, &id, &mut updated, bridge, inative) {
+            if let Err(e) = Self::update_rec(nodes, &id, &mut updated, bridge, inative, now) {
                 error!("can't update node: {e}");
             }
         }
@@ -97,7 +110,9 @@ impl Update {
     ) -> Result<()> {
         for node in nodes.values_mut() {
             let value = match &mut node.node_type {
-                crate::node::NodeType::Control(control) => Some(control.get_value(bridge)),
+                crate::node::NodeType::Control(control) => {
+                    Some(control.get_value(bridge).map(f64::from))
+                }
                 crate::node::NodeType::Fan(fan) => Some(fan.get_value(bridge)),
                 _ => None,
             };
@@ -195,7 +210,8 @@ impl Update {
         updated: &mut HashSet<Id>,
         bridge: &mut H,
         inative: bool,
-    ) -> Result<Option<Value>> {
+        now: Duration,
+    ) -> Result<Option<f64>> {
         if updated.contains(node_id) {
             return match nodes.get(node_id) {
                 Some(node) => Ok(node.value),
@@ -211,9 +227,7 @@ impl Update {
             updated.insert(node.id);
 
             if !node.node_type.is_valid() {
-                if !node.is_root() {
-                    node.value = None;
-                }
+                node.invalidate();
                 return Ok(None);
             }
             input_ids = node.inputs.iter().map(|i| i.id).collect();
@@ -221,14 +235,12 @@ impl Update {
 
         let mut input_values = Vec::new();
         for id in &input_ids {
-            match Self::update_rec(nodes, id, updated, bridge, inative)? {
+            match Self::update_rec(nodes, id, updated, bridge, inative, now)? {
                 Some(value) => input_values.push(value),
                 None => {
                     return match nodes.get_mut(node_id) {
                         Some(node) => {
-                            if !node.is_root() {
-                                node.value = None;
-                            }
+                            node.invalidate();
                             Ok(None)
                         }
                         None => Err(UpdateError::NodeNotFound(*node_id)),
@@ -241,26 +253,44 @@ impl Update {
             return Err(UpdateError::NodeNotFound(*node_id));
         };
 
-        node.update(&input_values, bridge, inative)?;
+        node.update(&input_values, bridge, inative, now)?;
 
         Ok(node.value)
     }
 }
 
 impl Node {
+    /// The node can't give a value, its state is forgotten.
+    fn invalidate(&mut self) {
+        if !self.is_root() {
+            self.value = None;
+        }
+        match &mut self.node_type {
+            NodeType::Pid(pid) => pid.reset(),
+            NodeType::Graph(graph) => graph.hysteresis.reset(),
+            NodeType::Linear(linear) => linear.hysteresis.reset(),
+            _ => {}
+        }
+    }
+
     fn update<H: HardwareBridge>(
         &mut self,
-        input_values: &[Value],
+        input_values: &[f64],
         bridge: &mut H,
         inative: bool,
+        now: Duration,
     ) -> Result<()> {
         let value = match &mut self.node_type {
             crate::node::NodeType::Control(control) => {
                 if inative {
                     return Ok(());
                 }
-                let input_value = input_values[0];
-                return if self.value == Some(input_value) {
+                let input_value = control.ramp(input_values[0], now).round() as Value;
+                // compare with what the hardware report, which can differ
+                // from the input when the native range is coarse
+                return if self.value.is_some()
+                    && self.value == control.reported_value(input_value).map(f64::from)
+                {
                     debug!("Control {} already set to {}", control.name, input_value);
                     Ok(())
                 } else {
@@ -270,11 +300,16 @@ impl Node {
             }
             crate::node::NodeType::Fan(fan) => fan.get_value(bridge),
             crate::node::NodeType::Temp(temp) => temp.get_value(bridge),
+            crate::node::NodeType::Sensor(sensor) => sensor.get_value(bridge),
+            crate::node::NodeType::CommandTemp(command_temp) => command_temp.get_value(),
             crate::node::NodeType::CustomTemp(custom_temp) => custom_temp.get_value(input_values),
+            crate::node::NodeType::Smooth(smooth) => smooth.get_value(input_values[0], now),
             crate::node::NodeType::Graph(graph) => graph.get_value(input_values[0]),
             crate::node::NodeType::Flat(flat) => Ok(flat.value.into()),
             crate::node::NodeType::Linear(linear, ..) => linear.get_value(input_values[0]),
             crate::node::NodeType::Target(target, ..) => target.get_value(input_values[0]),
+            crate::node::NodeType::Pid(pid) => pid.get_value(input_values[0], now),
+            crate::node::NodeType::Mix(mix) => mix.get_value(input_values),
         };
 
         match value {
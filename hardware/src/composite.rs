@@ -0,0 +1,425 @@
+//! Merge the hardware of several bridges, so sources like hwmon and
+//! user-declared files can be used together.
+
+use std::{collections::HashSet, sync::Arc};
+
+use thiserror::Error;
+
+use crate::{
+    HControl, HSensor, Hardware, HardwareBridge, HardwareDiff, Mode, OutputMode, SensorValue, Value,
+};
+
+#[derive(Error, Debug)]
+pub enum CompositeError {
+    #[error("No backend own {0}")]
+    NotFound(String),
+    #[error("{0} is already used by another backend")]
+    DuplicateId(String),
+}
+
+struct Backend {
+    /// Prefix of the ids of this backend
+    namespace: Option<String>,
+    bridge: Box<dyn HardwareBridge>,
+}
+
+impl Backend {
+    fn name(&self) -> &str {
+        self.namespace.as_deref().unwrap_or("hardware")
+    }
+}
+
+/// Each item of the merged hardware has the index of its entry in
+/// `sensors` or `controls` as internal index. The entry hold the
+/// owning backend, and the item as known by this backend.
+#[derive(Default)]
+pub struct CompositeBridge {
+    backends: Vec<Backend>,
+    hardware: Hardware,
+    sensors: Vec<(usize, Arc<HSensor>)>,
+    controls: Vec<(usize, Arc<HControl>)>,
+}
+
+impl CompositeBridge {
+    /// Add the hardware of a bridge. Its ids are prefixed by `{namespace}/`,
+    /// or kept as is without namespace. The bridge is rejected if one of
+    /// its ids is already taken. After a rescan, such items are ignored.
+    /// Configs using the ids of a bridge before it got a namespace are migrated.
+    pub fn push(
+        &mut self,
+        namespace: Option<&str>,
+        bridge: impl HardwareBridge + 'static,
+    ) -> crate::Result<()> {
+        if let Some(hardware_id) = bridge
+            .hardware()
+            .ids()
+            .map(|hardware_id| namespaced(namespace, hardware_id))
+            .find(|hardware_id| self.hardware.ids().any(|taken| taken == hardware_id))
+        {
+            return Err(CompositeError::DuplicateId(hardware_id).into());
+        }
+
+        self.backends.push(Backend {
+            namespace: namespace.map(str::to_owned),
+            bridge: Box::new(bridge),
+        });
+        self.merge();
+        Ok(())
+    }
+
+    /// Build the merged hardware from the hardware of each backend.
+    fn merge(&mut self) {
+        self.hardware = Hardware::default();
+        self.sensors.clear();
+        self.controls.clear();
+        let mut ids = HashSet::new();
+
+        for (backend_index, backend) in self.backends.iter().enumerate() {
+            let namespace = backend.namespace.as_deref();
+            let hardware = backend.bridge.hardware();
+
+            for (sensors, merged) in [
+                (&hardware.temps, &mut self.hardware.temps),
+                (&hardware.fans, &mut self.hardware.fans),
+                (&hardware.sensors, &mut self.hardware.sensors),
+            ] {
+                for sensor in sensors {
+                    let hardware_id = namespaced(namespace, &sensor.hardware_id);
+                    if !ids.insert(hardware_id.clone()) {
+                        warn!("{hardware_id} is already used by another backend");
+                        continue;
+                    }
+                    merged.push(Arc::new(HSensor {
+                        name: sensor.name.clone(),
+                        hardware_id,
+                        info: sensor.info.clone(),
+                        limits: sensor.limits,
+                        kind: sensor.kind,
+                        internal_index: self.sensors.len(),
+                    }));
+                    self.sensors.push((backend_index, sensor.clone()));
+                }
+            }
+
+            for control in &hardware.controls {
+                let hardware_id = namespaced(namespace, &control.hardware_id);
+                if !ids.insert(hardware_id.clone()) {
+                    warn!("{hardware_id} is already used by another backend");
+                    continue;
+                }
+                self.hardware.controls.push(Arc::new(HControl {
+                    name: control.name.clone(),
+                    hardware_id,
+                    info: control.info.clone(),
+                    kind: control.kind,
+                    range: control.range,
+                    capabilities: control.capabilities,
+                    internal_index: self.controls.len(),
+                }));
+                self.controls.push((backend_index, control.clone()));
+            }
+
+            for (legacy_id, hardware_id) in &hardware.legacy_ids {
+                self.hardware.add_legacy_id(
+                    namespaced(namespace, legacy_id),
+                    &namespaced(namespace, hardware_id),
+                );
+            }
+        }
+
+        for backend in &self.backends {
+            let Some(namespace) = backend.namespace.as_deref() else {
+                continue;
+            };
+            for hardware_id in backend.bridge.hardware().ids() {
+                if !ids.contains(hardware_id) {
+                    self.hardware.add_legacy_id(
+                        hardware_id.clone(),
+                        &namespaced(Some(namespace), hardware_id),
+                    );
+                }
+            }
+        }
+    }
+
+    fn sensor(
+        &mut self,
+        sensor: &HSensor,
+    ) -> crate::Result<(&mut dyn HardwareBridge, Arc<HSensor>)> {
+        match self.sensors.get(sensor.internal_index) {
+            Some((backend, inner)) => Ok((self.backends[*backend].bridge.as_mut(), inner.clone())),
+            None => Err(CompositeError::NotFound(sensor.hardware_id.clone()).into()),
+        }
+    }
+
+    fn control(
+        &mut self,
+        control: &HControl,
+    ) -> crate::Result<(&mut dyn HardwareBridge, Arc<HControl>)> {
+        match self.controls.get(control.internal_index) {
+            Some((backend, inner)) => Ok((self.backends[*backend].bridge.as_mut(), inner.clone())),
+            None => Err(CompositeError::NotFound(control.hardware_id.clone()).into()),
+        }
+    }
+}
+
+fn namespaced(namespace: Option<&str>, hardware_id: &str) -> String {
+    match namespace {
+        Some(namespace) => format!("{namespace}/{hardware_id}"),
+        None => hardware_id.to_owned(),
+    }
+}
+
+impl HardwareBridge for CompositeBridge {
+    fn new() -> crate::Result<Self> {
+        let mut bridge = Self::default();
+        bridge.push(None, crate::new()?)?;
+        Ok(bridge)
+    }
+
+    fn hardware(&self) -> &Hardware {
+        &self.hardware
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<SensorValue> {
+        let (bridge, sensor) = self.sensor(sensor)?;
+        bridge.get_sensor_value(&sensor)
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        let (bridge, control) = self.control(control)?;
+        bridge.get_control_value(&control)
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        let (bridge, control) = self.control(control)?;
+        bridge.set_value(&control, value)
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        let (bridge, control) = self.control(control)?;
+        bridge.set_mode(&control, mode)
+    }
+
+    fn get_frequency(&mut self, control: &HControl) -> crate::Result<Value> {
+        let (bridge, control) = self.control(control)?;
+        bridge.get_frequency(&control)
+    }
+
+    fn set_frequency(&mut self, control: &HControl, frequency: Value) -> crate::Result<()> {
+        let (bridge, control) = self.control(control)?;
+        bridge.set_frequency(&control, frequency)
+    }
+
+    fn get_output_mode(&mut self, control: &HControl) -> crate::Result<OutputMode> {
+        let (bridge, control) = self.control(control)?;
+        bridge.get_output_mode(&control)
+    }
+
+    fn set_output_mode(&mut self, control: &HControl, mode: OutputMode) -> crate::Result<()> {
+        let (bridge, control) = self.control(control)?;
+        bridge.set_output_mode(&control, mode)
+    }
+
+    /// A backend which fails doesn't stop the others from updating:
+    /// the error is only returned when all of them failed.
+    fn update(&mut self) -> crate::Result<()> {
+        let mut last_error = None;
+        let mut failed = 0;
+
+        for backend in &mut self.backends {
+            if let Err(e) = backend.bridge.update() {
+                error!("Can't update {}: {e}", backend.name());
+                failed += 1;
+                last_error = Some(e);
+            }
+        }
+
+        match last_error {
+            Some(e) if failed == self.backends.len() => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Like update, a backend which fails to rescan keeps its hardware.
+    fn rescan(&mut self) -> crate::Result<HardwareDiff> {
+        let mut last_error = None;
+        let mut failed = 0;
+
+        for backend in &mut self.backends {
+            if let Err(e) = backend.bridge.rescan() {
+                error!("Can't rescan {}: {e}", backend.name());
+                failed += 1;
+                last_error = Some(e);
+            }
+        }
+
+        let previous = std::mem::take(&mut self.hardware);
+        self.merge();
+
+        match last_error {
+            Some(e) if failed == self.backends.len() => Err(e),
+            _ => Ok(previous.diff(&self.hardware)),
+        }
+    }
+
+    /// Every backend is shut down, even if one fails.
+    fn shutdown(&mut self) -> crate::Result<()> {
+        let mut result = Ok(());
+
+        for backend in &mut self.backends {
+            if let Err(e) = backend.bridge.shutdown() {
+                error!("Can't shutdown {}: {e}", backend.name());
+                result = Err(e);
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, sync::Arc};
+
+    use super::{CompositeBridge, CompositeError};
+    use crate::{
+        HSensor, Hardware, HardwareBridge, HardwareError, Mode, SensorValue, Value,
+        record::{Record, ReplayBridge},
+    };
+
+    /// A backend whose update always fails
+    struct Broken(Hardware);
+
+    impl HardwareBridge for Broken {
+        fn new() -> crate::Result<Self> {
+            unreachable!()
+        }
+
+        fn hardware(&self) -> &Hardware {
+            &self.0
+        }
+
+        fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<SensorValue> {
+            Err(HardwareError::Unsupported(
+                "value",
+                sensor.hardware_id.clone(),
+            ))
+        }
+
+        fn get_control_value(&mut self, control: &crate::HControl) -> crate::Result<Value> {
+            Err(HardwareError::Unsupported(
+                "value",
+                control.hardware_id.clone(),
+            ))
+        }
+
+        fn set_value(&mut self, control: &crate::HControl, _: Value) -> crate::Result<()> {
+            Err(HardwareError::Unsupported(
+                "value",
+                control.hardware_id.clone(),
+            ))
+        }
+
+        fn set_mode(&mut self, control: &crate::HControl, _: &Mode) -> crate::Result<()> {
+            Err(HardwareError::Unsupported(
+                "mode",
+                control.hardware_id.clone(),
+            ))
+        }
+
+        fn update(&mut self) -> crate::Result<()> {
+            Err(HardwareError::Unsupported("update", "broken".into()))
+        }
+    }
+
+    fn hardware(ids: &[&str]) -> Hardware {
+        Hardware {
+            temps: ids
+                .iter()
+                .map(|id| {
+                    Arc::new(HSensor {
+                        name: id.to_string(),
+                        hardware_id: id.to_string(),
+                        info: String::new(),
+                        limits: Default::default(),
+                        kind: None,
+                        internal_index: 0,
+                    })
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn replay(ids: &[&str], value: SensorValue) -> ReplayBridge {
+        let mut records = vec![Record::Hardware {
+            hardware: hardware(ids),
+        }];
+        for id in ids {
+            records.push(Record::SensorValue {
+                time: 0,
+                id: id.to_string(),
+                result: Ok(value),
+            });
+        }
+        ReplayBridge::from_records(records).unwrap()
+    }
+
+    #[test]
+    fn test_composite() {
+        let mut bridge = CompositeBridge::default();
+        bridge
+            .push(None, replay(&["temp1", "temp2"], 40.0))
+            .unwrap();
+        bridge
+            .push(Some("remote"), replay(&["temp1"], 50.0))
+            .unwrap();
+        // the id is already taken by the first backend
+        assert!(matches!(
+            bridge.push(None, replay(&["temp3", "temp2"], 60.0)),
+            Err(HardwareError::Composite(CompositeError::DuplicateId(id))) if id == "temp2"
+        ));
+        bridge
+            .push(Some("broken"), Broken(hardware(&["temp1"])))
+            .unwrap();
+
+        let ids = bridge
+            .hardware()
+            .temps
+            .iter()
+            .map(|h| h.hardware_id.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["temp1", "temp2", "remote/temp1", "broken/temp1"]);
+
+        let temps = bridge.hardware().temps.clone();
+        assert_eq!(bridge.get_sensor_value(&temps[1]).unwrap(), 40.0);
+        assert_eq!(bridge.get_sensor_value(&temps[2]).unwrap(), 50.0);
+        assert!(bridge.get_sensor_value(&temps[3]).is_err());
+
+        // one broken backend doesn't stop the others
+        bridge.update().unwrap();
+        assert_eq!(bridge.get_sensor_value(&temps[0]).unwrap(), 40.0);
+
+        let mut bridge = CompositeBridge::default();
+        bridge
+            .push(Some("broken"), Broken(hardware(&["temp1"])))
+            .unwrap();
+        assert!(bridge.update().is_err());
+    }
+
+    #[test]
+    fn test_namespace_migration() {
+        let mut bridge = CompositeBridge::default();
+        bridge.push(None, replay(&["temp1"], 40.0)).unwrap();
+        bridge
+            .push(Some("file"), replay(&["temp1", "pump"], 50.0))
+            .unwrap();
+
+        // temp1 still belongs to the first backend
+        assert_eq!(
+            bridge.hardware().legacy_ids,
+            HashMap::from([("pump".to_owned(), "file/pump".to_owned())])
+        );
+    }
+}
@@ -1,82 +1,726 @@
-use rand::RngExt;
+use std::{
+    f64::consts::PI,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use crate::{HControl, HSensor, Hardware, HardwareBridge, Mode, Value};
+use serde::Deserialize;
+use thiserror::Error;
 
+use crate::{
+    Capabilities, ControlKind, ControlRange, HControl, HSensor, Hardware, HardwareBridge,
+    HardwareDiff, HardwareError, Mode, OutputMode, SensorKind, SensorLimits, SensorValue, Value,
+};
+
+/// Fake bridge driven by a scenario: each sensor follows a timeline,
+/// and every call made on a control is recorded.
 pub struct FakeHardwareBridge {
+    /// Items plugged at the last scan
     hardware: Hardware,
+    /// Every item of the scenario
+    scenario_hardware: Hardware,
+    /// Indexed by internal_index
+    timelines: Vec<Timeline>,
+    /// Indexed by internal_index
+    sensors_unplugged: Vec<Vec<[f64; 2]>>,
+    /// Indexed by internal_index
+    controls_unplugged: Vec<Vec<[f64; 2]>>,
+    /// Indexed by internal_index
+    control_values: Vec<Value>,
+    /// Indexed by internal_index, None when the control doesn't support it
+    frequencies: Vec<Option<Value>>,
+    /// Indexed by internal_index, None when the control doesn't support it
+    output_modes: Vec<Option<OutputMode>>,
+    /// Seconds elapsed between two calls of update
+    step: f64,
+    tick: usize,
+    calls: Vec<Call>,
 }
 
-static TEMP_INTERNAL_INDEX: usize = 0;
-static FAN_INTERNAL_INDEX: usize = 1;
-static CONTROL_INTERNAL_INDEX: usize = 2;
+#[derive(Error, Debug)]
+pub enum FakeError {
+    #[error("{0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Failed to parse scenario: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Invalid samples in {0}, line {1}")]
+    Csv(PathBuf, usize),
+    #[error("{0} is unplugged")]
+    Unplugged(String),
+}
 
-impl HardwareBridge for FakeHardwareBridge {
-    fn new() -> crate::Result<Self> {
-        let mut hardware = Hardware::default();
+type Result<T> = std::result::Result<T, FakeError>;
+
+/// A call made by the app on a control.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    SetValue {
+        tick: usize,
+        hardware_id: String,
+        value: Value,
+    },
+    SetMode {
+        tick: usize,
+        hardware_id: String,
+        mode: Mode,
+    },
+    SetFrequency {
+        tick: usize,
+        hardware_id: String,
+        frequency: Value,
+    },
+    SetOutputMode {
+        tick: usize,
+        hardware_id: String,
+        mode: OutputMode,
+    },
+}
+
+/// Values set on a control, in order.
+pub fn values_set(calls: &[Call], hardware_id: &str) -> Vec<Value> {
+    calls
+        .iter()
+        .filter_map(|call| match call {
+            Call::SetValue {
+                hardware_id: id,
+                value,
+                ..
+            } if id == hardware_id => Some(*value),
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Seconds elapsed between two calls of update
+    #[serde(default = "default_step")]
+    pub step: f64,
+    #[serde(default, rename = "Temp")]
+    pub temps: Vec<ScenarioSensor>,
+    #[serde(default, rename = "Fan")]
+    pub fans: Vec<ScenarioSensor>,
+    /// Needs a `kind`
+    #[serde(default, rename = "Sensor")]
+    pub sensors: Vec<ScenarioSensor>,
+    #[serde(default, rename = "Control")]
+    pub controls: Vec<ScenarioControl>,
+}
+
+fn default_step() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioSensor {
+    pub id: String,
+    pub name: Option<String>,
+    pub timeline: Timeline,
+    #[serde(default)]
+    pub limits: SensorLimits,
+    pub kind: Option<SensorKind>,
+    /// `[start, end)` intervals of seconds during which the sensor is unplugged
+    #[serde(default)]
+    pub unplugged: Vec<[f64; 2]>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioControl {
+    pub id: String,
+    pub name: Option<String>,
+    /// Value returned before any set_value
+    #[serde(default)]
+    pub value: Value,
+    #[serde(default)]
+    pub kind: ControlKind,
+    /// Native range, a percentage by default
+    #[serde(default)]
+    pub range: ControlRange,
+    /// Initial pwm frequency, if the control has one
+    pub frequency: Option<Value>,
+    /// Initial output mode, if the control has one
+    #[serde(rename = "outputMode")]
+    pub output_mode: Option<OutputMode>,
+    /// `[start, end)` intervals of seconds during which the control is unplugged
+    #[serde(default)]
+    pub unplugged: Vec<[f64; 2]>,
+}
+
+fn plugged_at(unplugged: &[[f64; 2]], time: f64) -> bool {
+    !unplugged
+        .iter()
+        .any(|[start, end]| time >= *start && time < *end)
+}
+
+/// Value of a sensor, as a function of the time in seconds.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Timeline {
+    Constant {
+        value: f64,
+    },
+    /// Go from `from` to `to` in `duration` seconds, starting at `start`
+    Ramp {
+        from: f64,
+        to: f64,
+        duration: f64,
+        #[serde(default)]
+        start: f64,
+    },
+    /// Jump from `before` to `after` at `at` seconds
+    Step {
+        before: f64,
+        after: f64,
+        at: f64,
+    },
+    Sine {
+        center: f64,
+        amplitude: f64,
+        period: f64,
+    },
+    /// Linear interpolation between `time,value` lines of a CSV file.
+    /// The path is relative to the scenario file.
+    Samples {
+        path: PathBuf,
+        #[serde(skip)]
+        samples: Vec<(f64, f64)>,
+    },
+}
+
+impl Timeline {
+    pub fn value_at(&self, time: f64) -> f64 {
+        match self {
+            Timeline::Constant { value } => *value,
+            Timeline::Ramp {
+                from,
+                to,
+                duration,
+                start,
+            } => {
+                if time <= *start {
+                    *from
+                } else if time >= start + duration {
+                    *to
+                } else {
+                    from + (to - from) * (time - start) / duration
+                }
+            }
+            Timeline::Step { before, after, at } => {
+                if time < *at {
+                    *before
+                } else {
+                    *after
+                }
+            }
+            Timeline::Sine {
+                center,
+                amplitude,
+                period,
+            } => center + amplitude * (2.0 * PI * time / period).sin(),
+            Timeline::Samples { samples, .. } => {
+                let next = samples.partition_point(|(t, _)| *t <= time);
+
+                match (
+                    next.checked_sub(1).map(|i| samples[i]),
+                    samples.get(next).copied(),
+                ) {
+                    (Some((t1, v1)), Some((t2, v2))) => v1 + (v2 - v1) * (time - t1) / (t2 - t1),
+                    (Some((_, v)), None) | (None, Some((_, v))) => v,
+                    (None, None) => 0.0,
+                }
+            }
+        }
+    }
+
+    pub(crate) fn load_samples(&mut self, dir: &Path) -> Result<()> {
+        let Timeline::Samples { path, samples } = self else {
+            return Ok(());
+        };
 
-        let temp1 = HSensor {
-            name: "temp1".into(),
-            hardware_id: "temp1".into(),
-            info: String::new(),
-            internal_index: TEMP_INTERNAL_INDEX,
+        let path = dir.join(path);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                return Err(FakeError::Io(
+                    format!("can't read samples {}", path.display()),
+                    e,
+                ));
+            }
         };
-        hardware.temps.push(temp1.into());
 
-        let temp2 = HSensor {
-            name: "temp2".into(),
-            hardware_id: "temp2".into(),
-            info: String::new(),
-            internal_index: TEMP_INTERNAL_INDEX,
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let sample = line
+                .split_once(',')
+                .and_then(|(t, v)| Some((t.trim().parse().ok()?, v.trim().parse().ok()?)));
+
+            match sample {
+                Some(sample) => samples.push(sample),
+                // allow a header
+                None if i == 0 => continue,
+                None => return Err(FakeError::Csv(path, i + 1)),
+            }
+        }
+
+        samples.sort_by(|(t1, _), (t2, _)| t1.total_cmp(t2));
+        Ok(())
+    }
+}
+
+impl Scenario {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                return Err(FakeError::Io(
+                    format!("can't read scenario {}", path.display()),
+                    e,
+                ));
+            }
         };
-        hardware.temps.push(temp2.into());
 
-        let fan1 = HSensor {
-            name: "fan1".into(),
-            hardware_id: "fan1".into(),
-            info: String::new(),
-            internal_index: FAN_INTERNAL_INDEX,
+        let mut scenario = toml::from_str::<Scenario>(&content)?;
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for sensor in scenario
+            .temps
+            .iter_mut()
+            .chain(scenario.fans.iter_mut())
+            .chain(scenario.sensors.iter_mut())
+        {
+            sensor.timeline.load_samples(dir)?;
+        }
+
+        Ok(scenario)
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        let sensor = |id: &str, timeline| ScenarioSensor {
+            id: id.into(),
+            name: None,
+            timeline,
+            limits: SensorLimits::default(),
+            kind: None,
+            unplugged: Vec::new(),
+        };
+        let control = |id: &str| ScenarioControl {
+            id: id.into(),
+            name: None,
+            value: 0,
+            kind: ControlKind::default(),
+            range: ControlRange::default(),
+            frequency: None,
+            output_mode: None,
+            unplugged: Vec::new(),
         };
-        hardware.fans.push(fan1.into());
 
-        let control1 = HControl {
-            name: "control1".into(),
-            hardware_id: "control1".into(),
-            info: String::new(),
-            internal_index: CONTROL_INTERNAL_INDEX,
+        Self {
+            step: default_step(),
+            temps: vec![
+                sensor(
+                    "temp1",
+                    Timeline::Sine {
+                        center: 55.0,
+                        amplitude: 25.0,
+                        period: 60.0,
+                    },
+                ),
+                sensor(
+                    "temp2",
+                    Timeline::Sine {
+                        center: 45.0,
+                        amplitude: 15.0,
+                        period: 25.0,
+                    },
+                ),
+            ],
+            fans: vec![sensor(
+                "fan1",
+                Timeline::Sine {
+                    center: 1200.0,
+                    amplitude: 400.0,
+                    period: 60.0,
+                },
+            )],
+            sensors: Vec::new(),
+            controls: vec![control("control1"), control("control2")],
+        }
+    }
+}
+
+impl FakeHardwareBridge {
+    pub fn from_scenario(scenario: Scenario) -> Self {
+        let mut hardware = Hardware::default();
+        let mut timelines = Vec::new();
+        let mut sensors_unplugged = Vec::new();
+        let mut controls_unplugged = Vec::new();
+        let mut control_values = Vec::new();
+        let mut frequencies = Vec::new();
+        let mut output_modes = Vec::new();
+
+        for (list, sensors) in [
+            (&mut hardware.temps, scenario.temps),
+            (&mut hardware.fans, scenario.fans),
+            (&mut hardware.sensors, scenario.sensors),
+        ] {
+            for sensor in sensors {
+                list.push(Arc::new(HSensor {
+                    name: sensor.name.unwrap_or_else(|| sensor.id.clone()),
+                    hardware_id: sensor.id,
+                    info: String::new(),
+                    limits: sensor.limits,
+                    kind: sensor.kind,
+                    internal_index: timelines.len(),
+                }));
+                timelines.push(sensor.timeline);
+                sensors_unplugged.push(sensor.unplugged);
+            }
+        }
+
+        for control in scenario.controls {
+            hardware.controls.push(Arc::new(HControl {
+                name: control.name.unwrap_or_else(|| control.id.clone()),
+                hardware_id: control.id,
+                info: String::new(),
+                kind: control.kind,
+                range: control.range,
+                capabilities: Capabilities {
+                    frequency: control.frequency.is_some(),
+                    output_mode: control.output_mode.is_some(),
+                },
+                internal_index: control_values.len(),
+            }));
+            control_values.push(control.value);
+            frequencies.push(control.frequency);
+            output_modes.push(control.output_mode);
+            controls_unplugged.push(control.unplugged);
+        }
+
+        let mut bridge = Self {
+            hardware: Hardware::default(),
+            scenario_hardware: hardware,
+            timelines,
+            sensors_unplugged,
+            controls_unplugged,
+            control_values,
+            frequencies,
+            output_modes,
+            step: scenario.step,
+            tick: 0,
+            calls: Vec::new(),
         };
-        hardware.controls.push(control1.into());
+        bridge.hardware = bridge.plugged_hardware();
+        bridge
+    }
 
-        let control2 = HControl {
-            name: "control2".into(),
-            hardware_id: "control2".into(),
-            info: String::new(),
-            internal_index: CONTROL_INTERNAL_INDEX,
+    /// Items of the scenario plugged at the current time.
+    fn plugged_hardware(&self) -> Hardware {
+        let time = self.time();
+        // new items, the app rejects a control already bound elsewhere
+        let sensors = |list: &Vec<Arc<HSensor>>| {
+            list.iter()
+                .filter(|h| plugged_at(&self.sensors_unplugged[h.internal_index], time))
+                .map(|h| {
+                    Arc::new(HSensor {
+                        name: h.name.clone(),
+                        hardware_id: h.hardware_id.clone(),
+                        info: h.info.clone(),
+                        limits: h.limits,
+                        kind: h.kind,
+                        internal_index: h.internal_index,
+                    })
+                })
+                .collect()
         };
-        hardware.controls.push(control2.into());
 
-        Ok(Self { hardware })
+        Hardware {
+            controls: self
+                .scenario_hardware
+                .controls
+                .iter()
+                .filter(|h| plugged_at(&self.controls_unplugged[h.internal_index], time))
+                .map(|h| {
+                    Arc::new(HControl {
+                        name: h.name.clone(),
+                        hardware_id: h.hardware_id.clone(),
+                        info: h.info.clone(),
+                        kind: h.kind,
+                        range: h.range,
+                        capabilities: h.capabilities,
+                        internal_index: h.internal_index,
+                    })
+                })
+                .collect(),
+            fans: sensors(&self.scenario_hardware.fans),
+            temps: sensors(&self.scenario_hardware.temps),
+            sensors: sensors(&self.scenario_hardware.sensors),
+            ..Default::default()
+        }
+    }
+
+    fn check_sensor(&self, sensor: &HSensor) -> Result<()> {
+        if plugged_at(&self.sensors_unplugged[sensor.internal_index], self.time()) {
+            Ok(())
+        } else {
+            Err(FakeError::Unplugged(sensor.hardware_id.clone()))
+        }
+    }
+
+    fn check_control(&self, control: &HControl) -> Result<()> {
+        if plugged_at(
+            &self.controls_unplugged[control.internal_index],
+            self.time(),
+        ) {
+            Ok(())
+        } else {
+            Err(FakeError::Unplugged(control.hardware_id.clone()))
+        }
+    }
+
+    pub fn from_file(path: &Path) -> crate::Result<Self> {
+        let scenario = Scenario::from_file(path)?;
+        Ok(Self::from_scenario(scenario))
+    }
+
+    /// Number of update since the creation of the bridge.
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    /// Time of the scenario, in seconds.
+    pub fn time(&self) -> f64 {
+        self.tick as f64 * self.step
+    }
+
+    /// All calls made on controls, in order.
+    pub fn calls(&self) -> &[Call] {
+        &self.calls
+    }
+
+    /// Values set on a control, in order.
+    pub fn values_set(&self, hardware_id: &str) -> Vec<Value> {
+        values_set(&self.calls, hardware_id)
+    }
+}
+
+impl HardwareBridge for FakeHardwareBridge {
+    fn new() -> crate::Result<Self> {
+        Ok(Self::from_scenario(Scenario::default()))
     }
+
     fn hardware(&self) -> &Hardware {
         &self.hardware
     }
 
-    fn get_sensor_value(&mut self, _sensor: &HSensor) -> crate::Result<Value> {
-        let nb = rand::rng().random_range(30..80);
-        Ok(nb)
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<SensorValue> {
+        self.check_sensor(sensor)?;
+        let timeline = &self.timelines[sensor.internal_index];
+        Ok(timeline.value_at(self.time()))
     }
 
-    fn get_control_value(&mut self, _control: &HControl) -> crate::Result<Value> {
-        let nb = rand::rng().random_range(30..80);
-        Ok(nb)
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        self.check_control(control)?;
+        Ok(self.control_values[control.internal_index])
     }
 
-    fn set_value(&mut self, _control: &HControl, value: Value) -> crate::Result<()> {
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        self.check_control(control)?;
         debug!("set value {value}");
+        self.control_values[control.internal_index] = value;
+        self.calls.push(Call::SetValue {
+            tick: self.tick,
+            hardware_id: control.hardware_id.clone(),
+            value,
+        });
         Ok(())
     }
 
-    fn set_mode(&mut self, _control: &HControl, mode: &Mode) -> crate::Result<()> {
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        self.check_control(control)?;
         debug!("set mode {mode}");
+        self.calls.push(Call::SetMode {
+            tick: self.tick,
+            hardware_id: control.hardware_id.clone(),
+            mode: mode.clone(),
+        });
+        Ok(())
+    }
+
+    fn get_frequency(&mut self, control: &HControl) -> crate::Result<Value> {
+        self.check_control(control)?;
+        self.frequencies[control.internal_index]
+            .ok_or_else(|| HardwareError::Unsupported("pwm frequency", control.hardware_id.clone()))
+    }
+
+    fn set_frequency(&mut self, control: &HControl, frequency: Value) -> crate::Result<()> {
+        self.check_control(control)?;
+        let current = &mut self.frequencies[control.internal_index];
+        if current.is_none() {
+            return Err(HardwareError::Unsupported(
+                "pwm frequency",
+                control.hardware_id.clone(),
+            ));
+        }
+        *current = Some(frequency);
+        self.calls.push(Call::SetFrequency {
+            tick: self.tick,
+            hardware_id: control.hardware_id.clone(),
+            frequency,
+        });
+        Ok(())
+    }
+
+    fn get_output_mode(&mut self, control: &HControl) -> crate::Result<OutputMode> {
+        self.check_control(control)?;
+        self.output_modes[control.internal_index]
+            .ok_or_else(|| HardwareError::Unsupported("output mode", control.hardware_id.clone()))
+    }
+
+    fn set_output_mode(&mut self, control: &HControl, mode: OutputMode) -> crate::Result<()> {
+        self.check_control(control)?;
+        let current = &mut self.output_modes[control.internal_index];
+        if current.is_none() {
+            return Err(HardwareError::Unsupported(
+                "output mode",
+                control.hardware_id.clone(),
+            ));
+        }
+        *current = Some(mode);
+        self.calls.push(Call::SetOutputMode {
+            tick: self.tick,
+            hardware_id: control.hardware_id.clone(),
+            mode,
+        });
+        Ok(())
+    }
+
+    fn update(&mut self) -> crate::Result<()> {
+        self.tick += 1;
         Ok(())
     }
+
+    fn rescan(&mut self) -> crate::Result<HardwareDiff> {
+        let hardware = self.plugged_hardware();
+        let diff = self.hardware.diff(&hardware);
+        self.hardware = hardware;
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FakeHardwareBridge, Scenario, Timeline};
+    use crate::{HardwareBridge, HardwareDiff, Mode, OutputMode};
+
+    #[test]
+    fn test_timelines() {
+        let ramp = Timeline::Ramp {
+            from: 30.0,
+            to: 80.0,
+            duration: 10.0,
+            start: 5.0,
+        };
+        assert_eq!(ramp.value_at(0.0), 30.0);
+        assert_eq!(ramp.value_at(10.0), 55.0);
+        assert_eq!(ramp.value_at(20.0), 80.0);
+
+        let step = Timeline::Step {
+            before: 40.0,
+            after: 90.0,
+            at: 3.0,
+        };
+        assert_eq!(step.value_at(2.9), 40.0);
+        assert_eq!(step.value_at(3.0), 90.0);
+
+        let sine = Timeline::Sine {
+            center: 50.0,
+            amplitude: 10.0,
+            period: 4.0,
+        };
+        assert_eq!(sine.value_at(1.0), 60.0);
+
+        let samples = Timeline::Samples {
+            path: Default::default(),
+            samples: vec![(0.0, 40.0), (10.0, 60.0)],
+        };
+        assert_eq!(samples.value_at(-1.0), 40.0);
+        assert_eq!(samples.value_at(5.0), 50.0);
+        assert_eq!(samples.value_at(11.0), 60.0);
+    }
+
+    #[test]
+    fn test_parse() {
+        let scenario = toml::from_str::<Scenario>(
+            r#"
+            step = 0.5
+
+            [[Temp]]
+            id = "cpu"
+            timeline = { kind = "ramp", from = 30, to = 80, duration = 10 }
+
+            [[Fan]]
+            id = "fan1"
+            name = "Fan 1"
+            timeline = { kind = "constant", value = 1200 }
+
+            [[Control]]
+            id = "pwm1"
+            value = 40
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(scenario.step, 0.5);
+        assert_eq!(scenario.temps.len(), 1);
+        assert_eq!(scenario.fans[0].name.as_deref(), Some("Fan 1"));
+        assert_eq!(scenario.controls[0].value, 40);
+    }
+
+    #[test]
+    fn test_hotplug() {
+        let mut scenario = Scenario::default();
+        scenario.controls[0].unplugged = vec![[2.0, f64::INFINITY]];
+        scenario.controls[1].unplugged = vec![[0.0, 1.0]];
+        scenario.controls[0].frequency = Some(25000);
+        scenario.controls[0].output_mode = Some(OutputMode::Pwm);
+        let mut bridge = FakeHardwareBridge::from_scenario(scenario);
+
+        let control1 = bridge.hardware().controls[0].clone();
+        assert_eq!(bridge.hardware().controls.len(), 1);
+        bridge.set_mode(&control1, &Mode::Manual).unwrap();
+
+        // hardware is only updated by a rescan
+        bridge.update().unwrap();
+        assert_eq!(bridge.hardware().controls.len(), 1);
+        assert_eq!(
+            bridge.rescan().unwrap(),
+            HardwareDiff {
+                added: vec!["control2".into()],
+                removed: vec![],
+            }
+        );
+
+        bridge.update().unwrap();
+        assert!(bridge.set_value(&control1, 50).is_err());
+        assert!(bridge.set_frequency(&control1, 30000).is_err());
+        assert!(bridge.set_output_mode(&control1, OutputMode::Dc).is_err());
+        assert_eq!(
+            bridge.rescan().unwrap(),
+            HardwareDiff {
+                added: vec![],
+                removed: vec!["control1".into()],
+            }
+        );
+        assert_eq!(bridge.hardware().controls[0].hardware_id, "control2");
+    }
 }
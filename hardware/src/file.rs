@@ -0,0 +1,348 @@
+//! Sensors and controls backed by arbitrary files, declared by the user
+//! for drivers that fan-control doesn't know about.
+//! Merged with the other hardware by [`crate::composite::CompositeBridge`].
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    Capabilities, ControlKind, ControlRange, HControl, HSensor, Hardware, HardwareBridge, Mode,
+    SensorKind, SensorValue, Value,
+};
+
+#[derive(Error, Debug)]
+pub enum FileError {
+    #[error("{0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Can't parse \"{1}\" read from {0}")]
+    Parse(PathBuf, String),
+    #[error("{0} has no enable file")]
+    NoEnable(String),
+    #[error("No file declared for {0}")]
+    NotFound(String),
+}
+
+type Result<T> = std::result::Result<T, FileError>;
+
+/// Files declared in `files.toml`, next to the configs.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FileEndpoints {
+    #[serde(default, rename = "Control")]
+    pub controls: Vec<FileControl>,
+    #[serde(default, rename = "Fan")]
+    pub fans: Vec<FileSensor>,
+    #[serde(default, rename = "Temp")]
+    pub temps: Vec<FileSensor>,
+    /// Need a `kind`
+    #[serde(default, rename = "Sensor")]
+    pub sensors: Vec<FileSensor>,
+}
+
+/// A number read from a file, as `value * scale + offset`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileSensor {
+    pub name: String,
+    /// Default to `file:{path}`
+    pub id: Option<String>,
+    pub path: PathBuf,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub offset: f64,
+    /// What an item of `Sensor` measures, the value being in its unit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SensorKind>,
+}
+
+/// A value written to a file, in `range`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileControl {
+    pub name: String,
+    /// Default to `file:{path}`
+    pub id: Option<String>,
+    pub path: PathBuf,
+    #[serde(default)]
+    pub range: ControlRange,
+    /// File selecting who drive the output, like `pwm{N}_enable`
+    pub enable: Option<FileEnable>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileEnable {
+    pub path: PathBuf,
+    /// Written to take control of the output
+    pub manual: String,
+    /// Written to give the control back. Default to
+    /// the content of the file when fan-control started.
+    pub auto: Option<String>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+fn file_id(id: &Option<String>, path: &Path) -> String {
+    match id {
+        Some(id) => id.clone(),
+        None => format!("file:{}", path.display()),
+    }
+}
+
+fn read_string(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content.trim().to_owned()),
+        Err(e) => Err(FileError::Io(format!("can't read {}", path.display()), e)),
+    }
+}
+
+fn read_number(path: &Path) -> Result<f64> {
+    let content = read_string(path)?;
+    content
+        .parse::<f64>()
+        .map_err(|_| FileError::Parse(path.to_path_buf(), content))
+}
+
+fn write_string(path: &Path, value: &str) -> Result<()> {
+    fs::write(path, value)
+        .map_err(|e| FileError::Io(format!("can't write {value} to {}", path.display()), e))
+}
+
+impl FileSensor {
+    fn get_value(&self) -> Result<SensorValue> {
+        Ok(read_number(&self.path)? * self.scale + self.offset)
+    }
+}
+
+struct ControlState {
+    control: FileControl,
+    /// Content of the enable file before we touch it
+    enable_cached: Option<String>,
+}
+
+impl ControlState {
+    fn get_value(&self) -> Result<Value> {
+        Ok(read_number(&self.control.path)?.round() as Value)
+    }
+
+    fn set_value(&self, value: Value) -> Result<()> {
+        let value = self.control.range.clamp(value);
+        write_string(&self.control.path, &value.to_string())
+    }
+
+    fn set_mode(&mut self, mode: &Mode) -> Result<()> {
+        let Some(enable) = &self.control.enable else {
+            // without enable file, the output is always ours
+            return match mode {
+                Mode::Specific(_) => Err(FileError::NoEnable(self.control.name.clone())),
+                _ => Ok(()),
+            };
+        };
+
+        if self.enable_cached.is_none() {
+            self.enable_cached = Some(read_string(&enable.path)?);
+        }
+
+        let value = match mode {
+            Mode::Manual => enable.manual.clone(),
+            Mode::Specific(value) => value.to_string(),
+            Mode::Auto => match &enable.auto {
+                Some(auto) => auto.clone(),
+                None => self.enable_cached.clone().unwrap(),
+            },
+        };
+        write_string(&enable.path, &value)
+    }
+}
+
+impl Drop for ControlState {
+    fn drop(&mut self) {
+        if self.enable_cached.is_some()
+            && let Err(e) = self.set_mode(&Mode::Auto)
+        {
+            error!("Can't restore {}: {e}", self.control.name);
+        }
+    }
+}
+
+enum Endpoint {
+    Sensor(FileSensor),
+    Control(ControlState),
+}
+
+/// Expose the declared files as hardware.
+pub struct FileBridge {
+    hardware: Hardware,
+    /// Indexed by internal_index
+    endpoints: Vec<Endpoint>,
+}
+
+impl FileBridge {
+    pub fn from_endpoints(files: FileEndpoints) -> Self {
+        let mut hardware = Hardware::default();
+        let mut endpoints = Vec::new();
+        let mut ids = HashSet::new();
+
+        for (sensors, hsensors) in [
+            (files.temps, &mut hardware.temps),
+            (files.fans, &mut hardware.fans),
+            (files.sensors, &mut hardware.sensors),
+        ] {
+            for sensor in sensors {
+                let hardware_id = file_id(&sensor.id, &sensor.path);
+                if !ids.insert(hardware_id.clone()) {
+                    warn!("File {hardware_id} is declared twice");
+                    continue;
+                }
+                hsensors.push(Arc::new(HSensor {
+                    name: sensor.name.clone(),
+                    hardware_id,
+                    info: sensor.path.display().to_string(),
+                    limits: Default::default(),
+                    kind: sensor.kind,
+                    internal_index: endpoints.len(),
+                }));
+                endpoints.push(Endpoint::Sensor(sensor));
+            }
+        }
+
+        for control in files.controls {
+            let hardware_id = file_id(&control.id, &control.path);
+            if !ids.insert(hardware_id.clone()) {
+                warn!("File {hardware_id} is declared twice");
+                continue;
+            }
+            hardware.controls.push(Arc::new(HControl {
+                name: control.name.clone(),
+                hardware_id,
+                info: control.path.display().to_string(),
+                kind: ControlKind::Pwm,
+                range: control.range,
+                capabilities: Capabilities::default(),
+                internal_index: endpoints.len(),
+            }));
+            endpoints.push(Endpoint::Control(ControlState {
+                control,
+                enable_cached: None,
+            }));
+        }
+
+        Self {
+            hardware,
+            endpoints,
+        }
+    }
+
+    fn sensor(&self, sensor: &HSensor) -> Result<&FileSensor> {
+        match self.endpoints.get(sensor.internal_index) {
+            Some(Endpoint::Sensor(file)) => Ok(file),
+            _ => Err(FileError::NotFound(sensor.hardware_id.clone())),
+        }
+    }
+
+    fn control(&mut self, control: &HControl) -> Result<&mut ControlState> {
+        match self.endpoints.get_mut(control.internal_index) {
+            Some(Endpoint::Control(state)) => Ok(state),
+            _ => Err(FileError::NotFound(control.hardware_id.clone())),
+        }
+    }
+}
+
+impl HardwareBridge for FileBridge {
+    fn new() -> crate::Result<Self> {
+        Ok(Self::from_endpoints(FileEndpoints::default()))
+    }
+
+    fn hardware(&self) -> &Hardware {
+        &self.hardware
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<SensorValue> {
+        Ok(self.sensor(sensor)?.get_value()?)
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        Ok(self.control(control)?.get_value()?)
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        Ok(self.control(control)?.set_value(value)?)
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        Ok(self.control(control)?.set_mode(mode)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{FileBridge, FileControl, FileEnable, FileEndpoints, FileSensor};
+    use crate::{ControlRange, HardwareBridge, Mode};
+
+    #[test]
+    fn test_files() {
+        let dir = std::env::temp_dir().join(format!("fan-control-files-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("temp"), "45500\n").unwrap();
+        fs::write(dir.join("duty"), "0").unwrap();
+        fs::write(dir.join("enable"), "2").unwrap();
+
+        let files = FileEndpoints {
+            temps: vec![FileSensor {
+                name: "nvme".into(),
+                id: None,
+                path: dir.join("temp"),
+                scale: 0.001,
+                offset: -5.0,
+                kind: None,
+            }],
+            controls: vec![FileControl {
+                name: "duty".into(),
+                id: Some("duty".into()),
+                path: dir.join("duty"),
+                range: ControlRange {
+                    min: 0,
+                    max: 10,
+                    step: 1,
+                },
+                enable: Some(FileEnable {
+                    path: dir.join("enable"),
+                    manual: "1".into(),
+                    auto: None,
+                }),
+            }],
+            ..Default::default()
+        };
+
+        let mut bridge = FileBridge::from_endpoints(files);
+
+        let temp = bridge.hardware().temps[0].clone();
+        let control = bridge.hardware().controls[0].clone();
+        assert_eq!(
+            temp.hardware_id,
+            format!("file:{}", dir.join("temp").display())
+        );
+        assert_eq!(control.range.max, 10);
+
+        assert_eq!(bridge.get_sensor_value(&temp).unwrap(), 40.5);
+
+        bridge.set_mode(&control, &Mode::Manual).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("enable")).unwrap(), "1");
+        bridge.set_value(&control, 20).unwrap();
+        assert_eq!(bridge.get_control_value(&control).unwrap(), 10);
+
+        // the enable file is restored on exit
+        drop(bridge);
+        assert_eq!(fs::read_to_string(dir.join("enable")).unwrap(), "2");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
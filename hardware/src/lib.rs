@@ -1,28 +1,59 @@
 use derive_more::Display;
-use serde::Serialize;
-use std::{fmt::Debug, rc::Rc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    sync::Arc,
+};
 use thiserror::Error;
 
 #[macro_use]
 extern crate log;
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "libsensors"))]
 pub mod linux;
 
+#[cfg(target_os = "linux")]
+pub mod sysfs;
+
 #[cfg(target_os = "windows")]
 pub mod windows;
 
 #[cfg(feature = "fake_hardware")]
 pub mod fake_hardware;
 
+#[cfg(feature = "fake_hardware")]
+pub mod simulated_hardware;
+
+pub mod composite;
+pub mod file;
+pub mod record;
+pub mod remote;
+
 #[derive(Error, Debug)]
 pub enum HardwareError {
-    #[cfg(target_os = "linux")]
+    #[cfg(all(target_os = "linux", feature = "libsensors"))]
     #[error(transparent)]
     Linux(#[from] linux::LinuxError),
+    #[cfg(target_os = "linux")]
+    #[error(transparent)]
+    Sysfs(#[from] sysfs::SysfsError),
     #[cfg(target_os = "windows")]
     #[error(transparent)]
     Windows(#[from] windows::WindowsError),
+    #[cfg(feature = "fake_hardware")]
+    #[error(transparent)]
+    Fake(#[from] fake_hardware::FakeError),
+    #[error(transparent)]
+    Record(#[from] record::RecordError),
+    #[error(transparent)]
+    File(#[from] file::FileError),
+    #[error(transparent)]
+    Composite(#[from] composite::CompositeError),
+    #[error(transparent)]
+    Remote(#[from] remote::RemoteError),
+    #[error("{0} is not supported by {1}")]
+    Unsupported(&'static str, String),
 }
 
 type Result<T> = std::result::Result<T, HardwareError>;
@@ -33,7 +64,7 @@ pub trait HItem {
     fn info(&self) -> &String;
 }
 
-#[derive(Serialize, Debug, Eq)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct HSensor {
     pub name: String,
     #[serde(rename = "id")]
@@ -42,10 +73,78 @@ pub struct HSensor {
     #[serde(skip)]
     pub info: String,
 
+    #[serde(default, skip_serializing_if = "SensorLimits::is_empty")]
+    pub limits: SensorLimits,
+
+    /// What an item of [`Hardware::sensors`] measures, None for fans and temps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SensorKind>,
+
     #[serde(skip)]
     internal_index: usize,
 }
 
+/// Thresholds reported by the chip, in the unit of the sensor
+/// (Celsius, RPM, or the one of its kind). Only informative: the chip act on them by itself.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default)]
+pub struct SensorLimits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<SensorValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<SensorValue>,
+    /// Above it, the hardware may shut down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crit: Option<SensorValue>,
+}
+
+impl SensorLimits {
+    pub fn is_empty(&self) -> bool {
+        self.min.is_none() && self.max.is_none() && self.crit.is_none()
+    }
+
+    /// Highest value the sensor should reach.
+    pub fn upper(&self) -> Option<SensorValue> {
+        self.crit.or(self.max)
+    }
+}
+
+/// Quantity measured by a sensor which is neither a fan nor a temperature.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Display)]
+pub enum SensorKind {
+    /// In volt
+    Voltage,
+    /// In ampere
+    Current,
+    /// In watt
+    Power,
+    /// In joule, counted since the chip started
+    Energy,
+    /// Relative humidity, in percent
+    Humidity,
+}
+
+impl SensorKind {
+    pub const ALL: [SensorKind; 5] = [
+        SensorKind::Voltage,
+        SensorKind::Current,
+        SensorKind::Power,
+        SensorKind::Energy,
+        SensorKind::Humidity,
+    ];
+
+    /// Symbol of the unit of the values.
+    pub fn unit(self) -> &'static str {
+        match self {
+            SensorKind::Voltage => "V",
+            SensorKind::Current => "A",
+            SensorKind::Power => "W",
+            SensorKind::Energy => "J",
+            SensorKind::Humidity => "%",
+        }
+    }
+}
+
 impl HItem for HSensor {
     fn id(&self) -> &String {
         &self.hardware_id
@@ -60,7 +159,7 @@ impl HItem for HSensor {
     }
 }
 
-#[derive(Serialize, Debug, Eq)]
+#[derive(Serialize, Deserialize, Debug, Eq)]
 pub struct HControl {
     pub name: String,
     #[serde(rename = "id")]
@@ -69,6 +168,16 @@ pub struct HControl {
     #[serde(skip)]
     pub info: String,
 
+    #[serde(default)]
+    pub kind: ControlKind,
+
+    /// Values accepted by set_value and returned by get_control_value
+    #[serde(default)]
+    pub range: ControlRange,
+
+    #[serde(default)]
+    pub capabilities: Capabilities,
+
     #[serde(skip)]
     internal_index: usize,
 }
@@ -99,26 +208,191 @@ impl PartialEq for HSensor {
     }
 }
 
-#[derive(Serialize, Debug, Default)]
+impl Eq for HSensor {}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Hardware {
     #[serde(default, rename = "Control")]
-    pub controls: Vec<Rc<HControl>>,
+    pub controls: Vec<Arc<HControl>>,
     #[serde(default, rename = "Fan")]
-    pub fans: Vec<Rc<HSensor>>,
+    pub fans: Vec<Arc<HSensor>>,
     #[serde(default, rename = "Temp")]
-    pub temps: Vec<Rc<HSensor>>,
+    pub temps: Vec<Arc<HSensor>>,
+    /// Voltages, currents, powers, energies and humidities
+    #[serde(default, rename = "Sensor", skip_serializing_if = "Vec::is_empty")]
+    pub sensors: Vec<Arc<HSensor>>,
+    /// Ids given by previous versions, mapped to the current ones
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub legacy_ids: HashMap<String, String>,
+}
+
+impl Hardware {
+    fn ids(&self) -> impl Iterator<Item = &String> {
+        self.controls
+            .iter()
+            .map(|h| &h.hardware_id)
+            .chain(self.fans.iter().map(|h| &h.hardware_id))
+            .chain(self.temps.iter().map(|h| &h.hardware_id))
+            .chain(self.sensors.iter().map(|h| &h.hardware_id))
+    }
+
+    /// Remember the id a previous version gave to an item. When several items
+    /// had the same one, it is kept for the first, like the old configs did.
+    pub(crate) fn add_legacy_id(&mut self, legacy_id: String, hardware_id: &str) {
+        if legacy_id != hardware_id {
+            self.legacy_ids
+                .entry(legacy_id)
+                .or_insert_with(|| hardware_id.to_owned());
+        }
+    }
+
+    /// Ids which appeared and disappeared between `self` and `new`.
+    pub fn diff(&self, new: &Hardware) -> HardwareDiff {
+        let old_ids = self.ids().collect::<HashSet<_>>();
+        let new_ids = new.ids().collect::<HashSet<_>>();
+
+        HardwareDiff {
+            added: new
+                .ids()
+                .filter(|id| !old_ids.contains(id))
+                .cloned()
+                .collect(),
+            removed: self
+                .ids()
+                .filter(|id| !new_ids.contains(id))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Result of a rescan.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct HardwareDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl HardwareDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
 }
 
 pub type Value = i32;
 
-#[derive(Debug, Clone, PartialEq, Eq, Display)]
+/// Reading of a sensor. Not rounded, so 45.9 °C stays 45.9.
+pub type SensorValue = f64;
+
+/// What the value of a control drive.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ControlKind {
+    /// A duty cycle
+    #[default]
+    Pwm,
+    /// A target speed in RPM, that the chip maintains by itself
+    FanTarget,
+}
+
+/// Optional settings of a pwm output.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Capabilities {
+    /// `pwm{N}_freq`
+    pub frequency: bool,
+    /// `pwm{N}_mode`
+    pub output_mode: bool,
+}
+
+/// Signal sent to the fan. 3-pin fans can only be slowed down in DC.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    Dc,
+    Pwm,
+}
+
+impl OutputMode {
+    /// Value of `pwm{N}_mode`
+    pub fn from_raw(value: Value) -> Self {
+        match value {
+            0 => OutputMode::Dc,
+            _ => OutputMode::Pwm,
+        }
+    }
+
+    pub fn to_raw(self) -> Value {
+        match self {
+            OutputMode::Dc => 0,
+            OutputMode::Pwm => 1,
+        }
+    }
+}
+
+/// Native range of a control: the values are written to
+/// the hardware as is, without conversion.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct ControlRange {
+    pub min: Value,
+    pub max: Value,
+    pub step: Value,
+}
+
+impl Default for ControlRange {
+    /// A control that directly take a percentage
+    fn default() -> Self {
+        Self {
+            min: 0,
+            max: 100,
+            step: 1,
+        }
+    }
+}
+
+impl ControlRange {
+    /// Range of a pwm file in hwmon
+    pub const PWM: ControlRange = ControlRange {
+        min: 0,
+        max: 255,
+        step: 1,
+    };
+
+    /// Nearest value accepted by the hardware.
+    pub fn clamp(&self, raw: Value) -> Value {
+        let step = self.step.max(1);
+        let steps = ((raw - self.min) as f64 / step as f64).round() as Value;
+        (self.min + steps * step).clamp(self.min, self.max)
+    }
+
+    /// Convert a percentage to a native value.
+    /// When the range has at least 100 values, converting
+    /// the result back with `to_percent` give the same percentage.
+    pub fn from_percent(&self, percent: Value) -> Value {
+        let percent = percent.clamp(0, 100) as f64;
+        let raw = self.min as f64 + percent * (self.max - self.min) as f64 / 100.0;
+        self.clamp(raw.round() as Value)
+    }
+
+    pub fn to_percent(&self, raw: Value) -> Value {
+        if self.max <= self.min {
+            return 0;
+        }
+        let percent = (raw - self.min) as f64 * 100.0 / (self.max - self.min) as f64;
+        (percent.round() as Value).clamp(0, 100)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Display, Serialize, Deserialize)]
 pub enum Mode {
     Auto,
     Manual,
     Specific(Value),
 }
 
-/// Try to construct a new hardware bridge
+/// Try to construct a new hardware bridge.
+/// On Linux, libsensors is used when the `libsensors` feature is enabled,
+/// otherwise sysfs is read directly.
 pub fn new() -> Result<impl HardwareBridge> {
     #[cfg(feature = "fake_hardware")]
     return fake_hardware::FakeHardwareBridge::new();
@@ -126,23 +400,72 @@ pub fn new() -> Result<impl HardwareBridge> {
     #[cfg(all(not(feature = "fake_hardware"), target_os = "windows"))]
     return windows::WindowsBridge::new();
 
-    #[cfg(all(not(feature = "fake_hardware"), target_os = "linux"))]
+    #[cfg(all(
+        not(feature = "fake_hardware"),
+        target_os = "linux",
+        feature = "libsensors"
+    ))]
     return linux::LinuxBridge::new();
+
+    #[cfg(all(
+        not(feature = "fake_hardware"),
+        target_os = "linux",
+        not(feature = "libsensors")
+    ))]
+    return sysfs::SysfsBridge::new();
 }
 
-pub trait HardwareBridge {
+/// Bridges are moved to the thread running the control loop.
+pub trait HardwareBridge: Send {
     fn new() -> Result<Self>
     where
         Self: Sized;
 
     fn hardware(&self) -> &Hardware;
 
-    fn get_sensor_value(&mut self, sensor: &HSensor) -> Result<Value>;
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> Result<SensorValue>;
     fn get_control_value(&mut self, control: &HControl) -> Result<Value>;
 
     fn set_value(&mut self, control: &HControl, value: Value) -> Result<()>;
     fn set_mode(&mut self, control: &HControl, mode: &Mode) -> Result<()>;
 
+    /// Frequency of the pwm signal, in Hz.
+    fn get_frequency(&mut self, control: &HControl) -> Result<Value> {
+        Err(HardwareError::Unsupported(
+            "pwm frequency",
+            control.hardware_id.clone(),
+        ))
+    }
+
+    fn set_frequency(&mut self, control: &HControl, _frequency: Value) -> Result<()> {
+        Err(HardwareError::Unsupported(
+            "pwm frequency",
+            control.hardware_id.clone(),
+        ))
+    }
+
+    fn get_output_mode(&mut self, control: &HControl) -> Result<OutputMode> {
+        Err(HardwareError::Unsupported(
+            "output mode",
+            control.hardware_id.clone(),
+        ))
+    }
+
+    fn set_output_mode(&mut self, control: &HControl, _mode: OutputMode) -> Result<()> {
+        Err(HardwareError::Unsupported(
+            "output mode",
+            control.hardware_id.clone(),
+        ))
+    }
+
+    /// Look for hardware plugged or unplugged since the last scan.
+    /// The items returned by `hardware` are replaced, even when
+    /// nothing changed: they must be bound again by id. Controls
+    /// which disappeared are given back to the hardware.
+    fn rescan(&mut self) -> Result<HardwareDiff> {
+        Ok(HardwareDiff::default())
+    }
+
     /// Used on Windows, because we update all sensors in one function, so
     /// we don't want to update at each call, instead, we call this function
     /// one time in each update iteration.
@@ -155,3 +478,29 @@ pub trait HardwareBridge {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::ControlRange;
+
+    #[test]
+    fn test_range() {
+        let range = ControlRange::PWM;
+        for percent in 0..=100 {
+            assert_eq!(range.to_percent(range.from_percent(percent)), percent);
+        }
+        assert_eq!(range.from_percent(50), 128);
+        assert_eq!(range.from_percent(150), 255);
+
+        let range = ControlRange {
+            min: 60,
+            max: 160,
+            step: 20,
+        };
+        assert_eq!(range.from_percent(0), 60);
+        assert_eq!(range.from_percent(45), 100);
+        assert_eq!(range.clamp(171), 160);
+        assert_eq!(range.clamp(0), 60);
+        assert_eq!(range.to_percent(100), 40);
+    }
+}
@@ -1,9 +1,18 @@
-use std::{fmt::Debug, rc::Rc};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use lm_sensors::{ChipRef, FeatureRef, LMSensors, SubFeatureRef, feature, value};
 use thiserror::Error;
 
-use crate::{HControl, HSensor, Hardware, HardwareBridge, HardwareError, Mode, Value};
+use crate::sysfs::{self, DEFAULT_SYSFS_ROOT, FanTargetAttributes, SensorAttributes};
+use crate::{
+    Capabilities, ControlKind, ControlRange, HControl, HSensor, Hardware, HardwareBridge,
+    HardwareDiff, HardwareError, Mode, OutputMode, SensorKind, SensorLimits, SensorValue, Value,
+};
 use ouroboros::self_referencing;
 
 // https://www.kernel.org/doc/Documentation/hwmon/sysfs-interface
@@ -13,14 +22,24 @@ static DEFAULT_PWM_ENABLE: f64 = 5.0;
 static MANUAL_MODE: f64 = 1.0;
 
 pub struct LinuxBridge {
-    lm_sensor: LinuxBridgeSelfRef,
+    /// None when libsensors failed to initialize again during a rescan
+    lm_sensor: Option<LinuxBridgeSelfRef>,
     hardware: Hardware,
 }
 
+// SAFETY: libsensors has no thread affinity, it only forbids concurrent calls.
+// The bridge is moved once to the thread of the control loop, and all the
+// references into the library are owned by it and only used through &mut self.
+unsafe impl Send for LinuxBridge {}
+
 #[derive(Error, Debug)]
 pub enum LinuxError {
     #[error("{0}: {1}")]
     LmSensors(String, lm_sensors::errors::Error),
+    #[error("{0} is not present anymore")]
+    NotFound(String),
+    #[error("libsensors is not initialized")]
+    NotInitialized,
 }
 
 #[self_referencing]
@@ -37,6 +56,12 @@ struct LinuxBridgeSelfRef {
 
 impl Drop for PwmRefs<'_> {
     fn drop(&mut self) {
+        if !self.release_on_drop {
+            return;
+        }
+        if let Err(e) = self.restore_settings() {
+            error!("can't restore the settings of a pwm when quitting: {e}")
+        }
         if let Err(e) = self.enable.set_raw_value(self.default_enable_cached) {
             error!("can't set auto to a pwm sensor when quitting: {e}")
         }
@@ -45,15 +70,202 @@ impl Drop for PwmRefs<'_> {
 
 struct PwmRefs<'a> {
     io: SubFeatureRef<'a>,
+    /// `pwm{N}` in sysfs, to restore the pwm when libsensors is gone
+    path: PathBuf,
     enable: SubFeatureRef<'a>,
     default_enable_cached: f64,
+    frequency: Option<CachedRef<'a>>,
+    output_mode: Option<CachedRef<'a>>,
+    /// False when libsensors is initialized again by a rescan
+    release_on_drop: bool,
+}
+
+/// What a pwm cached before we touched it, kept across a rescan. libsensors may
+/// be gone when it is dropped, so it restores the pwm through sysfs, unless a new
+/// scan found the pwm again.
+struct PwmDefaults {
+    path: PathBuf,
+    enable: f64,
+    frequency: Option<f64>,
+    output_mode: Option<f64>,
+    release_on_drop: bool,
+}
+
+impl PwmDefaults {
+    fn attribute(&self, suffix: &str) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(suffix);
+        path.into()
+    }
+}
+
+impl Drop for PwmDefaults {
+    fn drop(&mut self) {
+        if !self.release_on_drop {
+            return;
+        }
+        for (suffix, default) in [("_freq", self.frequency), ("_mode", self.output_mode)] {
+            if let Some(default) = default
+                && let Err(e) = sysfs::write_value(&self.attribute(suffix), default)
+            {
+                error!("can't restore the settings of a pwm after a rescan: {e}")
+            }
+        }
+        if let Err(e) = sysfs::write_value(&self.attribute("_enable"), self.enable) {
+            error!("can't set auto to a pwm after a rescan: {e}")
+        }
+    }
+}
+
+enum ControlDefaults {
+    Pwm(PwmDefaults),
+    FanTarget(FanTargetAttributes),
+}
+
+impl PwmRefs<'_> {
+    /// Stop restoring the pwm when dropped, and return what it would have restored.
+    fn detach(&mut self) -> PwmDefaults {
+        self.release_on_drop = false;
+        PwmDefaults {
+            path: self.path.clone(),
+            enable: self.default_enable_cached,
+            frequency: self.frequency.as_ref().map(|cached| cached.default),
+            output_mode: self.output_mode.as_ref().map(|cached| cached.default),
+            release_on_drop: true,
+        }
+    }
+
+    /// Take over the state cached by a previous scan of the same pwm,
+    /// which then doesn't touch the hardware when dropped.
+    fn inherit(&mut self, previous: &mut PwmDefaults) {
+        self.default_enable_cached = previous.enable;
+        for (cached, default) in [
+            (&mut self.frequency, previous.frequency),
+            (&mut self.output_mode, previous.output_mode),
+        ] {
+            if let (Some(cached), Some(default)) = (cached, default) {
+                cached.default = default;
+            }
+        }
+        previous.release_on_drop = false;
+    }
+
+    fn restore_settings(&self) -> std::result::Result<(), LinuxError> {
+        for cached in self.frequency.iter().chain(&self.output_mode) {
+            if let Err(e) = cached.io.set_raw_value(cached.default) {
+                let explication = format!("can't restore {} to a pwm", cached.default);
+                return Err(LinuxError::LmSensors(explication, e));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Optional sub feature, with the value it had before we touched it.
+struct CachedRef<'a> {
+    io: SubFeatureRef<'a>,
+    default: f64,
+}
+
+impl<'a> CachedRef<'a> {
+    fn new(feature_ref: &FeatureRef<'a>, kind: value::Kind) -> Option<Self> {
+        let io = feature_ref.sub_feature_by_kind(kind).ok()?;
+        match io.raw_value() {
+            Ok(default) => Some(Self { io, default }),
+            Err(e) => {
+                error!("can't read pwm sub feature {e}");
+                None
+            }
+        }
+    }
 }
 struct SensorRefs<'a> {
     io: SubFeatureRef<'a>,
 }
 
+/// Sub features of the sensors which are neither fans nor temps.
+struct SubFeatureKinds {
+    kind: SensorKind,
+    /// The first one found is read
+    inputs: &'static [value::Kind],
+    /// Min, max and crit
+    limits: Option<(value::Kind, value::Kind, Option<value::Kind>)>,
+}
+
+impl SubFeatureKinds {
+    fn of(feature_kind: feature::Kind) -> Option<Self> {
+        let kinds = match feature_kind {
+            feature::Kind::Voltage => Self {
+                kind: SensorKind::Voltage,
+                inputs: &[value::Kind::VoltageInput],
+                limits: Some((
+                    value::Kind::VoltageMinimum,
+                    value::Kind::VoltageMaximum,
+                    Some(value::Kind::VoltageCritical),
+                )),
+            },
+            feature::Kind::Current => Self {
+                kind: SensorKind::Current,
+                inputs: &[value::Kind::CurrentInput],
+                limits: Some((
+                    value::Kind::CurrentMinimum,
+                    value::Kind::CurrentMaximum,
+                    Some(value::Kind::CurrentCritical),
+                )),
+            },
+            feature::Kind::Power => Self {
+                kind: SensorKind::Power,
+                // amdgpu only has an average
+                inputs: &[value::Kind::PowerInput, value::Kind::PowerAverage],
+                limits: Some((
+                    value::Kind::PowerMinimum,
+                    value::Kind::PowerMaximum,
+                    Some(value::Kind::PowerCritical),
+                )),
+            },
+            feature::Kind::Energy => Self {
+                kind: SensorKind::Energy,
+                inputs: &[value::Kind::EnergyInput],
+                limits: None,
+            },
+            feature::Kind::Humidity => Self {
+                kind: SensorKind::Humidity,
+                inputs: &[value::Kind::HumidityInput],
+                limits: None,
+            },
+            _ => return None,
+        };
+        Some(kinds)
+    }
+}
+
+/// Read the limits of a feature, when libsensors knows them.
+fn get_limits(
+    feature_ref: &FeatureRef,
+    min: value::Kind,
+    max: value::Kind,
+    crit: Option<value::Kind>,
+) -> SensorLimits {
+    let read = |kind| {
+        feature_ref
+            .sub_feature_by_kind(kind)
+            .ok()
+            .and_then(|sub_feature_ref| sub_feature_ref.raw_value().ok())
+    };
+
+    SensorLimits {
+        min: read(min),
+        max: read(max),
+        crit: crit.and_then(read),
+    }
+}
+
 enum InternalSubFeatureRef<'a> {
     Pwm(PwmRefs<'a>),
+    /// libsensors doesn't know `fan{N}_target`, so it is accessed through sysfs
+    FanTarget(FanTargetAttributes),
+    /// Thermal zones are not part of hwmon, so they are read through sysfs
+    ThermalZone(SensorAttributes),
     Sensor(SensorRefs<'a>),
 }
 
@@ -62,9 +274,13 @@ fn generate_hardware<'a>(
     hardware: &mut Hardware,
 ) -> Vec<InternalSubFeatureRef<'a>> {
     struct HInfo {
+        chip_path: PathBuf,
         name: String,
         hardware_id: String,
         info: String,
+        chip_id: String,
+        sub_feature: String,
+        legacy_id: String,
     }
 
     #[derive(Error, Debug)]
@@ -75,7 +291,10 @@ fn generate_hardware<'a>(
         InvalidData(String),
     }
 
+    /// Build the infos of a sub feature, and remember its legacy id.
     fn get_infos_from_refs(
+        hardware: &mut Hardware,
+        chip_ids: &mut sysfs::ChipIds,
         chip_ref: &ChipRef,
         feature_ref: &FeatureRef,
         sub_feature_ref: &SubFeatureRef,
@@ -98,9 +317,17 @@ fn generate_hardware<'a>(
             }
         };
 
+        let prefix = sysfs::read_string(&chip_path.join("name"))
+            .map_err(|e| GetInfoError::InvalidData(e.to_string()))?;
+        let chip_id = chip_ids.get(&chip_path, &prefix);
+        let hardware_id = format!("{chip_id}/{sub_feature_name}");
+        let legacy_id = format!("{label}-{chip_name}-{sub_feature_name}");
+        hardware.add_legacy_id(legacy_id.clone(), &hardware_id);
+
         Ok(HInfo {
+            chip_path: chip_path.to_path_buf(),
             name: format!("{label} {chip_name}"),
-            hardware_id: format!("{label}-{chip_name}-{sub_feature_name}"),
+            hardware_id,
             info: format!(
                 "chip path: {}\nchip name: {}\nbus: {}\nlabel: {}\nfeature: {}",
                 chip_path.display(),
@@ -109,10 +336,14 @@ fn generate_hardware<'a>(
                 label,
                 sub_feature_name
             ),
+            chip_id,
+            sub_feature: sub_feature_name.to_owned(),
+            legacy_id,
         })
     }
 
     let mut sensors = Vec::new();
+    let mut chip_ids = sysfs::ChipIds::default();
 
     for chip_ref in lib.chip_iter(None) {
         for feature_ref in chip_ref.feature_iter() {
@@ -127,18 +358,59 @@ fn generate_hardware<'a>(
                             continue;
                         };
 
-                        match get_infos_from_refs(&chip_ref, &feature_ref, &sub_feature_ref) {
+                        match get_infos_from_refs(
+                            hardware,
+                            &mut chip_ids,
+                            &chip_ref,
+                            &feature_ref,
+                            &sub_feature_ref,
+                        ) {
                             Ok(h_info) => {
                                 let sensor = SensorRefs {
                                     io: sub_feature_ref,
                                 };
                                 sensors.push(InternalSubFeatureRef::Sensor(sensor));
-                                hardware.fans.push(Rc::new(HSensor {
-                                    name: h_info.name,
-                                    hardware_id: h_info.hardware_id,
-                                    info: h_info.info,
+                                hardware.fans.push(Arc::new(HSensor {
+                                    name: h_info.name.clone(),
+                                    hardware_id: h_info.hardware_id.clone(),
+                                    info: h_info.info.clone(),
+                                    limits: get_limits(
+                                        &feature_ref,
+                                        value::Kind::FanMinimum,
+                                        value::Kind::FanMaximum,
+                                        None,
+                                    ),
+                                    kind: None,
                                     internal_index: next_internal_index,
                                 }));
+
+                                let target = chip_ref.path().and_then(|chip_path| {
+                                    let feature = h_info.sub_feature.strip_suffix("_input")?;
+                                    FanTargetAttributes::discover(&chip_path, feature)
+                                        .map(|target| (feature.to_owned(), target))
+                                });
+
+                                if let Some((feature, (target, range))) = target
+                                    && let Some(legacy_id) = h_info.legacy_id.strip_suffix("_input")
+                                {
+                                    let hardware_id =
+                                        format!("{}/{feature}_target", h_info.chip_id);
+                                    hardware
+                                        .add_legacy_id(format!("{legacy_id}_target"), &hardware_id);
+                                    hardware.controls.push(Arc::new(HControl {
+                                        name: h_info.name,
+                                        hardware_id,
+                                        info: h_info.info.replace(
+                                            &format!("{feature}_input"),
+                                            &format!("{feature}_target"),
+                                        ),
+                                        kind: ControlKind::FanTarget,
+                                        range,
+                                        capabilities: Capabilities::default(),
+                                        internal_index: sensors.len(),
+                                    }));
+                                    sensors.push(InternalSubFeatureRef::FanTarget(target));
+                                }
                             }
                             Err(e) => {
                                 error!("can't generate hardware metadata for fan: {e}");
@@ -152,16 +424,29 @@ fn generate_hardware<'a>(
                             continue;
                         };
 
-                        match get_infos_from_refs(&chip_ref, &feature_ref, &sub_feature_ref) {
+                        match get_infos_from_refs(
+                            hardware,
+                            &mut chip_ids,
+                            &chip_ref,
+                            &feature_ref,
+                            &sub_feature_ref,
+                        ) {
                             Ok(h_info) => {
                                 let sensor = SensorRefs {
                                     io: sub_feature_ref,
                                 };
                                 sensors.push(InternalSubFeatureRef::Sensor(sensor));
-                                hardware.temps.push(Rc::new(HSensor {
+                                hardware.temps.push(Arc::new(HSensor {
                                     name: h_info.name,
                                     hardware_id: h_info.hardware_id,
                                     info: h_info.info,
+                                    limits: get_limits(
+                                        &feature_ref,
+                                        value::Kind::TemperatureMinimum,
+                                        value::Kind::TemperatureMaximum,
+                                        Some(value::Kind::TemperatureCritical),
+                                    ),
+                                    kind: None,
                                     internal_index: next_internal_index,
                                 }));
                             }
@@ -196,18 +481,39 @@ fn generate_hardware<'a>(
                             }
                         };
 
-                        match get_infos_from_refs(&chip_ref, &feature_ref, &sub_feature_ref_io) {
+                        match get_infos_from_refs(
+                            hardware,
+                            &mut chip_ids,
+                            &chip_ref,
+                            &feature_ref,
+                            &sub_feature_ref_io,
+                        ) {
                             Ok(h_info) => {
+                                let frequency = CachedRef::new(&feature_ref, value::Kind::PwmFreq);
+                                let output_mode =
+                                    CachedRef::new(&feature_ref, value::Kind::PwmMode);
+                                let capabilities = Capabilities {
+                                    frequency: frequency.is_some(),
+                                    output_mode: output_mode.is_some(),
+                                };
+
                                 let sensor = InternalSubFeatureRef::Pwm(PwmRefs {
                                     io: sub_feature_ref_io,
+                                    path: h_info.chip_path.join(&h_info.sub_feature),
                                     enable: sub_feature_ref_enable,
                                     default_enable_cached: enable_cached,
+                                    frequency,
+                                    output_mode,
+                                    release_on_drop: true,
                                 });
                                 sensors.push(sensor);
-                                hardware.controls.push(Rc::new(HControl {
+                                hardware.controls.push(Arc::new(HControl {
                                     name: h_info.name,
                                     hardware_id: h_info.hardware_id,
                                     info: h_info.info,
+                                    kind: ControlKind::Pwm,
+                                    range: ControlRange::PWM,
+                                    capabilities,
                                     internal_index: next_internal_index,
                                 }));
                             }
@@ -216,36 +522,106 @@ fn generate_hardware<'a>(
                             }
                         }
                     }
+                    feature::Kind::Voltage
+                    | feature::Kind::Current
+                    | feature::Kind::Power
+                    | feature::Kind::Energy
+                    | feature::Kind::Humidity => {
+                        let Some(sub_kinds) = SubFeatureKinds::of(feature_kind) else {
+                            continue;
+                        };
+                        let Some(sub_feature_ref) = sub_kinds
+                            .inputs
+                            .iter()
+                            .find_map(|kind| feature_ref.sub_feature_by_kind(*kind).ok())
+                        else {
+                            continue;
+                        };
+
+                        match get_infos_from_refs(
+                            hardware,
+                            &mut chip_ids,
+                            &chip_ref,
+                            &feature_ref,
+                            &sub_feature_ref,
+                        ) {
+                            Ok(h_info) => {
+                                let sensor = SensorRefs {
+                                    io: sub_feature_ref,
+                                };
+                                sensors.push(InternalSubFeatureRef::Sensor(sensor));
+                                hardware.sensors.push(Arc::new(HSensor {
+                                    name: h_info.name,
+                                    hardware_id: h_info.hardware_id,
+                                    info: h_info.info,
+                                    limits: sub_kinds
+                                        .limits
+                                        .map(|(min, max, crit)| {
+                                            get_limits(&feature_ref, min, max, crit)
+                                        })
+                                        .unwrap_or_default(),
+                                    kind: Some(sub_kinds.kind),
+                                    internal_index: next_internal_index,
+                                }));
+                            }
+                            Err(e) => {
+                                error!(
+                                    "can't generate hardware metadata for {}: {e}",
+                                    sub_kinds.kind
+                                );
+                            }
+                        }
+                    }
                     _ => continue,
                 },
                 None => continue,
             };
         }
     }
+    sensors.extend(
+        sysfs::thermal_zones(Path::new(DEFAULT_SYSFS_ROOT), hardware, sensors.len())
+            .into_iter()
+            .map(InternalSubFeatureRef::ThermalZone),
+    );
+
     sensors
 }
 
+fn init_lib(hardware: &mut Hardware) -> crate::Result<LinuxBridgeSelfRef> {
+    let lib = match lm_sensors::Initializer::default().initialize() {
+        Ok(lib) => lib,
+        Err(e) => {
+            return Err(HardwareError::Linux(LinuxError::LmSensors(
+                "failed to init libsensor".into(),
+                e,
+            )));
+        }
+    };
+
+    Ok(LinuxBridgeSelfRefBuilder {
+        lib,
+        sensors_builder: |lib: &LMSensors| generate_hardware(lib, hardware),
+    }
+    .build())
+}
+
+fn get<'b, 'a>(
+    sensors: &'b [InternalSubFeatureRef<'a>],
+    internal_index: usize,
+    hardware_id: &str,
+) -> crate::Result<&'b InternalSubFeatureRef<'a>> {
+    sensors
+        .get(internal_index)
+        .ok_or_else(|| LinuxError::NotFound(hardware_id.to_owned()).into())
+}
+
 impl HardwareBridge for LinuxBridge {
     fn new() -> crate::Result<Self> {
         let mut hardware = Hardware::default();
-
-        let lib = match lm_sensors::Initializer::default().initialize() {
-            Ok(lib) => lib,
-            Err(e) => {
-                return Err(HardwareError::Linux(LinuxError::LmSensors(
-                    "failed to init libsensor".into(),
-                    e,
-                )));
-            }
-        };
-        let bridge = LinuxBridgeSelfRefBuilder {
-            lib,
-            sensors_builder: |lib: &LMSensors| generate_hardware(lib, &mut hardware),
-        }
-        .build();
+        let bridge = init_lib(&mut hardware)?;
 
         Ok(Self {
-            lm_sensor: bridge,
+            lm_sensor: Some(bridge),
             hardware,
         })
     }
@@ -253,65 +629,61 @@ impl HardwareBridge for LinuxBridge {
         &self.hardware
     }
 
-    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<Value> {
-        self.lm_sensor.with_sensors(|sensors| {
-            match sensors.get(sensor.internal_index).expect("no sensor found") {
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<SensorValue> {
+        self.with_sensors(|sensors| {
+            match get(sensors, sensor.internal_index, &sensor.hardware_id)? {
                 InternalSubFeatureRef::Sensor(sensor_refs) => match sensor_refs.io.raw_value() {
-                    Ok(value) => Ok(value as i32),
+                    Ok(value) => Ok(value),
                     Err(e) => Err(HardwareError::Linux(LinuxError::LmSensors(
                         "sensor".to_owned(),
                         e,
                     ))),
                 },
-                _ => unreachable!(),
+                InternalSubFeatureRef::ThermalZone(zone) => Ok(zone.get_value()?),
+                _ => Err(LinuxError::NotFound(sensor.hardware_id.clone()).into()),
             }
         })
     }
     fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
-        self.lm_sensor.with_sensors(|sensors| {
-            match sensors
-                .get(control.internal_index)
-                .expect("no sensor found")
-            {
+        self.with_sensors(|sensors| {
+            match get(sensors, control.internal_index, &control.hardware_id)? {
                 InternalSubFeatureRef::Pwm(pwm_refs) => match pwm_refs.io.raw_value() {
-                    Ok(value) => Ok((value / 2.55) as i32),
+                    Ok(value) => Ok(value.round() as Value),
                     Err(e) => Err(HardwareError::Linux(LinuxError::LmSensors(
                         "pwm".to_owned(),
                         e,
                     ))),
                 },
-                _ => unreachable!(),
+                InternalSubFeatureRef::FanTarget(target) => Ok(target.get_value()?),
+                _ => Err(LinuxError::NotFound(control.hardware_id.clone()).into()),
             }
         })
     }
 
     fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
-        self.lm_sensor.with_sensors(|sensors| {
-            match sensors
-                .get(control.internal_index)
-                .expect("no sensor found")
-            {
+        self.with_sensors(|sensors| {
+            match get(sensors, control.internal_index, &control.hardware_id)? {
                 InternalSubFeatureRef::Pwm(pwm_refs) => {
-                    let value = value as f64 * 2.55;
-                    if let Err(e) = pwm_refs.io.set_raw_value(value) {
+                    if let Err(e) = pwm_refs.io.set_raw_value(value.into()) {
                         let explication = format!("can't set value {value} to a pwm");
                         let e = LinuxError::LmSensors(explication, e);
                         return Err(HardwareError::Linux(e));
                     }
                     Ok(())
                 }
-                _ => unreachable!(),
+                InternalSubFeatureRef::FanTarget(target) => Ok(target.set_value(value)?),
+                _ => Err(LinuxError::NotFound(control.hardware_id.clone()).into()),
             }
         })
     }
 
     fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
-        self.lm_sensor.with_sensors(|sensors| {
-            match sensors
-                .get(control.internal_index)
-                .expect("no sensor found")
-            {
+        self.with_sensors(|sensors| {
+            match get(sensors, control.internal_index, &control.hardware_id)? {
                 InternalSubFeatureRef::Pwm(pwm_refs) => {
+                    if *mode == Mode::Auto {
+                        pwm_refs.restore_settings()?;
+                    }
                     let value = match mode {
                         Mode::Auto => pwm_refs.default_enable_cached,
                         Mode::Manual => MANUAL_MODE,
@@ -325,8 +697,142 @@ impl HardwareBridge for LinuxBridge {
                     }
                     Ok(())
                 }
-                _ => unreachable!(),
+                InternalSubFeatureRef::FanTarget(target) => Ok(target.set_mode(mode)?),
+                _ => Err(LinuxError::NotFound(control.hardware_id.clone()).into()),
             }
         })
     }
+
+    fn get_frequency(&mut self, control: &HControl) -> crate::Result<Value> {
+        self.with_pwm_setting(
+            control,
+            |pwm| &pwm.frequency,
+            "pwm frequency",
+            |io| io.raw_value().map(|value| value.round() as Value),
+        )
+    }
+
+    fn set_frequency(&mut self, control: &HControl, frequency: Value) -> crate::Result<()> {
+        self.with_pwm_setting(
+            control,
+            |pwm| &pwm.frequency,
+            "pwm frequency",
+            |io| io.set_raw_value(frequency.into()),
+        )
+    }
+
+    fn get_output_mode(&mut self, control: &HControl) -> crate::Result<OutputMode> {
+        self.with_pwm_setting(
+            control,
+            |pwm| &pwm.output_mode,
+            "output mode",
+            |io| {
+                io.raw_value()
+                    .map(|value| OutputMode::from_raw(value.round() as Value))
+            },
+        )
+    }
+
+    fn set_output_mode(&mut self, control: &HControl, mode: OutputMode) -> crate::Result<()> {
+        self.with_pwm_setting(
+            control,
+            |pwm| &pwm.output_mode,
+            "output mode",
+            |io| io.set_raw_value(mode.to_raw().into()),
+        )
+    }
+
+    /// libsensors only discovers chips when initialized, so it is initialized again.
+    /// Only one instance can exist at a time: the state cached for each control is
+    /// saved before the old one is dropped, and written back through sysfs if the
+    /// control is not found again, or if libsensors fails to initialize.
+    fn rescan(&mut self) -> crate::Result<HardwareDiff> {
+        let mut defaults = HashMap::new();
+
+        if let Some(lm_sensor) = &mut self.lm_sensor {
+            let controls = &self.hardware.controls;
+            lm_sensor.with_sensors_mut(|sensors| {
+                for control in controls {
+                    let saved = match sensors.get_mut(control.internal_index) {
+                        Some(InternalSubFeatureRef::Pwm(pwm_refs)) => {
+                            ControlDefaults::Pwm(pwm_refs.detach())
+                        }
+                        Some(InternalSubFeatureRef::FanTarget(target)) => {
+                            ControlDefaults::FanTarget(target.detach())
+                        }
+                        _ => continue,
+                    };
+                    defaults.insert(control.hardware_id.clone(), saved);
+                }
+            });
+        }
+        self.lm_sensor.take();
+
+        // on failure, the saved defaults give every control back to the hardware
+        let mut hardware = Hardware::default();
+        let mut lm_sensor = init_lib(&mut hardware)?;
+
+        lm_sensor.with_sensors_mut(|sensors| {
+            for control in &hardware.controls {
+                match (
+                    sensors.get_mut(control.internal_index),
+                    defaults.get_mut(&control.hardware_id),
+                ) {
+                    (
+                        Some(InternalSubFeatureRef::Pwm(pwm_refs)),
+                        Some(ControlDefaults::Pwm(saved)),
+                    ) => pwm_refs.inherit(saved),
+                    (
+                        Some(InternalSubFeatureRef::FanTarget(target)),
+                        Some(ControlDefaults::FanTarget(saved)),
+                    ) => target.inherit(saved),
+                    _ => {}
+                }
+            }
+        });
+
+        // the controls which disappeared are given back to the hardware
+        drop(defaults);
+
+        let diff = self.hardware.diff(&hardware);
+        self.lm_sensor = Some(lm_sensor);
+        self.hardware = hardware;
+        Ok(diff)
+    }
+}
+
+impl LinuxBridge {
+    fn with_sensors<T>(
+        &self,
+        f: impl for<'a> FnOnce(&Vec<InternalSubFeatureRef<'a>>) -> crate::Result<T>,
+    ) -> crate::Result<T> {
+        match &self.lm_sensor {
+            Some(lm_sensor) => lm_sensor.with_sensors(f),
+            None => Err(LinuxError::NotInitialized.into()),
+        }
+    }
+
+    fn with_pwm_setting<T>(
+        &mut self,
+        control: &HControl,
+        setting: impl for<'a, 'b> Fn(&'b PwmRefs<'a>) -> &'b Option<CachedRef<'a>>,
+        name: &'static str,
+        f: impl FnOnce(&SubFeatureRef) -> std::result::Result<T, lm_sensors::errors::Error>,
+    ) -> crate::Result<T> {
+        self.with_sensors(|sensors| {
+            let cached = match get(sensors, control.internal_index, &control.hardware_id)? {
+                InternalSubFeatureRef::Pwm(pwm_refs) => setting(pwm_refs).as_ref(),
+                _ => None,
+            };
+
+            let Some(cached) = cached else {
+                return Err(HardwareError::Unsupported(
+                    name,
+                    control.hardware_id.clone(),
+                ));
+            };
+
+            f(&cached.io).map_err(|e| HardwareError::Linux(LinuxError::LmSensors(name.into(), e)))
+        })
+    }
 }
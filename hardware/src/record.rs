@@ -0,0 +1,628 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    HControl, HSensor, Hardware, HardwareBridge, HardwareDiff, Mode, OutputMode, SensorValue, Value,
+};
+
+// A recording is a JSON lines file. The first line describe the hardware,
+// the next ones are the calls made on the bridge, in order. The hardware
+// found by each rescan is recorded among the calls.
+
+#[derive(Error, Debug)]
+pub enum RecordError {
+    #[error("{0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Invalid recording {0}, line {1}: {2}")]
+    Parse(PathBuf, usize, serde_json::Error),
+    #[error("The recording {0} doesn't start with the hardware")]
+    NoHardware(PathBuf),
+    #[error("A recording file is needed")]
+    NoFile,
+    #[error("Recorded error: {0}")]
+    Replayed(String),
+    #[error("No value was recorded for {0}")]
+    NoValue(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind")]
+pub enum Record {
+    Hardware {
+        hardware: Hardware,
+    },
+    SensorValue {
+        /// Milliseconds since the start of the recording
+        time: u64,
+        id: String,
+        result: std::result::Result<SensorValue, String>,
+    },
+    ControlValue {
+        time: u64,
+        id: String,
+        result: std::result::Result<Value, String>,
+    },
+    SetValue {
+        time: u64,
+        id: String,
+        value: Value,
+    },
+    SetMode {
+        time: u64,
+        id: String,
+        mode: Mode,
+    },
+    SetFrequency {
+        time: u64,
+        id: String,
+        frequency: Value,
+    },
+    SetOutputMode {
+        time: u64,
+        id: String,
+        mode: OutputMode,
+    },
+}
+
+/// Pass every call to the inner bridge, and log them to a file.
+pub struct RecordingBridge<H: HardwareBridge> {
+    inner: H,
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl<H: HardwareBridge> RecordingBridge<H> {
+    pub fn create(inner: H, path: &Path) -> crate::Result<Self> {
+        let file = match File::create(path) {
+            Ok(file) => file,
+            Err(e) => {
+                return Err(RecordError::Io(
+                    format!("can't create recording {}", path.display()),
+                    e,
+                )
+                .into());
+            }
+        };
+
+        let mut bridge = Self {
+            inner,
+            writer: BufWriter::new(file),
+            start: Instant::now(),
+        };
+
+        let hardware = bridge.inner.hardware().clone();
+        bridge.record(Record::Hardware { hardware });
+
+        info!("Recording hardware calls to {}.", path.display());
+        Ok(bridge)
+    }
+
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+
+    fn time(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    fn record(&mut self, record: Record) {
+        let res = serde_json::to_writer(&mut self.writer, &record)
+            .map_err(std::io::Error::from)
+            .and_then(|_| self.writer.write_all(b"\n"))
+            .and_then(|_| self.writer.flush());
+
+        if let Err(e) = res {
+            error!("can't write to the recording: {e}");
+        }
+    }
+}
+
+impl<H: HardwareBridge> HardwareBridge for RecordingBridge<H> {
+    fn new() -> crate::Result<Self> {
+        Err(RecordError::NoFile.into())
+    }
+
+    fn hardware(&self) -> &Hardware {
+        self.inner.hardware()
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<SensorValue> {
+        let res = self.inner.get_sensor_value(sensor);
+        self.record(Record::SensorValue {
+            time: self.time(),
+            id: sensor.hardware_id.clone(),
+            result: res.as_ref().copied().map_err(|e| e.to_string()),
+        });
+        res
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        let res = self.inner.get_control_value(control);
+        self.record(Record::ControlValue {
+            time: self.time(),
+            id: control.hardware_id.clone(),
+            result: res.as_ref().copied().map_err(|e| e.to_string()),
+        });
+        res
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        self.record(Record::SetValue {
+            time: self.time(),
+            id: control.hardware_id.clone(),
+            value,
+        });
+        self.inner.set_value(control, value)
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        self.record(Record::SetMode {
+            time: self.time(),
+            id: control.hardware_id.clone(),
+            mode: mode.clone(),
+        });
+        self.inner.set_mode(control, mode)
+    }
+
+    fn get_frequency(&mut self, control: &HControl) -> crate::Result<Value> {
+        self.inner.get_frequency(control)
+    }
+
+    fn set_frequency(&mut self, control: &HControl, frequency: Value) -> crate::Result<()> {
+        self.record(Record::SetFrequency {
+            time: self.time(),
+            id: control.hardware_id.clone(),
+            frequency,
+        });
+        self.inner.set_frequency(control, frequency)
+    }
+
+    fn get_output_mode(&mut self, control: &HControl) -> crate::Result<OutputMode> {
+        self.inner.get_output_mode(control)
+    }
+
+    fn set_output_mode(&mut self, control: &HControl, mode: OutputMode) -> crate::Result<()> {
+        self.record(Record::SetOutputMode {
+            time: self.time(),
+            id: control.hardware_id.clone(),
+            mode,
+        });
+        self.inner.set_output_mode(control, mode)
+    }
+
+    fn rescan(&mut self) -> crate::Result<HardwareDiff> {
+        let res = self.inner.rescan();
+        // even after an error, so the rescans of a replay match the recorded ones
+        let hardware = self.inner.hardware().clone();
+        self.record(Record::Hardware { hardware });
+        res
+    }
+
+    fn update(&mut self) -> crate::Result<()> {
+        self.inner.update()
+    }
+
+    fn shutdown(&mut self) -> crate::Result<()> {
+        self.inner.shutdown()
+    }
+}
+
+/// Feed back the readings of a recording, in the same order.
+/// When all the readings of an item were consumed, the last one is repeated.
+pub struct ReplayBridge {
+    hardware: Hardware,
+    /// Hardware found by the recorded rescans
+    rescans: VecDeque<Hardware>,
+    readings: HashMap<String, VecDeque<std::result::Result<SensorValue, String>>>,
+    last_readings: HashMap<String, std::result::Result<SensorValue, String>>,
+    /// Calls changing a control, in the recorded order
+    recorded_calls: Vec<Record>,
+    /// Calls changing a control received while replaying
+    calls: Vec<Record>,
+    start: Instant,
+}
+
+impl ReplayBridge {
+    pub fn from_file(path: &Path) -> crate::Result<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                return Err(
+                    RecordError::Io(format!("can't open recording {}", path.display()), e).into(),
+                );
+            }
+        };
+
+        let mut records = Vec::new();
+
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    return Err(RecordError::Io(
+                        format!("can't read recording {}", path.display()),
+                        e,
+                    )
+                    .into());
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<Record>(&line) {
+                Ok(record) => records.push(record),
+                Err(e) => return Err(RecordError::Parse(path.to_path_buf(), i + 1, e).into()),
+            }
+        }
+
+        Self::from_records(records)
+            .ok_or_else(|| RecordError::NoHardware(path.to_path_buf()).into())
+    }
+
+    /// Return None if the first record is not the hardware.
+    pub fn from_records(records: Vec<Record>) -> Option<Self> {
+        let mut records = records.into_iter();
+
+        let Some(Record::Hardware { hardware }) = records.next() else {
+            return None;
+        };
+
+        let mut bridge = Self {
+            hardware: indexed(hardware),
+            rescans: VecDeque::new(),
+            readings: HashMap::new(),
+            last_readings: HashMap::new(),
+            recorded_calls: Vec::new(),
+            calls: Vec::new(),
+            start: Instant::now(),
+        };
+
+        for record in records {
+            match record {
+                Record::SensorValue { id, result, .. } => {
+                    bridge.readings.entry(id).or_default().push_back(result);
+                }
+                Record::ControlValue { id, result, .. } => {
+                    let result = result.map(SensorValue::from);
+                    bridge.readings.entry(id).or_default().push_back(result);
+                }
+                Record::SetValue { .. }
+                | Record::SetMode { .. }
+                | Record::SetFrequency { .. }
+                | Record::SetOutputMode { .. } => {
+                    bridge.recorded_calls.push(record);
+                }
+                Record::Hardware { hardware } => {
+                    bridge.rescans.push_back(indexed(hardware));
+                }
+            }
+        }
+
+        Some(bridge)
+    }
+
+    /// Calls changing a control received while replaying.
+    pub fn calls(&self) -> &[Record] {
+        &self.calls
+    }
+
+    /// Calls changing a control, from the recording.
+    pub fn recorded_calls(&self) -> &[Record] {
+        &self.recorded_calls
+    }
+
+    fn next_reading(&mut self, id: &str) -> crate::Result<SensorValue> {
+        let reading = match self.readings.get_mut(id).and_then(|r| r.pop_front()) {
+            Some(reading) => {
+                self.last_readings.insert(id.to_owned(), reading.clone());
+                reading
+            }
+            None => match self.last_readings.get(id) {
+                Some(reading) => reading.clone(),
+                None => return Err(RecordError::NoValue(id.to_owned()).into()),
+            },
+        };
+
+        reading.map_err(|e| RecordError::Replayed(e).into())
+    }
+
+    fn push_call(&mut self, call: Record) {
+        let same = |a: &Record, b: &Record| match (a, b) {
+            (
+                Record::SetValue { id, value, .. },
+                Record::SetValue {
+                    id: id2,
+                    value: value2,
+                    ..
+                },
+            ) => id == id2 && value == value2,
+            (
+                Record::SetMode { id, mode, .. },
+                Record::SetMode {
+                    id: id2,
+                    mode: mode2,
+                    ..
+                },
+            ) => id == id2 && mode == mode2,
+            (
+                Record::SetFrequency { id, frequency, .. },
+                Record::SetFrequency {
+                    id: id2,
+                    frequency: frequency2,
+                    ..
+                },
+            ) => id == id2 && frequency == frequency2,
+            (
+                Record::SetOutputMode { id, mode, .. },
+                Record::SetOutputMode {
+                    id: id2,
+                    mode: mode2,
+                    ..
+                },
+            ) => id == id2 && mode == mode2,
+            _ => false,
+        };
+
+        match self.recorded_calls.get(self.calls.len()) {
+            Some(recorded) if same(recorded, &call) => {}
+            recorded => warn!("replay diverged: got {call:?} instead of {recorded:?}"),
+        }
+
+        self.calls.push(call);
+    }
+}
+
+/// The internal indexes are not serialized.
+fn indexed(hardware: Hardware) -> Hardware {
+    let mut indexed = Hardware::default();
+
+    let mut index = 0;
+    for (list, items) in [
+        (&mut indexed.temps, hardware.temps),
+        (&mut indexed.fans, hardware.fans),
+        (&mut indexed.sensors, hardware.sensors),
+    ] {
+        for item in items {
+            list.push(Arc::new(HSensor {
+                name: item.name.clone(),
+                hardware_id: item.hardware_id.clone(),
+                info: item.info.clone(),
+                limits: item.limits,
+                kind: item.kind,
+                internal_index: index,
+            }));
+            index += 1;
+        }
+    }
+    for (index, item) in hardware.controls.into_iter().enumerate() {
+        indexed.controls.push(Arc::new(HControl {
+            name: item.name.clone(),
+            hardware_id: item.hardware_id.clone(),
+            info: item.info.clone(),
+            kind: item.kind,
+            range: item.range,
+            capabilities: item.capabilities,
+            internal_index: index,
+        }));
+    }
+
+    indexed
+}
+
+impl HardwareBridge for ReplayBridge {
+    fn new() -> crate::Result<Self> {
+        Err(RecordError::NoFile.into())
+    }
+
+    fn hardware(&self) -> &Hardware {
+        &self.hardware
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<SensorValue> {
+        self.next_reading(&sensor.hardware_id)
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        self.next_reading(&control.hardware_id)
+            .map(|value| value.round() as Value)
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        self.push_call(Record::SetValue {
+            time: self.start.elapsed().as_millis() as u64,
+            id: control.hardware_id.clone(),
+            value,
+        });
+        Ok(())
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        self.push_call(Record::SetMode {
+            time: self.start.elapsed().as_millis() as u64,
+            id: control.hardware_id.clone(),
+            mode: mode.clone(),
+        });
+        Ok(())
+    }
+
+    fn set_frequency(&mut self, control: &HControl, frequency: Value) -> crate::Result<()> {
+        self.push_call(Record::SetFrequency {
+            time: self.start.elapsed().as_millis() as u64,
+            id: control.hardware_id.clone(),
+            frequency,
+        });
+        Ok(())
+    }
+
+    fn set_output_mode(&mut self, control: &HControl, mode: OutputMode) -> crate::Result<()> {
+        self.push_call(Record::SetOutputMode {
+            time: self.start.elapsed().as_millis() as u64,
+            id: control.hardware_id.clone(),
+            mode,
+        });
+        Ok(())
+    }
+
+    /// Move to the hardware of the next recorded rescan. Without one left,
+    /// the hardware doesn't change.
+    fn rescan(&mut self) -> crate::Result<HardwareDiff> {
+        let Some(hardware) = self.rescans.pop_front() else {
+            return Ok(HardwareDiff::default());
+        };
+
+        let diff = self.hardware.diff(&hardware);
+        self.hardware = hardware;
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{Record, ReplayBridge};
+    use crate::{
+        Capabilities, ControlKind, ControlRange, HControl, HSensor, Hardware, HardwareBridge, Mode,
+    };
+
+    #[test]
+    fn test_replay() {
+        let sensor = |id: &str| {
+            Arc::new(HSensor {
+                name: id.into(),
+                hardware_id: id.into(),
+                info: String::new(),
+                limits: Default::default(),
+                kind: None,
+                internal_index: 0,
+            })
+        };
+        let hardware = Hardware {
+            controls: vec![Arc::new(HControl {
+                name: "control1".into(),
+                hardware_id: "control1".into(),
+                info: String::new(),
+                kind: ControlKind::Pwm,
+                range: ControlRange::default(),
+                capabilities: Capabilities::default(),
+                internal_index: 0,
+            })],
+            fans: vec![sensor("fan1")],
+            temps: vec![sensor("temp1")],
+            ..Default::default()
+        };
+
+        let lines = [
+            Record::Hardware { hardware },
+            Record::SensorValue {
+                time: 0,
+                id: "temp1".into(),
+                result: Ok(40.0),
+            },
+            Record::SensorValue {
+                time: 0,
+                id: "fan1".into(),
+                result: Err("can't read".into()),
+            },
+            Record::SetMode {
+                time: 0,
+                id: "control1".into(),
+                mode: Mode::Manual,
+            },
+            Record::SensorValue {
+                time: 1000,
+                id: "temp1".into(),
+                result: Ok(45.0),
+            },
+        ]
+        .iter()
+        .map(|record| serde_json::to_string(record).unwrap())
+        .collect::<Vec<_>>();
+
+        let records = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let mut bridge = ReplayBridge::from_records(records).unwrap();
+
+        let hardware = bridge.hardware();
+        let temp = hardware.temps[0].clone();
+        let fan = hardware.fans[0].clone();
+        let control = hardware.controls[0].clone();
+        // indexes are rebuilt, so sensors can be told apart
+        assert_ne!(temp, fan);
+
+        assert_eq!(bridge.get_sensor_value(&temp).unwrap(), 40.0);
+        assert!(bridge.get_sensor_value(&fan).is_err());
+        assert_eq!(bridge.get_sensor_value(&temp).unwrap(), 45.0);
+        // the last reading is repeated
+        assert_eq!(bridge.get_sensor_value(&temp).unwrap(), 45.0);
+        assert!(bridge.get_control_value(&control).is_err());
+
+        bridge.set_mode(&control, &Mode::Manual).unwrap();
+        assert_eq!(bridge.calls().len(), bridge.recorded_calls().len());
+    }
+
+    #[test]
+    fn test_replay_rescan() {
+        let hardware = |ids: &[&str]| Hardware {
+            temps: ids
+                .iter()
+                .map(|id| {
+                    Arc::new(HSensor {
+                        name: (*id).into(),
+                        hardware_id: (*id).into(),
+                        info: String::new(),
+                        limits: Default::default(),
+                        kind: None,
+                        internal_index: 0,
+                    })
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let records = vec![
+            Record::Hardware {
+                hardware: hardware(&["temp1"]),
+            },
+            Record::SensorValue {
+                time: 0,
+                id: "temp1".into(),
+                result: Ok(40.0),
+            },
+            Record::Hardware {
+                hardware: hardware(&["temp1", "temp2"]),
+            },
+            Record::SensorValue {
+                time: 1000,
+                id: "temp2".into(),
+                result: Ok(50.0),
+            },
+        ];
+        let mut bridge = ReplayBridge::from_records(records).unwrap();
+        assert_eq!(bridge.hardware().temps.len(), 1);
+
+        let diff = bridge.rescan().unwrap();
+        assert_eq!(diff.added, ["temp2"]);
+        assert!(diff.removed.is_empty());
+        let temp2 = bridge.hardware().temps[1].clone();
+        assert_eq!(temp2.internal_index, 1);
+        assert_eq!(bridge.get_sensor_value(&temp2).unwrap(), 50.0);
+
+        // no rescan left
+        assert!(bridge.rescan().unwrap().is_empty());
+        assert_eq!(bridge.hardware().temps.len(), 2);
+    }
+}
@@ -0,0 +1,738 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use thiserror::Error;
+
+use crate::{
+    HControl, HSensor, Hardware, HardwareBridge, HardwareDiff, Mode, OutputMode, SensorValue, Value,
+};
+
+// The client and the agent exchange JSON lines: each request
+// of the client is answered by exactly one line of the agent.
+
+/// Longest wait for an answer, before the agent is considered gone.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the agent checks if it must stop, while waiting.
+const STOP_POLL: Duration = Duration::from_millis(200);
+
+#[derive(Error, Debug)]
+pub enum RemoteError {
+    #[error("{0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Invalid message: {0}")]
+    Parse(serde_json::Error),
+    #[error("The connection was closed")]
+    Closed,
+    #[error("An agent address is needed")]
+    NoAddress,
+    #[error("Unknown hardware id {0}")]
+    UnknownId(String),
+    #[error("Unexpected answer of the agent: {0}")]
+    Unexpected(String),
+    #[error("Agent: {0}")]
+    Agent(String),
+}
+
+type Result<T> = std::result::Result<T, RemoteError>;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind")]
+enum Request {
+    Hardware,
+    SensorValue {
+        id: String,
+    },
+    ControlValue {
+        id: String,
+    },
+    SetValue {
+        id: String,
+        value: Value,
+    },
+    SetMode {
+        id: String,
+        mode: Mode,
+    },
+    Frequency {
+        id: String,
+    },
+    SetFrequency {
+        id: String,
+        frequency: Value,
+    },
+    OutputMode {
+        id: String,
+    },
+    SetOutputMode {
+        id: String,
+        mode: OutputMode,
+    },
+    Rescan,
+    Update,
+    /// Controls set by the client go back to auto
+    Shutdown,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Reply {
+    Hardware(Hardware),
+    Value(Value),
+    Reading(SensorValue),
+    OutputMode(OutputMode),
+    Rescanned {
+        hardware: Hardware,
+        result: std::result::Result<HardwareDiff, String>,
+    },
+    Done,
+}
+
+/// Errors of the bridge of the agent are sent as text.
+type Answer = std::result::Result<Reply, String>;
+
+/// A connection carrying JSON lines.
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    line: String,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Result<Self> {
+        let reader = match stream.try_clone() {
+            Ok(reader) => reader,
+            Err(e) => return Err(RemoteError::Io("can't clone the stream".into(), e)),
+        };
+
+        Ok(Self {
+            reader: BufReader::new(reader),
+            writer: BufWriter::new(stream),
+            line: String::new(),
+        })
+    }
+
+    fn send(&mut self, message: &impl Serialize) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, message).map_err(RemoteError::Parse)?;
+
+        self.writer
+            .write_all(b"\n")
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RemoteError::Io("can't send a message".into(), e))
+    }
+
+    /// Return None when the other side closed the connection. After a read
+    /// timeout, the part of the line already received is kept for the next call.
+    fn receive<T: DeserializeOwned>(&mut self) -> Result<Option<T>> {
+        match self.reader.read_line(&mut self.line) {
+            Ok(0) => Ok(None),
+            Ok(_) => {
+                let message = serde_json::from_str(&self.line)
+                    .map(Some)
+                    .map_err(RemoteError::Parse);
+                self.line.clear();
+                message
+            }
+            Err(e) => Err(RemoteError::Io("can't receive a message".into(), e)),
+        }
+    }
+}
+
+fn is_timeout(error: &RemoteError) -> bool {
+    matches!(
+        error,
+        RemoteError::Io(_, e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+    )
+}
+
+/// Drive the hardware of another machine, through an [`Agent`].
+pub struct RemoteBridge {
+    address: Vec<SocketAddr>,
+    /// None after an error: a late answer to the failed request would be
+    /// taken for the answer to the next one, so a new connection is opened
+    connection: Option<Connection>,
+    hardware: Hardware,
+    /// Controls taken by this client, taken again after a reconnection
+    /// because the agent gives them back to the hardware when a client is gone
+    modes: HashMap<String, Mode>,
+}
+
+fn open(address: &[SocketAddr]) -> Result<Connection> {
+    let stream = TcpStream::connect(address)
+        .map_err(|e| RemoteError::Io("can't connect to the agent".into(), e))?;
+
+    // values are read one by one, don't wait to fill a packet
+    if let Err(e) = stream
+        .set_nodelay(true)
+        .and_then(|_| stream.set_read_timeout(Some(TIMEOUT)))
+    {
+        return Err(RemoteError::Io("can't configure the connection".into(), e));
+    }
+
+    if let Ok(address) = stream.peer_addr() {
+        info!("Connected to the agent {address}.");
+    }
+
+    Connection::new(stream)
+}
+
+/// Send a request and wait for its answer.
+fn exchange(connection: &mut Connection, request: &Request) -> crate::Result<Reply> {
+    connection.send(request)?;
+
+    match connection.receive::<Answer>()? {
+        Some(answer) => answer.map_err(|e| RemoteError::Agent(e).into()),
+        None => Err(RemoteError::Closed.into()),
+    }
+}
+
+impl RemoteBridge {
+    pub fn connect(address: impl ToSocketAddrs) -> crate::Result<Self> {
+        let address = address
+            .to_socket_addrs()
+            .map_err(|e| RemoteError::Io("can't resolve the address of the agent".into(), e))?
+            .collect::<Vec<_>>();
+
+        let mut bridge = Self {
+            connection: Some(open(&address)?),
+            address,
+            hardware: Hardware::default(),
+            modes: HashMap::new(),
+        };
+
+        bridge.hardware = match bridge.call(Request::Hardware)? {
+            Reply::Hardware(hardware) => hardware,
+            reply => return Err(unexpected(reply)),
+        };
+        Ok(bridge)
+    }
+
+    fn call(&mut self, request: Request) -> crate::Result<Reply> {
+        let mut connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => self.reconnect()?,
+        };
+
+        let reply = exchange(&mut connection, &request);
+        // errors of the agent are answers, the connection is still in sync
+        match &reply {
+            Err(crate::HardwareError::Remote(e)) if !matches!(e, RemoteError::Agent(_)) => {}
+            _ => self.connection = Some(connection),
+        }
+        reply
+    }
+
+    fn reconnect(&self) -> crate::Result<Connection> {
+        let mut connection = open(&self.address)?;
+        for (id, mode) in &self.modes {
+            let request = Request::SetMode {
+                id: id.clone(),
+                mode: mode.clone(),
+            };
+            match exchange(&mut connection, &request) {
+                Ok(Reply::Done) => {}
+                Ok(reply) => return Err(unexpected(reply)),
+                Err(crate::HardwareError::Remote(RemoteError::Agent(e))) => {
+                    error!("Can't take {id} again: {e}.")
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(connection)
+    }
+
+    fn call_value(&mut self, request: Request) -> crate::Result<Value> {
+        match self.call(request)? {
+            Reply::Value(value) => Ok(value),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn call_done(&mut self, request: Request) -> crate::Result<()> {
+        match self.call(request)? {
+            Reply::Done => Ok(()),
+            reply => Err(unexpected(reply)),
+        }
+    }
+}
+
+fn unexpected(reply: Reply) -> crate::HardwareError {
+    RemoteError::Unexpected(format!("{reply:?}")).into()
+}
+
+impl HardwareBridge for RemoteBridge {
+    fn new() -> crate::Result<Self> {
+        Err(RemoteError::NoAddress.into())
+    }
+
+    fn hardware(&self) -> &Hardware {
+        &self.hardware
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<SensorValue> {
+        match self.call(Request::SensorValue {
+            id: sensor.hardware_id.clone(),
+        })? {
+            Reply::Reading(value) => Ok(value),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        self.call_value(Request::ControlValue {
+            id: control.hardware_id.clone(),
+        })
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        self.call_done(Request::SetValue {
+            id: control.hardware_id.clone(),
+            value,
+        })
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        self.call_done(Request::SetMode {
+            id: control.hardware_id.clone(),
+            mode: mode.clone(),
+        })?;
+
+        match mode {
+            Mode::Auto => self.modes.remove(&control.hardware_id),
+            Mode::Manual | Mode::Specific(_) => {
+                self.modes.insert(control.hardware_id.clone(), mode.clone())
+            }
+        };
+        Ok(())
+    }
+
+    fn get_frequency(&mut self, control: &HControl) -> crate::Result<Value> {
+        self.call_value(Request::Frequency {
+            id: control.hardware_id.clone(),
+        })
+    }
+
+    fn set_frequency(&mut self, control: &HControl, frequency: Value) -> crate::Result<()> {
+        self.call_done(Request::SetFrequency {
+            id: control.hardware_id.clone(),
+            frequency,
+        })
+    }
+
+    fn get_output_mode(&mut self, control: &HControl) -> crate::Result<OutputMode> {
+        match self.call(Request::OutputMode {
+            id: control.hardware_id.clone(),
+        })? {
+            Reply::OutputMode(mode) => Ok(mode),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn set_output_mode(&mut self, control: &HControl, mode: OutputMode) -> crate::Result<()> {
+        self.call_done(Request::SetOutputMode {
+            id: control.hardware_id.clone(),
+            mode,
+        })
+    }
+
+    fn rescan(&mut self) -> crate::Result<HardwareDiff> {
+        match self.call(Request::Rescan)? {
+            Reply::Rescanned { hardware, result } => {
+                self.hardware = hardware;
+                result.map_err(|e| RemoteError::Agent(e).into())
+            }
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn update(&mut self) -> crate::Result<()> {
+        self.call_done(Request::Update)
+    }
+
+    fn shutdown(&mut self) -> crate::Result<()> {
+        self.call_done(Request::Shutdown)?;
+        self.modes.clear();
+        Ok(())
+    }
+}
+
+/// Serve a local bridge to the [`RemoteBridge`] of another instance,
+/// one client at a time. There is no authentication: the agent must
+/// only be reachable from a trusted network.
+pub struct Agent<H: HardwareBridge> {
+    bridge: H,
+    listener: TcpListener,
+}
+
+impl<H: HardwareBridge> Agent<H> {
+    pub fn bind(bridge: H, address: impl ToSocketAddrs) -> crate::Result<Self> {
+        let listener = TcpListener::bind(address)
+            .map_err(|e| RemoteError::Io("can't listen for clients".into(), e))?;
+
+        Ok(Self { bridge, listener })
+    }
+
+    pub fn local_addr(&self) -> crate::Result<SocketAddr> {
+        self.listener
+            .local_addr()
+            .map_err(|e| RemoteError::Io("can't get the address of the agent".into(), e).into())
+    }
+
+    pub fn into_inner(self) -> H {
+        self.bridge
+    }
+
+    /// Serve clients, one after another, until `stop` is set, e.g. by a signal.
+    /// The controls taken by the current client are given back before returning.
+    pub fn serve(&mut self, stop: &AtomicBool) -> crate::Result<()> {
+        if let Ok(address) = self.listener.local_addr() {
+            info!("Agent listening on {address}.");
+        }
+
+        self.listener
+            .set_nonblocking(true)
+            .map_err(|e| RemoteError::Io("can't configure the listener".into(), e))?;
+
+        while !stop.load(Ordering::Relaxed) {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    if let Err(e) = self.serve_client(stream, address, stop) {
+                        error!("{e}");
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(STOP_POLL),
+                Err(e) => error!("{}", RemoteError::Io("can't accept a client".into(), e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait for a client, and answer it until it disconnects. The controls
+    /// it took are given back to the hardware, even if the connection is lost.
+    pub fn serve_one(&mut self) -> crate::Result<()> {
+        let (stream, address) = self
+            .listener
+            .accept()
+            .map_err(|e| RemoteError::Io("can't accept a client".into(), e))?;
+
+        self.serve_client(stream, address, &AtomicBool::new(false))
+    }
+
+    fn serve_client(
+        &mut self,
+        stream: TcpStream,
+        address: SocketAddr,
+        stop: &AtomicBool,
+    ) -> crate::Result<()> {
+        info!("Client {address} connected.");
+        let _ = stream.set_nodelay(true);
+        // some platforms give the client the mode of the listener
+        if let Err(e) = stream
+            .set_nonblocking(false)
+            .and_then(|_| stream.set_read_timeout(Some(STOP_POLL)))
+        {
+            return Err(RemoteError::Io("can't configure the connection".into(), e).into());
+        }
+        let mut connection = Connection::new(stream)?;
+        let mut manual = HashSet::new();
+
+        let result = loop {
+            let request = match connection.receive::<Request>() {
+                Ok(Some(request)) => request,
+                Ok(None) => break Ok(()),
+                Err(e) if is_timeout(&e) => {
+                    if stop.load(Ordering::Relaxed) {
+                        break Ok(());
+                    }
+                    continue;
+                }
+                Err(e) => break Err(e),
+            };
+
+            let shutdown = matches!(request, Request::Shutdown);
+            let answer = self.answer(request, &mut manual);
+            if let Err(e) = connection.send(&answer) {
+                break Err(e);
+            }
+            if shutdown {
+                break Ok(());
+            }
+        };
+
+        self.release(&mut manual);
+        info!("Client {address} disconnected.");
+        result.map_err(Into::into)
+    }
+
+    fn answer(&mut self, request: Request, manual: &mut HashSet<String>) -> Answer {
+        let reply = match request {
+            Request::Hardware => Ok(Reply::Hardware(self.bridge.hardware().clone())),
+            Request::SensorValue { id } => self
+                .sensor(&id)
+                .and_then(|sensor| self.bridge.get_sensor_value(&sensor))
+                .map(Reply::Reading),
+            Request::ControlValue { id } => self
+                .control(&id)
+                .and_then(|control| self.bridge.get_control_value(&control))
+                .map(Reply::Value),
+            Request::SetValue { id, value } => self
+                .control(&id)
+                .and_then(|control| self.bridge.set_value(&control, value))
+                .map(|_| Reply::Done),
+            Request::SetMode { id, mode } => self
+                .control(&id)
+                .and_then(|control| self.bridge.set_mode(&control, &mode))
+                .map(|_| {
+                    match mode {
+                        Mode::Auto => manual.remove(&id),
+                        Mode::Manual | Mode::Specific(_) => manual.insert(id),
+                    };
+                    Reply::Done
+                }),
+            Request::Frequency { id } => self
+                .control(&id)
+                .and_then(|control| self.bridge.get_frequency(&control))
+                .map(Reply::Value),
+            Request::SetFrequency { id, frequency } => self
+                .control(&id)
+                .and_then(|control| self.bridge.set_frequency(&control, frequency))
+                .map(|_| Reply::Done),
+            Request::OutputMode { id } => self
+                .control(&id)
+                .and_then(|control| self.bridge.get_output_mode(&control))
+                .map(Reply::OutputMode),
+            Request::SetOutputMode { id, mode } => self
+                .control(&id)
+                .and_then(|control| self.bridge.set_output_mode(&control, mode))
+                .map(|_| Reply::Done),
+            Request::Rescan => {
+                let result = self.bridge.rescan().map_err(|e| e.to_string());
+                Ok(Reply::Rescanned {
+                    hardware: self.bridge.hardware().clone(),
+                    result,
+                })
+            }
+            Request::Update => self.bridge.update().map(|_| Reply::Done),
+            Request::Shutdown => {
+                self.release(manual);
+                Ok(Reply::Done)
+            }
+        };
+
+        reply.map_err(|e| e.to_string())
+    }
+
+    /// Set the controls taken by a client back to auto.
+    fn release(&mut self, manual: &mut HashSet<String>) {
+        for id in manual.drain() {
+            if let Err(e) = self
+                .control(&id)
+                .and_then(|control| self.bridge.set_mode(&control, &Mode::Auto))
+            {
+                error!("Can't set {id} back to auto: {e}.");
+            }
+        }
+    }
+
+    fn sensor(&self, id: &str) -> crate::Result<Arc<HSensor>> {
+        let hardware = self.bridge.hardware();
+        hardware
+            .fans
+            .iter()
+            .chain(&hardware.temps)
+            .chain(&hardware.sensors)
+            .find(|sensor| sensor.hardware_id == id)
+            .cloned()
+            .ok_or_else(|| RemoteError::UnknownId(id.to_owned()).into())
+    }
+
+    fn control(&self, id: &str) -> crate::Result<Arc<HControl>> {
+        self.bridge
+            .hardware()
+            .controls
+            .iter()
+            .find(|control| control.hardware_id == id)
+            .cloned()
+            .ok_or_else(|| RemoteError::UnknownId(id.to_owned()).into())
+    }
+}
+
+#[cfg(all(test, feature = "fake_hardware"))]
+mod test {
+    use std::{
+        net::TcpListener,
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread,
+    };
+
+    use super::{Agent, Answer, Connection, RemoteBridge, Reply, Request};
+    use crate::{
+        HardwareBridge, Mode,
+        fake_hardware::{Call, FakeHardwareBridge, Scenario},
+    };
+
+    #[test]
+    fn test_loopback() {
+        let mut agent = Agent::bind(
+            FakeHardwareBridge::from_scenario(Scenario::default()),
+            "127.0.0.1:0",
+        )
+        .unwrap();
+        let address = agent.local_addr().unwrap();
+        let agent = thread::spawn(move || {
+            agent.serve_one().unwrap();
+            agent.into_inner()
+        });
+
+        let mut local = FakeHardwareBridge::from_scenario(Scenario::default());
+        let mut remote = RemoteBridge::connect(address).unwrap();
+
+        let hardware = remote.hardware().clone();
+        assert_eq!(
+            serde_json::to_string(&hardware).unwrap(),
+            serde_json::to_string(local.hardware()).unwrap()
+        );
+
+        for _ in 0..3 {
+            remote.update().unwrap();
+            local.update().unwrap();
+        }
+        let local_temp = local.hardware().temps[0].clone();
+        let local_fan = local.hardware().fans[0].clone();
+        assert_eq!(
+            remote.get_sensor_value(&hardware.temps[0]).unwrap(),
+            local.get_sensor_value(&local_temp).unwrap()
+        );
+        assert_eq!(
+            remote.get_sensor_value(&hardware.fans[0]).unwrap(),
+            local.get_sensor_value(&local_fan).unwrap()
+        );
+
+        let control = &hardware.controls[0];
+        remote.set_mode(control, &Mode::Manual).unwrap();
+        remote.set_value(control, 40).unwrap();
+        assert_eq!(remote.get_control_value(control).unwrap(), 40);
+
+        // errors of the agent are forwarded
+        assert!(remote.get_frequency(control).is_err());
+
+        // the connection is lost without a shutdown
+        drop(remote);
+        let bridge = agent.join().unwrap();
+
+        let id = &control.hardware_id;
+        assert_eq!(
+            bridge.calls(),
+            [
+                Call::SetMode {
+                    tick: 3,
+                    hardware_id: id.clone(),
+                    mode: Mode::Manual
+                },
+                Call::SetValue {
+                    tick: 3,
+                    hardware_id: id.clone(),
+                    value: 40
+                },
+                Call::SetMode {
+                    tick: 3,
+                    hardware_id: id.clone(),
+                    mode: Mode::Auto
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stop() {
+        let mut agent = Agent::bind(
+            FakeHardwareBridge::from_scenario(Scenario::default()),
+            "127.0.0.1:0",
+        )
+        .unwrap();
+        let address = agent.local_addr().unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let agent = thread::spawn({
+            let stop = Arc::clone(&stop);
+            move || {
+                agent.serve(&stop).unwrap();
+                agent.into_inner()
+            }
+        });
+
+        let mut remote = RemoteBridge::connect(address).unwrap();
+        let control = remote.hardware().controls[0].clone();
+        remote.set_mode(&control, &Mode::Manual).unwrap();
+
+        // the client is still connected
+        stop.store(true, Ordering::Relaxed);
+        let bridge = agent.join().unwrap();
+        assert!(matches!(
+            bridge.calls().last(),
+            Some(Call::SetMode {
+                mode: Mode::Auto,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let hardware = FakeHardwareBridge::from_scenario(Scenario::default())
+            .hardware()
+            .clone();
+        let control = hardware.controls[0].clone();
+
+        let agent = thread::spawn(move || {
+            let accept = || Connection::new(listener.accept().unwrap().0).unwrap();
+            let answer = |connection: &mut Connection, answer: Answer| {
+                let request = connection.receive::<Request>().unwrap().unwrap();
+                connection.send(&answer).unwrap();
+                request
+            };
+
+            let mut first = accept();
+            answer(&mut first, Ok(Reply::Hardware(hardware)));
+            answer(&mut first, Ok(Reply::Done));
+            // not the answer to the last request
+            first.receive::<Request>().unwrap();
+            first.send(&"garbage").unwrap();
+
+            let mut second = accept();
+            [
+                answer(&mut second, Ok(Reply::Done)),
+                answer(&mut second, Ok(Reply::Done)),
+            ]
+        });
+
+        let mut remote = RemoteBridge::connect(address).unwrap();
+        remote.set_mode(&control, &Mode::Manual).unwrap();
+        assert!(remote.set_value(&control, 40).is_err());
+        remote.set_value(&control, 50).unwrap();
+
+        // the control is taken again on the new connection
+        let requests = agent.join().unwrap();
+        assert!(matches!(
+            requests[0],
+            Request::SetMode {
+                mode: Mode::Manual,
+                ..
+            }
+        ));
+        assert!(matches!(requests[1], Request::SetValue { value: 50, .. }));
+    }
+}
@@ -0,0 +1,486 @@
+use std::{fs, path::Path, sync::Arc};
+
+use serde::Deserialize;
+
+use crate::{
+    Capabilities, ControlKind, ControlRange, HControl, HSensor, Hardware, HardwareBridge, Mode,
+    SensorValue, Value,
+    fake_hardware::{self, Call, FakeError, Timeline},
+};
+
+/// Fake bridge where temperatures react to the duty cycle written by the app.
+///
+/// Each heat source is a thermal mass heated by a load, and cooled by the
+/// ambient air and by the fans blowing on it. Each fan has an RPM that follows
+/// its duty cycle with some inertia, can stall at low duty and needs a higher
+/// duty to start again.
+pub struct SimulatedHardwareBridge {
+    hardware: Hardware,
+    ambient: f64,
+    /// Seconds elapsed between two calls of update
+    step: f64,
+    tick: usize,
+    /// Temp sensors, indexed by internal_index
+    sources: Vec<SourceState>,
+    /// Controls, indexed by internal_index. Fan sensors are indexed
+    /// after the temp sensors.
+    fans: Vec<FanState>,
+    calls: Vec<Call>,
+}
+
+type Result<T> = std::result::Result<T, FakeError>;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Simulation {
+    /// Seconds elapsed between two calls of update
+    #[serde(default = "default_step")]
+    pub step: f64,
+    /// Temperature of the air, in °C
+    #[serde(default = "default_ambient")]
+    pub ambient: f64,
+    #[serde(default, rename = "HeatSource")]
+    pub sources: Vec<HeatSource>,
+    #[serde(default, rename = "Fan")]
+    pub fans: Vec<SimulatedFan>,
+}
+
+fn default_step() -> f64 {
+    1.0
+}
+
+fn default_ambient() -> f64 {
+    25.0
+}
+
+/// Exposed as a Temp sensor.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HeatSource {
+    pub id: String,
+    pub name: Option<String>,
+    /// Heat produced, in W
+    pub load: Timeline,
+    /// Energy needed to raise the temperature by 1°C, in J/°C
+    #[serde(rename = "thermalMass")]
+    pub thermal_mass: f64,
+    /// Heat dissipated without any fan, in W/°C above ambient
+    #[serde(rename = "passiveCooling")]
+    pub passive_cooling: f64,
+    /// Heat dissipated by each fan at its max RPM, in W/°C above ambient.
+    /// Scale linearly with the RPM.
+    pub cooling: f64,
+    /// Ids of the fans cooling this source
+    #[serde(default)]
+    pub fans: Vec<String>,
+    /// Start temperature, ambient if not set
+    pub initial: Option<f64>,
+}
+
+/// Exposed as a Fan sensor, and a Control.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SimulatedFan {
+    pub id: String,
+    pub name: Option<String>,
+    /// Id of the control driving this fan
+    pub control: String,
+    #[serde(rename = "maxRpm")]
+    pub max_rpm: f64,
+    /// Minimum duty, in percent, to start a stopped fan
+    #[serde(rename = "startDuty", default)]
+    pub start_duty: f64,
+    /// Duty, in percent, under which a running fan stops
+    #[serde(rename = "stopDuty", default)]
+    pub stop_duty: f64,
+    /// Time constant of the RPM, in seconds
+    #[serde(default)]
+    pub inertia: f64,
+    /// Duty, in percent, used when the control is in auto mode
+    #[serde(rename = "autoDuty", default = "default_auto_duty")]
+    pub auto_duty: f64,
+}
+
+fn default_auto_duty() -> f64 {
+    50.0
+}
+
+struct SourceState {
+    source: HeatSource,
+    /// internal_index of the fans
+    fans: Vec<usize>,
+    temp: f64,
+}
+
+struct FanState {
+    fan: SimulatedFan,
+    duty: Value,
+    mode: Mode,
+    rpm: f64,
+}
+
+impl FanState {
+    fn effective_duty(&self) -> f64 {
+        match self.mode {
+            Mode::Auto => self.fan.auto_duty,
+            _ => self.duty as f64,
+        }
+    }
+
+    fn target_rpm(&self) -> f64 {
+        let duty = self.effective_duty();
+
+        let running = if self.rpm > 0.0 {
+            duty >= self.fan.stop_duty
+        } else {
+            duty > 0.0 && duty >= self.fan.start_duty
+        };
+
+        if running {
+            self.fan.max_rpm * duty.min(100.0) / 100.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Part of the max airflow, between 0 and 1.
+    fn airflow(&self) -> f64 {
+        if self.fan.max_rpm > 0.0 {
+            self.rpm / self.fan.max_rpm
+        } else {
+            0.0
+        }
+    }
+
+    fn simulate(&mut self, dt: f64) {
+        let target = self.target_rpm();
+
+        if target == 0.0 && self.rpm > 0.0 && self.effective_duty() < self.fan.stop_duty {
+            // stalled fans stop almost immediately
+            self.rpm = 0.0;
+            return;
+        }
+
+        if self.fan.inertia > 0.0 {
+            self.rpm += (target - self.rpm) * (dt / self.fan.inertia).min(1.0);
+        } else {
+            self.rpm = target;
+        }
+    }
+}
+
+impl Simulation {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                return Err(FakeError::Io(
+                    format!("can't read simulation {}", path.display()),
+                    e,
+                ));
+            }
+        };
+
+        let mut simulation = toml::from_str::<Simulation>(&content)?;
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for source in &mut simulation.sources {
+            source.load.load_samples(dir)?;
+        }
+
+        Ok(simulation)
+    }
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self {
+            step: default_step(),
+            ambient: default_ambient(),
+            sources: vec![HeatSource {
+                id: "temp1".into(),
+                name: None,
+                load: Timeline::Step {
+                    before: 20.0,
+                    after: 120.0,
+                    at: 30.0,
+                },
+                thermal_mass: 200.0,
+                passive_cooling: 0.5,
+                cooling: 4.0,
+                fans: vec!["fan1".into()],
+                initial: None,
+            }],
+            fans: vec![SimulatedFan {
+                id: "fan1".into(),
+                name: None,
+                control: "control1".into(),
+                max_rpm: 2000.0,
+                start_duty: 30.0,
+                stop_duty: 20.0,
+                inertia: 2.0,
+                auto_duty: default_auto_duty(),
+            }],
+        }
+    }
+}
+
+impl SimulatedHardwareBridge {
+    pub fn from_simulation(simulation: Simulation) -> Self {
+        let mut hardware = Hardware::default();
+        let mut fans = Vec::new();
+
+        for fan in simulation.fans {
+            let name = fan.name.clone().unwrap_or_else(|| fan.id.clone());
+
+            hardware.fans.push(Arc::new(HSensor {
+                name: name.clone(),
+                hardware_id: fan.id.clone(),
+                info: String::new(),
+                limits: Default::default(),
+                kind: None,
+                internal_index: simulation.sources.len() + fans.len(),
+            }));
+            hardware.controls.push(Arc::new(HControl {
+                name: format!("{name} control"),
+                hardware_id: fan.control.clone(),
+                info: String::new(),
+                kind: ControlKind::Pwm,
+                range: ControlRange::default(),
+                capabilities: Capabilities::default(),
+                internal_index: fans.len(),
+            }));
+            fans.push(FanState {
+                fan,
+                duty: 0,
+                mode: Mode::Auto,
+                rpm: 0.0,
+            });
+        }
+
+        let mut sources = Vec::new();
+
+        for source in simulation.sources {
+            hardware.temps.push(Arc::new(HSensor {
+                name: source.name.clone().unwrap_or_else(|| source.id.clone()),
+                hardware_id: source.id.clone(),
+                info: String::new(),
+                limits: Default::default(),
+                kind: None,
+                internal_index: sources.len(),
+            }));
+
+            let source_fans = source
+                .fans
+                .iter()
+                .filter_map(|id| {
+                    let index = fans.iter().position(|f| &f.fan.id == id);
+                    if index.is_none() {
+                        warn!("fan {id} of heat source {} doesn't exist", source.id);
+                    }
+                    index
+                })
+                .collect();
+
+            sources.push(SourceState {
+                temp: source.initial.unwrap_or(simulation.ambient),
+                fans: source_fans,
+                source,
+            });
+        }
+
+        Self {
+            hardware,
+            ambient: simulation.ambient,
+            step: simulation.step,
+            tick: 0,
+            sources,
+            fans,
+            calls: Vec::new(),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> crate::Result<Self> {
+        let simulation = Simulation::from_file(path)?;
+        Ok(Self::from_simulation(simulation))
+    }
+
+    /// Time of the simulation, in seconds.
+    pub fn time(&self) -> f64 {
+        self.tick as f64 * self.step
+    }
+
+    /// Exact temperature of a heat source.
+    pub fn temperature(&self, hardware_id: &str) -> Option<f64> {
+        self.sources
+            .iter()
+            .find(|s| s.source.id == hardware_id)
+            .map(|s| s.temp)
+    }
+
+    /// Exact RPM of a fan.
+    pub fn rpm(&self, hardware_id: &str) -> Option<f64> {
+        self.fans
+            .iter()
+            .find(|f| f.fan.id == hardware_id)
+            .map(|f| f.rpm)
+    }
+
+    /// All calls made on controls, in order.
+    pub fn calls(&self) -> &[Call] {
+        &self.calls
+    }
+
+    /// Values set on a control, in order.
+    pub fn values_set(&self, hardware_id: &str) -> Vec<Value> {
+        fake_hardware::values_set(&self.calls, hardware_id)
+    }
+
+    /// Advance the simulation by `duration` seconds.
+    pub fn simulate(&mut self, duration: f64) {
+        const MAX_DT: f64 = 0.1;
+
+        let start = self.time();
+        let mut elapsed = 0.0;
+
+        while elapsed < duration {
+            let dt = MAX_DT.min(duration - elapsed);
+
+            for fan in &mut self.fans {
+                fan.simulate(dt);
+            }
+
+            for state in &mut self.sources {
+                let source = &state.source;
+                let airflow = state
+                    .fans
+                    .iter()
+                    .map(|index| self.fans[*index].airflow())
+                    .sum::<f64>();
+
+                let conductance = source.passive_cooling + source.cooling * airflow;
+                let heat = source.load.value_at(start + elapsed);
+                let cooling = conductance * (state.temp - self.ambient);
+
+                state.temp += (heat - cooling) * dt / source.thermal_mass;
+            }
+
+            elapsed += dt;
+        }
+    }
+}
+
+impl HardwareBridge for SimulatedHardwareBridge {
+    fn new() -> crate::Result<Self> {
+        Ok(Self::from_simulation(Simulation::default()))
+    }
+
+    fn hardware(&self) -> &Hardware {
+        &self.hardware
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<SensorValue> {
+        let index = sensor.internal_index;
+        let value = match index.checked_sub(self.sources.len()) {
+            Some(fan_index) => self.fans[fan_index].rpm,
+            None => self.sources[index].temp,
+        };
+        Ok(value)
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        Ok(self.fans[control.internal_index].effective_duty().round() as Value)
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        self.fans[control.internal_index].duty = value;
+        self.calls.push(Call::SetValue {
+            tick: self.tick,
+            hardware_id: control.hardware_id.clone(),
+            value,
+        });
+        Ok(())
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        self.fans[control.internal_index].mode = mode.clone();
+        self.calls.push(Call::SetMode {
+            tick: self.tick,
+            hardware_id: control.hardware_id.clone(),
+            mode: mode.clone(),
+        });
+        Ok(())
+    }
+
+    fn update(&mut self) -> crate::Result<()> {
+        self.simulate(self.step);
+        self.tick += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{SimulatedHardwareBridge, Simulation};
+    use crate::{HardwareBridge, Mode, fake_hardware::Timeline};
+
+    fn simulation() -> Simulation {
+        let mut simulation = Simulation::default();
+        simulation.sources[0].load = Timeline::Constant { value: 100.0 };
+        simulation
+    }
+
+    fn run(bridge: &mut SimulatedHardwareBridge, duty: i32, seconds: usize) {
+        let control = bridge.hardware().controls[0].clone();
+        bridge.set_mode(&control, &Mode::Manual).unwrap();
+        bridge.set_value(&control, duty).unwrap();
+        for _ in 0..seconds {
+            bridge.update().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_equilibrium() {
+        // without airflow: 25 + 100 / 0.5
+        let mut bridge = SimulatedHardwareBridge::from_simulation(simulation());
+        run(&mut bridge, 0, 3000);
+        assert!((bridge.temperature("temp1").unwrap() - 225.0).abs() < 1.0);
+
+        // full airflow: 25 + 100 / 4.5
+        let mut bridge = SimulatedHardwareBridge::from_simulation(simulation());
+        run(&mut bridge, 100, 3000);
+        assert!((bridge.temperature("temp1").unwrap() - 47.2).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_parse() {
+        let simulation = Simulation::from_file(Path::new("./scenarios/simulation.toml")).unwrap();
+        let bridge = SimulatedHardwareBridge::from_simulation(simulation);
+
+        assert_eq!(bridge.hardware().temps.len(), 2);
+        assert_eq!(bridge.hardware().fans.len(), 1);
+        assert_eq!(bridge.hardware().controls[0].hardware_id, "control1");
+    }
+
+    #[test]
+    fn test_fan_thresholds() {
+        let mut bridge = SimulatedHardwareBridge::from_simulation(simulation());
+
+        // under start duty, a stopped fan doesn't start
+        run(&mut bridge, 25, 10);
+        assert_eq!(bridge.rpm("fan1").unwrap(), 0.0);
+
+        run(&mut bridge, 30, 30);
+        assert!((bridge.rpm("fan1").unwrap() - 600.0).abs() < 1.0);
+
+        // between stop and start duty, a running fan keeps running
+        run(&mut bridge, 25, 30);
+        assert!((bridge.rpm("fan1").unwrap() - 500.0).abs() < 1.0);
+
+        // under stop duty, it stalls
+        run(&mut bridge, 15, 1);
+        assert_eq!(bridge.rpm("fan1").unwrap(), 0.0);
+    }
+}
@@ -0,0 +1,1348 @@
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use thiserror::Error;
+
+use crate::{
+    Capabilities, ControlKind, ControlRange, HControl, HSensor, Hardware, HardwareBridge,
+    HardwareDiff, HardwareError, Mode, OutputMode, SensorKind, SensorLimits, SensorValue, Value,
+};
+
+// https://www.kernel.org/doc/Documentation/hwmon/sysfs-interface
+// https://github.com/lm-sensors/lm-sensors/blob/master/lib/sysfs.c
+
+pub(crate) static DEFAULT_SYSFS_ROOT: &str = "/sys";
+
+static DEFAULT_PWM_ENABLE: u32 = 5;
+static MANUAL_MODE: u32 = 1;
+/// Used when a fan with a target doesn't expose `fan{N}_max`
+static DEFAULT_MAX_RPM: Value = 5000;
+
+/// Linux bridge that reads the hwmon class directly from sysfs,
+/// without linking to libsensors.
+pub struct SysfsBridge {
+    root: PathBuf,
+    sensors: Vec<InternalAttribute>,
+    hardware: Hardware,
+}
+
+#[derive(Error, Debug)]
+pub enum SysfsError {
+    #[error("{0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Can't parse \"{1}\" read from {0}")]
+    Parse(PathBuf, String),
+    #[error("{0} is not present anymore")]
+    NotFound(String),
+    #[error("a fan target has no mode {0}")]
+    FanTargetMode(Value),
+    #[error("{0} is not a pwm mode")]
+    PwmMode(Value),
+}
+
+type Result<T> = std::result::Result<T, SysfsError>;
+
+struct PwmAttributes {
+    io: PathBuf,
+    enable: PathBuf,
+    default_enable_cached: u32,
+    frequency: Option<CachedAttribute>,
+    output_mode: Option<CachedAttribute>,
+    /// False when the pwm was found again by a rescan
+    release_on_drop: bool,
+}
+
+impl PwmAttributes {
+    /// Take over the state cached by a previous scan of the same pwm,
+    /// which then doesn't touch the hardware when dropped.
+    fn inherit(&mut self, previous: &mut Self) {
+        self.default_enable_cached = previous.default_enable_cached;
+        for (attribute, previous) in [
+            (&mut self.frequency, &previous.frequency),
+            (&mut self.output_mode, &previous.output_mode),
+        ] {
+            if let (Some(attribute), Some(previous)) = (attribute, previous) {
+                attribute.default = previous.default;
+            }
+        }
+        previous.release_on_drop = false;
+    }
+
+    fn restore_settings(&self) -> Result<()> {
+        for attribute in self.frequency.iter().chain(&self.output_mode) {
+            write_value(&attribute.path, attribute.default)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PwmAttributes {
+    fn drop(&mut self) {
+        if !self.release_on_drop {
+            return;
+        }
+        if let Err(e) = self.restore_settings() {
+            error!("can't restore the settings of a pwm when quitting: {e}")
+        }
+        if let Err(e) = write_value(&self.enable, self.default_enable_cached) {
+            error!("can't set auto to a pwm sensor when quitting: {e}")
+        }
+    }
+}
+
+/// Optional attribute, with the value it had before we touched it.
+#[derive(Clone)]
+struct CachedAttribute {
+    path: PathBuf,
+    default: Value,
+}
+
+impl CachedAttribute {
+    fn discover(path: PathBuf) -> Option<Self> {
+        if !path.exists() {
+            return None;
+        }
+        match read_value(&path) {
+            Ok(default) => Some(Self { path, default }),
+            Err(e) => {
+                error!("can't read pwm attribute {e}");
+                None
+            }
+        }
+    }
+}
+
+/// Target speed of a fan, for drivers with a closed-loop control
+/// (applesmc, dell_smm, some nct6775 modes). Used by both Linux backends.
+pub(crate) struct FanTargetAttributes {
+    io: PathBuf,
+    /// `{feature}_manual` (applesmc): the firmware ignores the target unless it is 1
+    manual: Option<CachedAttribute>,
+    /// Target before we touched it, restored in auto mode
+    default_target: Value,
+    /// Nothing is restored unless we wrote to the fan
+    target_written: Cell<bool>,
+    manual_written: Cell<bool>,
+    /// False when the fan was found again by a rescan
+    release_on_drop: bool,
+}
+
+impl Drop for FanTargetAttributes {
+    fn drop(&mut self) {
+        if !self.release_on_drop {
+            return;
+        }
+        if let Err(e) = self.restore() {
+            error!("can't give a fan back to its firmware when quitting: {e}")
+        }
+    }
+}
+
+impl FanTargetAttributes {
+    /// Look for `{feature}_target` next to a fan input, `feature` being `fan{N}`.
+    pub(crate) fn discover(chip_path: &Path, feature: &str) -> Option<(Self, ControlRange)> {
+        let io = chip_path.join(format!("{feature}_target"));
+        if !io.exists() {
+            return None;
+        }
+
+        let default_target = match read_value::<Value>(&io) {
+            Ok(value) => value,
+            Err(e) => {
+                error!("can't read the target of a fan {e}");
+                return None;
+            }
+        };
+
+        let range = ControlRange {
+            min: read_value(&chip_path.join(format!("{feature}_min"))).unwrap_or(0),
+            max: read_value(&chip_path.join(format!("{feature}_max"))).unwrap_or(DEFAULT_MAX_RPM),
+            step: 1,
+        };
+
+        Some((
+            Self {
+                io,
+                manual: CachedAttribute::discover(chip_path.join(format!("{feature}_manual"))),
+                default_target,
+                target_written: Cell::new(false),
+                manual_written: Cell::new(false),
+                release_on_drop: true,
+            },
+            range,
+        ))
+    }
+
+    /// Take over what a previous scan of the same fan cached and wrote.
+    pub(crate) fn inherit(&mut self, previous: &mut Self) {
+        self.default_target = previous.default_target;
+        self.target_written.set(previous.target_written.get());
+        if let (Some(manual), Some(previous_manual)) = (&mut self.manual, &previous.manual) {
+            manual.default = previous_manual.default;
+            self.manual_written.set(previous.manual_written.get());
+        }
+        previous.release_on_drop = false;
+    }
+
+    /// Hand the duty of restoring the fan to the returned copy, which only
+    /// needs sysfs to do it.
+    #[cfg(feature = "libsensors")]
+    pub(crate) fn detach(&mut self) -> Self {
+        self.release_on_drop = false;
+        Self {
+            io: self.io.clone(),
+            manual: self.manual.clone(),
+            default_target: self.default_target,
+            target_written: self.target_written.clone(),
+            manual_written: self.manual_written.clone(),
+            release_on_drop: true,
+        }
+    }
+
+    pub(crate) fn get_value(&self) -> Result<Value> {
+        read_value(&self.io)
+    }
+
+    pub(crate) fn set_value(&self, value: Value) -> Result<()> {
+        write_value(&self.io, value)?;
+        self.target_written.set(true);
+        Ok(())
+    }
+
+    /// Only drivers with `{feature}_manual` have a mode to switch,
+    /// for the others writing a target is enough to take control.
+    pub(crate) fn set_mode(&self, mode: &Mode) -> Result<()> {
+        match mode {
+            Mode::Auto => self.restore(),
+            Mode::Manual => match &self.manual {
+                Some(manual) => {
+                    write_value(&manual.path, 1)?;
+                    self.manual_written.set(true);
+                    Ok(())
+                }
+                None => Ok(()),
+            },
+            Mode::Specific(value) => Err(SysfsError::FanTargetMode(*value)),
+        }
+    }
+
+    /// Write back what we changed, the target first so the firmware
+    /// doesn't get it back with our last one.
+    fn restore(&self) -> Result<()> {
+        if self.target_written.get() {
+            write_value(&self.io, self.default_target)?;
+            self.target_written.set(false);
+        }
+        if let Some(manual) = &self.manual
+            && self.manual_written.get()
+        {
+            write_value(&manual.path, manual.default)?;
+            self.manual_written.set(false);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct SensorAttributes {
+    io: PathBuf,
+    /// The kernel exposes temperatures in millidegree Celsius
+    divisor: f64,
+}
+
+impl SensorAttributes {
+    fn new(chip_path: &Path, sub_feature: &str, divisor: f64) -> Self {
+        Self {
+            io: chip_path.join(sub_feature),
+            divisor,
+        }
+    }
+
+    pub(crate) fn get_value(&self) -> Result<SensorValue> {
+        Ok(read_value::<f64>(&self.io)? / self.divisor)
+    }
+
+    /// Read `{feature}_min`, `{feature}_max` and `{feature}_crit`, when the chip has them.
+    fn limits(&self, chip_path: &Path, feature: &str) -> SensorLimits {
+        let read = |limit: &str| {
+            read_value::<f64>(&chip_path.join(format!("{feature}_{limit}")))
+                .ok()
+                .map(|value| value / self.divisor)
+        };
+
+        SensorLimits {
+            min: read("min"),
+            max: read("max"),
+            crit: read("crit"),
+        }
+    }
+}
+
+enum InternalAttribute {
+    Pwm(PwmAttributes),
+    FanTarget(FanTargetAttributes),
+    Sensor(SensorAttributes),
+}
+
+pub(crate) fn read_string(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content.trim().to_owned()),
+        Err(e) => Err(SysfsError::Io(format!("can't read {}", path.display()), e)),
+    }
+}
+
+fn read_value<T: std::str::FromStr>(path: &Path) -> Result<T> {
+    let content = read_string(path)?;
+    content
+        .parse::<T>()
+        .map_err(|_| SysfsError::Parse(path.to_path_buf(), content))
+}
+
+pub(crate) fn write_value(path: &Path, value: impl std::fmt::Display) -> Result<()> {
+    match fs::write(path, value.to_string()) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(SysfsError::Io(
+            format!("can't write {value} to {}", path.display()),
+            e,
+        )),
+    }
+}
+
+/// Return the entries of `dir` which match `{prefix}{number}`, sorted by number.
+fn numbered_entries(dir: &Path, prefix: &str) -> Vec<(u32, PathBuf)> {
+    let Ok(read_dir) = dir.read_dir() else {
+        return Vec::new();
+    };
+
+    let mut entries = read_dir
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let number = file_name.to_str()?.strip_prefix(prefix)?.parse().ok()?;
+            Some((number, entry.path()))
+        })
+        .collect::<Vec<_>>();
+
+    entries.sort_by_key(|(number, _)| *number);
+    entries
+}
+
+/// Return the channel numbers of the `{kind}{N}_{attribute}` files of a chip, sorted.
+/// The pwm outputs are the only one without attribute suffix (`pwm{N}`).
+fn channels(chip_path: &Path, kind: &str, suffix: &str) -> Vec<u32> {
+    let Ok(read_dir) = chip_path.read_dir() else {
+        return Vec::new();
+    };
+
+    let mut channels = read_dir
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let channel = file_name
+                .to_str()?
+                .strip_prefix(kind)?
+                .strip_suffix(suffix)?
+                .parse()
+                .ok()?;
+            Some(channel)
+        })
+        .collect::<Vec<_>>();
+
+    channels.sort_unstable();
+    channels
+}
+
+/// Prefix of the hwmon files of a kind of sensor, and the number of
+/// values they hold in a volt, an ampere, a watt, a joule or a percent.
+fn hwmon_unit(kind: SensorKind) -> (&'static str, f64) {
+    match kind {
+        SensorKind::Voltage => ("in", 1000.0),
+        SensorKind::Current => ("curr", 1000.0),
+        SensorKind::Power => ("power", 1_000_000.0),
+        SensorKind::Energy => ("energy", 1_000_000.0),
+        SensorKind::Humidity => ("humidity", 1000.0),
+    }
+}
+
+/// Channels of a kind of sensor, with the suffix of their value. Some chips,
+/// like amdgpu, only expose an average power (`power{N}_average`).
+fn sensor_channels(chip_path: &Path, kind: SensorKind) -> Vec<(u32, &'static str)> {
+    let (prefix, _) = hwmon_unit(kind);
+    let mut channels = channels(chip_path, prefix, "_input")
+        .into_iter()
+        .map(|channel| (channel, "_input"))
+        .collect::<Vec<_>>();
+
+    if kind == SensorKind::Power {
+        for channel in self::channels(chip_path, prefix, "_average") {
+            if !channels.iter().any(|(c, _)| *c == channel) {
+                channels.push((channel, "_average"));
+            }
+        }
+        channels.sort_unstable();
+    }
+
+    channels
+}
+
+/// Bus and name of the device of a chip, like `("pci", "0000:03:00.0")`.
+/// None for virtual devices.
+fn device(chip_path: &Path) -> Option<(String, String)> {
+    let device_path = chip_path.join("device");
+
+    let subsystem = fs::read_link(device_path.join("subsystem"))
+        .ok()
+        .and_then(|path| Some(path.file_name()?.to_string_lossy().into_owned()));
+
+    let device_name = fs::canonicalize(&device_path)
+        .ok()
+        .and_then(|path| Some(path.file_name()?.to_string_lossy().into_owned()));
+
+    subsystem.zip(device_name)
+}
+
+/// Identify a chip by its driver and its address on its bus, like
+/// `amdgpu/pci:0000:03:00.0`, or `{driver}/virtual` without bus device.
+/// Used as prefix of hardware ids: unlike the chip name, labels can't change it,
+/// and two identical chips on a bus never share it.
+fn chip_id(chip_path: &Path, prefix: &str) -> String {
+    match device(chip_path) {
+        Some((subsystem, device_name)) => format!("{prefix}/{subsystem}:{device_name}"),
+        None => format!("{prefix}/virtual"),
+    }
+}
+
+/// Ids of the chips met during a scan. The `hwmonN` numbers change between
+/// boots, so two identical chips without bus device can only be told apart
+/// by their order: `#2` is added to the id of the second one, and so on.
+#[derive(Default)]
+pub(crate) struct ChipIds {
+    ids: HashMap<PathBuf, String>,
+    taken: HashSet<String>,
+}
+
+impl ChipIds {
+    pub(crate) fn get(&mut self, chip_path: &Path, prefix: &str) -> String {
+        if let Some(id) = self.ids.get(chip_path) {
+            return id.clone();
+        }
+
+        let id = chip_id(chip_path, prefix);
+        let id = if self.taken.contains(&id) {
+            (2..)
+                .map(|n| format!("{id}#{n}"))
+                .find(|candidate| !self.taken.contains(candidate))
+                .unwrap()
+        } else {
+            id
+        };
+
+        self.taken.insert(id.clone());
+        self.ids.insert(chip_path.to_path_buf(), id.clone());
+        id
+    }
+}
+
+/// Build the chip name the same way libsensors does (`sensors_snprintf_chip_name`),
+/// so hardware ids of previous versions stay the same with both Linux backends.
+fn chip_name(chip_path: &Path, prefix: &str) -> String {
+    let Some((subsystem, device_name)) = device(chip_path) else {
+        return format!("{prefix}-virtual-0");
+    };
+
+    let parse_hex = |s: &str| u32::from_str_radix(s, 16).ok();
+
+    match subsystem.as_str() {
+        "i2c" => {
+            if let Some((bus, addr)) = device_name.split_once('-')
+                && let (Ok(bus), Some(addr)) = (bus.parse::<u32>(), parse_hex(addr))
+            {
+                return format!("{prefix}-i2c-{bus}-{addr:02x}");
+            }
+        }
+        "pci" => {
+            // domain:bus:slot.function
+            let parts = device_name
+                .split([':', '.'])
+                .map(parse_hex)
+                .collect::<Option<Vec<_>>>();
+
+            if let Some([domain, bus, slot, function]) = parts.as_deref() {
+                let addr = (domain << 16) + (bus << 8) + (slot << 3) + function;
+                return format!("{prefix}-pci-{addr:04x}");
+            }
+        }
+        "platform" | "of_platform" => {
+            let addr = device_name
+                .rsplit_once('.')
+                .and_then(|(_, addr)| addr.parse::<u32>().ok())
+                .unwrap_or(0);
+            return format!("{prefix}-isa-{addr:04x}");
+        }
+        "acpi" => return format!("{prefix}-acpi-0"),
+        "hid" => {
+            // bus:vendor:product.id
+            if let Some((ids, addr)) = device_name.rsplit_once('.')
+                && let Some(bus) = ids.split(':').next().and_then(parse_hex)
+                && let Some(addr) = parse_hex(addr)
+            {
+                return format!("{prefix}-hid-{bus}-{addr:x}");
+            }
+        }
+        "scsi" => {
+            // host:channel:id:lun
+            let parts = device_name
+                .split(':')
+                .map(|part| part.parse::<u32>().ok())
+                .collect::<Option<Vec<_>>>();
+
+            if let Some([host, _, _, lun]) = parts.as_deref() {
+                return format!("{prefix}-scsi-{host}-{lun:x}");
+            }
+        }
+        _ => {}
+    }
+
+    format!("{prefix}-virtual-0")
+}
+
+struct Chip {
+    path: PathBuf,
+    id: String,
+    name: String,
+}
+
+struct HInfo {
+    name: String,
+    hardware_id: String,
+    info: String,
+}
+
+/// Build the infos of a sub feature, and remember its legacy id.
+fn get_infos(hardware: &mut Hardware, chip: &Chip, feature: &str, sub_feature: &str) -> HInfo {
+    // libsensors fall back to the feature name when there is no label
+    let label = read_string(&chip.path.join(format!("{feature}_label")))
+        .unwrap_or_else(|_| feature.to_owned());
+
+    let hardware_id = format!("{}/{sub_feature}", chip.id);
+    hardware.add_legacy_id(format!("{label}-{}-{sub_feature}", chip.name), &hardware_id);
+
+    HInfo {
+        name: format!("{label} {}", chip.name),
+        hardware_id,
+        info: format!(
+            "chip path: {}\nchip name: {}\nlabel: {}\nfeature: {}",
+            chip.path.display(),
+            chip.name,
+            label,
+            sub_feature
+        ),
+    }
+}
+
+fn generate_hardware(root: &Path, hardware: &mut Hardware) -> Vec<InternalAttribute> {
+    let mut sensors = Vec::new();
+    let mut chip_ids = ChipIds::default();
+
+    for (_, chip_path) in numbered_entries(&root.join("class/hwmon"), "hwmon") {
+        let prefix = match read_string(&chip_path.join("name")) {
+            Ok(prefix) => prefix,
+            Err(e) => {
+                warn!("skip hwmon chip without name: {e}");
+                continue;
+            }
+        };
+        let chip = Chip {
+            id: chip_ids.get(&chip_path, &prefix),
+            name: chip_name(&chip_path, &prefix),
+            path: chip_path,
+        };
+        let chip_path = &chip.path;
+
+        for channel in channels(chip_path, "fan", "_input") {
+            let feature = format!("fan{channel}");
+            let sub_feature = format!("{feature}_input");
+            let h_info = get_infos(hardware, &chip, &feature, &sub_feature);
+            let attributes = SensorAttributes::new(chip_path, &sub_feature, 1.0);
+
+            hardware.fans.push(Arc::new(HSensor {
+                name: h_info.name,
+                hardware_id: h_info.hardware_id,
+                info: h_info.info,
+                limits: attributes.limits(chip_path, &feature),
+                kind: None,
+                internal_index: sensors.len(),
+            }));
+            sensors.push(InternalAttribute::Sensor(attributes));
+
+            if let Some((target, range)) = FanTargetAttributes::discover(chip_path, &feature) {
+                let sub_feature = format!("{feature}_target");
+                let h_info = get_infos(hardware, &chip, &feature, &sub_feature);
+
+                hardware.controls.push(Arc::new(HControl {
+                    name: h_info.name,
+                    hardware_id: h_info.hardware_id,
+                    info: h_info.info,
+                    kind: ControlKind::FanTarget,
+                    range,
+                    capabilities: Capabilities::default(),
+                    internal_index: sensors.len(),
+                }));
+                sensors.push(InternalAttribute::FanTarget(target));
+            }
+        }
+
+        for channel in channels(chip_path, "temp", "_input") {
+            let feature = format!("temp{channel}");
+            let sub_feature = format!("{feature}_input");
+            let h_info = get_infos(hardware, &chip, &feature, &sub_feature);
+            let attributes = SensorAttributes::new(chip_path, &sub_feature, 1000.0);
+
+            hardware.temps.push(Arc::new(HSensor {
+                name: h_info.name,
+                hardware_id: h_info.hardware_id,
+                info: h_info.info,
+                limits: attributes.limits(chip_path, &feature),
+                kind: None,
+                internal_index: sensors.len(),
+            }));
+            sensors.push(InternalAttribute::Sensor(attributes));
+        }
+
+        for kind in SensorKind::ALL {
+            let (prefix, unit) = hwmon_unit(kind);
+
+            for (channel, suffix) in sensor_channels(chip_path, kind) {
+                let feature = format!("{prefix}{channel}");
+                let sub_feature = format!("{feature}{suffix}");
+                let h_info = get_infos(hardware, &chip, &feature, &sub_feature);
+                let attributes = SensorAttributes::new(chip_path, &sub_feature, unit);
+
+                hardware.sensors.push(Arc::new(HSensor {
+                    name: h_info.name,
+                    hardware_id: h_info.hardware_id,
+                    info: h_info.info,
+                    limits: attributes.limits(chip_path, &feature),
+                    kind: Some(kind),
+                    internal_index: sensors.len(),
+                }));
+                sensors.push(InternalAttribute::Sensor(attributes));
+            }
+        }
+
+        for channel in channels(chip_path, "pwm", "") {
+            let feature = format!("pwm{channel}");
+            let enable = chip_path.join(format!("{feature}_enable"));
+
+            if !enable.exists() {
+                continue;
+            }
+
+            let enable_cached = match read_value::<u32>(&enable) {
+                Ok(value) => {
+                    if value == MANUAL_MODE {
+                        DEFAULT_PWM_ENABLE
+                    } else {
+                        value
+                    }
+                }
+                Err(e) => {
+                    error!("can't read value of pwm {e}");
+                    continue;
+                }
+            };
+
+            let h_info = get_infos(hardware, &chip, &feature, &feature);
+            let frequency = CachedAttribute::discover(chip_path.join(format!("{feature}_freq")));
+            let output_mode = CachedAttribute::discover(chip_path.join(format!("{feature}_mode")));
+
+            hardware.controls.push(Arc::new(HControl {
+                name: h_info.name,
+                hardware_id: h_info.hardware_id,
+                info: h_info.info,
+                kind: ControlKind::Pwm,
+                range: ControlRange::PWM,
+                capabilities: Capabilities {
+                    frequency: frequency.is_some(),
+                    output_mode: output_mode.is_some(),
+                },
+                internal_index: sensors.len(),
+            }));
+            sensors.push(InternalAttribute::Pwm(PwmAttributes {
+                io: chip_path.join(feature),
+                enable,
+                default_enable_cached: enable_cached,
+                frequency,
+                output_mode,
+                release_on_drop: true,
+            }));
+        }
+    }
+
+    sensors.extend(
+        thermal_zones(root, hardware, sensors.len())
+            .into_iter()
+            .map(InternalAttribute::Sensor),
+    );
+
+    sensors
+}
+
+/// Add the zones of the thermal class to the temps. Some laptops and ARM boards
+/// only report their useful temperatures there, and not in hwmon.
+/// Ids are built from the zone type and its occurrence among the zones of this type,
+/// because zone numbers can change between boots.
+/// The kernel exposes all the zones of a type in a single hwmon chip, registered
+/// under the first of them: these zones are already read as hwmon sensors.
+pub(crate) fn thermal_zones(
+    root: &Path,
+    hardware: &mut Hardware,
+    first_index: usize,
+) -> Vec<SensorAttributes> {
+    let mut sensors = Vec::new();
+    let mut types_count = HashMap::<String, usize>::new();
+
+    let zones = numbered_entries(&root.join("class/thermal"), "thermal_zone")
+        .into_iter()
+        .filter_map(
+            |(_, zone_path)| match read_string(&zone_path.join("type")) {
+                Ok(zone_type) => Some((zone_path, zone_type)),
+                Err(e) => {
+                    warn!("skip thermal zone without type: {e}");
+                    None
+                }
+            },
+        )
+        .collect::<Vec<_>>();
+
+    let hwmon_types = zones
+        .iter()
+        .filter(|(zone_path, _)| !numbered_entries(zone_path, "hwmon").is_empty())
+        .map(|(_, zone_type)| zone_type.clone())
+        .collect::<HashSet<_>>();
+
+    for (zone_path, zone_type) in zones {
+        let io = zone_path.join("temp");
+        if !io.exists() || hwmon_types.contains(&zone_type) {
+            continue;
+        }
+
+        // several zones can have the same type, like acpitz
+        let count = types_count.entry(zone_type.clone()).or_default();
+        let name = match *count {
+            0 => zone_type.clone(),
+            n => format!("{zone_type} {n}"),
+        };
+        let hardware_id = format!("{zone_type}-thermal-{count}");
+        *count += 1;
+
+        hardware.temps.push(Arc::new(HSensor {
+            name,
+            hardware_id,
+            info: format!(
+                "thermal zone path: {}\ntype: {}",
+                zone_path.display(),
+                zone_type
+            ),
+            limits: trip_points(&zone_path),
+            kind: None,
+            internal_index: first_index + sensors.len(),
+        }));
+        sensors.push(SensorAttributes {
+            io,
+            divisor: 1000.0,
+        });
+    }
+
+    sensors
+}
+
+/// The `hot` and `critical` trip points of a thermal zone, as max and crit.
+fn trip_points(zone_path: &Path) -> SensorLimits {
+    let mut limits = SensorLimits::default();
+
+    for trip in 0.. {
+        let Ok(trip_type) = read_string(&zone_path.join(format!("trip_point_{trip}_type"))) else {
+            break;
+        };
+        let temp = read_value::<f64>(&zone_path.join(format!("trip_point_{trip}_temp")))
+            .ok()
+            .map(|temp| temp / 1000.0);
+
+        match trip_type.as_str() {
+            "hot" => limits.max = temp,
+            "critical" => limits.crit = temp,
+            _ => {}
+        }
+    }
+
+    limits
+}
+
+impl SysfsBridge {
+    /// Construct a bridge reading the sysfs tree mounted at `root`,
+    /// instead of `/sys`. Used to test against a fixture directory.
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        let mut hardware = Hardware::default();
+        let root = root.into();
+        let sensors = generate_hardware(&root, &mut hardware);
+
+        Self {
+            root,
+            sensors,
+            hardware,
+        }
+    }
+
+    fn sensor(&self, sensor: &HSensor) -> Result<&SensorAttributes> {
+        match self.sensors.get(sensor.internal_index) {
+            Some(InternalAttribute::Sensor(attributes)) => Ok(attributes),
+            _ => Err(SysfsError::NotFound(sensor.hardware_id.clone())),
+        }
+    }
+
+    fn control(&self, control: &HControl) -> Result<&InternalAttribute> {
+        match self.sensors.get(control.internal_index) {
+            Some(attribute @ (InternalAttribute::Pwm(_) | InternalAttribute::FanTarget(_))) => {
+                Ok(attribute)
+            }
+            _ => Err(SysfsError::NotFound(control.hardware_id.clone())),
+        }
+    }
+
+    fn pwm_setting(
+        &self,
+        control: &HControl,
+        setting: impl Fn(&PwmAttributes) -> &Option<CachedAttribute>,
+        name: &'static str,
+    ) -> crate::Result<PathBuf> {
+        match self.control(control)? {
+            InternalAttribute::Pwm(pwm) => setting(pwm).as_ref().map(|a| a.path.clone()),
+            _ => None,
+        }
+        .ok_or_else(|| HardwareError::Unsupported(name, control.hardware_id.clone()))
+    }
+}
+
+impl HardwareBridge for SysfsBridge {
+    fn new() -> crate::Result<Self> {
+        Ok(Self::with_root(DEFAULT_SYSFS_ROOT))
+    }
+
+    fn hardware(&self) -> &Hardware {
+        &self.hardware
+    }
+
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<SensorValue> {
+        Ok(self.sensor(sensor)?.get_value()?)
+    }
+
+    fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
+        match self.control(control)? {
+            InternalAttribute::Pwm(pwm) => {
+                let value = read_value::<f64>(&pwm.io)?;
+                Ok(value.round() as Value)
+            }
+            InternalAttribute::FanTarget(target) => Ok(target.get_value()?),
+            InternalAttribute::Sensor(_) => unreachable!(),
+        }
+    }
+
+    fn set_value(&mut self, control: &HControl, value: Value) -> crate::Result<()> {
+        match self.control(control)? {
+            InternalAttribute::Pwm(pwm) => write_value(&pwm.io, value)?,
+            InternalAttribute::FanTarget(target) => target.set_value(value)?,
+            InternalAttribute::Sensor(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn set_mode(&mut self, control: &HControl, mode: &Mode) -> crate::Result<()> {
+        match self.control(control)? {
+            InternalAttribute::Pwm(pwm) => {
+                if *mode == Mode::Auto {
+                    pwm.restore_settings()?;
+                }
+                let value = match mode {
+                    Mode::Auto => pwm.default_enable_cached,
+                    Mode::Manual => MANUAL_MODE,
+                    Mode::Specific(value) => {
+                        u32::try_from(*value).map_err(|_| SysfsError::PwmMode(*value))?
+                    }
+                };
+                write_value(&pwm.enable, value)?;
+            }
+            InternalAttribute::FanTarget(target) => target.set_mode(mode)?,
+            InternalAttribute::Sensor(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn get_frequency(&mut self, control: &HControl) -> crate::Result<Value> {
+        let path = &self.pwm_setting(control, |pwm| &pwm.frequency, "pwm frequency")?;
+        Ok(read_value(path)?)
+    }
+
+    fn set_frequency(&mut self, control: &HControl, frequency: Value) -> crate::Result<()> {
+        let path = &self.pwm_setting(control, |pwm| &pwm.frequency, "pwm frequency")?;
+        Ok(write_value(path, frequency)?)
+    }
+
+    fn get_output_mode(&mut self, control: &HControl) -> crate::Result<OutputMode> {
+        let path = &self.pwm_setting(control, |pwm| &pwm.output_mode, "output mode")?;
+        Ok(OutputMode::from_raw(read_value(path)?))
+    }
+
+    fn set_output_mode(&mut self, control: &HControl, mode: OutputMode) -> crate::Result<()> {
+        let path = &self.pwm_setting(control, |pwm| &pwm.output_mode, "output mode")?;
+        Ok(write_value(path, mode.to_raw())?)
+    }
+
+    fn rescan(&mut self) -> crate::Result<HardwareDiff> {
+        let mut hardware = Hardware::default();
+        let mut sensors = generate_hardware(&self.root, &mut hardware);
+
+        let previous = self
+            .hardware
+            .controls
+            .iter()
+            .map(|h| (h.hardware_id.as_str(), h.internal_index))
+            .collect::<HashMap<_, _>>();
+
+        for control in &hardware.controls {
+            let Some(&index) = previous.get(control.hardware_id.as_str()) else {
+                continue;
+            };
+            match (
+                &mut sensors[control.internal_index],
+                &mut self.sensors[index],
+            ) {
+                (InternalAttribute::Pwm(new), InternalAttribute::Pwm(old)) => new.inherit(old),
+                (InternalAttribute::FanTarget(new), InternalAttribute::FanTarget(old)) => {
+                    new.inherit(old)
+                }
+                _ => {}
+            }
+        }
+
+        let diff = self.hardware.diff(&hardware);
+        // the controls which disappeared are given back to the hardware when dropped
+        self.sensors = sensors;
+        self.hardware = hardware;
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use super::SysfsBridge;
+    use crate::{
+        ControlKind, ControlRange, HardwareBridge, Mode, OutputMode, SensorKind, SensorLimits,
+    };
+
+    fn fixture(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("fan-control-sysfs-{name}"));
+        let _ = fs::remove_dir_all(&root);
+
+        let write = |path: &str, content: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+
+        write("class/hwmon/hwmon0/name", "nct6775\n");
+        write("class/hwmon/hwmon0/temp1_input", "45900\n");
+        write("class/hwmon/hwmon0/temp1_label", "SYSTIN\n");
+        write("class/hwmon/hwmon0/temp1_max", "80000\n");
+        write("class/hwmon/hwmon0/temp1_crit", "100000\n");
+        write("class/hwmon/hwmon0/fan1_min", "300\n");
+        write("class/hwmon/hwmon0/temp2_input", "30000\n");
+        write("class/hwmon/hwmon0/fan1_input", "1200\n");
+        write("class/hwmon/hwmon0/pwm1", "255\n");
+        write("class/hwmon/hwmon0/pwm1_enable", "5\n");
+        write("class/hwmon/hwmon0/pwm1_freq", "25000\n");
+        write("class/hwmon/hwmon0/pwm1_mode", "1\n");
+        // no pwm2_enable: can't be controlled
+        write("class/hwmon/hwmon0/pwm2", "128\n");
+
+        write("class/hwmon/hwmon10/name", "k10temp\n");
+        write("class/hwmon/hwmon10/temp1_input", "60000\n");
+
+        root
+    }
+
+    #[test]
+    fn test_discovery() {
+        let root = fixture("discovery");
+        let bridge = SysfsBridge::with_root(&root);
+        let hardware = bridge.hardware();
+
+        let temps = hardware
+            .temps
+            .iter()
+            .map(|t| t.hardware_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            temps,
+            [
+                "nct6775/virtual/temp1_input",
+                "nct6775/virtual/temp2_input",
+                "k10temp/virtual/temp1_input",
+            ]
+        );
+        assert_eq!(hardware.fans.len(), 1);
+        assert_eq!(hardware.controls.len(), 1);
+        assert_eq!(hardware.controls[0].hardware_id, "nct6775/virtual/pwm1");
+
+        drop(bridge);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_ids() {
+        let root = fixture("ids");
+
+        // two disks behind the same scsi host
+        for (hwmon, device) in [("hwmon20", "0:0:0:0"), ("hwmon21", "0:0:1:0")] {
+            let device_path = root.join("devices/scsi").join(device);
+            fs::create_dir_all(&device_path).unwrap();
+            std::os::unix::fs::symlink(root.join("bus/scsi"), device_path.join("subsystem"))
+                .unwrap();
+
+            let chip_path = root.join("class/hwmon").join(hwmon);
+            fs::create_dir_all(&chip_path).unwrap();
+            std::os::unix::fs::symlink(&device_path, chip_path.join("device")).unwrap();
+            fs::write(chip_path.join("name"), "drivetemp\n").unwrap();
+            fs::write(chip_path.join("temp1_input"), "35000\n").unwrap();
+        }
+
+        // a virtual chip, registered by a thermal zone
+        let chip_path = root.join("devices/virtual/thermal/thermal_zone3/hwmon7");
+        fs::create_dir_all(&chip_path).unwrap();
+        fs::write(chip_path.join("name"), "acpitz\n").unwrap();
+        fs::write(chip_path.join("temp1_input"), "40000\n").unwrap();
+        std::os::unix::fs::symlink(&chip_path, root.join("class/hwmon/hwmon7")).unwrap();
+
+        // a second nct6775, without bus device either
+        fs::create_dir_all(root.join("class/hwmon/hwmon8")).unwrap();
+        fs::write(root.join("class/hwmon/hwmon8/name"), "nct6775\n").unwrap();
+        fs::write(root.join("class/hwmon/hwmon8/temp1_input"), "40000\n").unwrap();
+
+        let bridge = SysfsBridge::with_root(&root);
+        let hardware = bridge.hardware();
+        let disks = hardware
+            .temps
+            .iter()
+            .map(|t| t.hardware_id.as_str())
+            .filter(|id| id.starts_with("drivetemp"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            disks,
+            [
+                "drivetemp/scsi:0:0:0:0/temp1_input",
+                "drivetemp/scsi:0:0:1:0/temp1_input"
+            ]
+        );
+        assert!(
+            hardware
+                .temps
+                .iter()
+                .any(|t| t.hardware_id == "acpitz/virtual/temp1_input")
+        );
+        assert!(
+            hardware
+                .temps
+                .iter()
+                .any(|t| t.hardware_id == "nct6775/virtual#2/temp1_input")
+        );
+
+        // the id of previous versions was the same for both
+        assert_eq!(
+            hardware.legacy_ids["temp1-drivetemp-scsi-0-0-temp1_input"],
+            "drivetemp/scsi:0:0:0:0/temp1_input"
+        );
+        assert_eq!(
+            hardware.legacy_ids["SYSTIN-nct6775-virtual-0-temp1_input"],
+            "nct6775/virtual/temp1_input"
+        );
+
+        drop(bridge);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_renumbered() {
+        let root = fixture("renumbered");
+        let ids = |root: &Path| {
+            let bridge = SysfsBridge::with_root(root);
+            let hardware = bridge.hardware();
+            let mut ids = hardware.ids().cloned().collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+        let before = ids(&root);
+
+        // the kernel numbers the chips in the order their driver registers them
+        let hwmon = root.join("class/hwmon");
+        fs::rename(hwmon.join("hwmon0"), hwmon.join("hwmon4")).unwrap();
+        fs::rename(hwmon.join("hwmon10"), hwmon.join("hwmon2")).unwrap();
+        assert_eq!(ids(&root), before);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_values() {
+        let root = fixture("values");
+        let mut bridge = SysfsBridge::with_root(&root);
+        let temp = bridge.hardware().temps[0].clone();
+        let fan = bridge.hardware().fans[0].clone();
+        let control = bridge.hardware().controls[0].clone();
+        let pwm_path = root.join("class/hwmon/hwmon0/pwm1");
+        let enable_path = root.join("class/hwmon/hwmon0/pwm1_enable");
+
+        assert_eq!(bridge.get_sensor_value(&temp).unwrap(), 45.9);
+        assert_eq!(bridge.get_sensor_value(&fan).unwrap(), 1200.0);
+
+        assert_eq!(
+            temp.limits,
+            SensorLimits {
+                min: None,
+                max: Some(80.0),
+                crit: Some(100.0),
+            }
+        );
+        assert_eq!(fan.limits.min, Some(300.0));
+        assert_eq!(control.range, ControlRange::PWM);
+        assert_eq!(bridge.get_control_value(&control).unwrap(), 255);
+
+        assert!(bridge.set_mode(&control, &Mode::Specific(-1)).is_err());
+        assert_eq!(fs::read_to_string(&enable_path).unwrap(), "5\n");
+        bridge.set_mode(&control, &Mode::Manual).unwrap();
+        assert_eq!(fs::read_to_string(&enable_path).unwrap(), "1");
+
+        bridge.set_value(&control, 128).unwrap();
+        assert_eq!(fs::read_to_string(&pwm_path).unwrap(), "128");
+        assert_eq!(bridge.get_control_value(&control).unwrap(), 128);
+
+        assert!(control.capabilities.frequency && control.capabilities.output_mode);
+        bridge.set_frequency(&control, 22500).unwrap();
+        bridge.set_output_mode(&control, OutputMode::Dc).unwrap();
+        assert_eq!(bridge.get_frequency(&control).unwrap(), 22500);
+        assert_eq!(bridge.get_output_mode(&control).unwrap(), OutputMode::Dc);
+
+        // the original pwm_enable is restored when the bridge is dropped
+        drop(bridge);
+        assert_eq!(fs::read_to_string(&enable_path).unwrap(), "5");
+        let read = |file| fs::read_to_string(root.join("class/hwmon/hwmon0").join(file)).unwrap();
+        assert_eq!(read("pwm1_freq"), "25000");
+        assert_eq!(read("pwm1_mode"), "1");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_rescan() {
+        let root = fixture("rescan");
+        let mut bridge = SysfsBridge::with_root(&root);
+        let control = bridge.hardware().controls[0].clone();
+        let enable_path = root.join("class/hwmon/hwmon0/pwm1_enable");
+        bridge.set_mode(&control, &Mode::Manual).unwrap();
+
+        // a chip loaded late
+        fs::create_dir_all(root.join("class/hwmon/hwmon2")).unwrap();
+        fs::write(root.join("class/hwmon/hwmon2/name"), "it87\n").unwrap();
+        fs::write(root.join("class/hwmon/hwmon2/temp1_input"), "40000\n").unwrap();
+        fs::remove_dir_all(root.join("class/hwmon/hwmon10")).unwrap();
+
+        let diff = bridge.rescan().unwrap();
+        assert_eq!(diff.added, ["it87/virtual/temp1_input"]);
+        assert_eq!(diff.removed, ["k10temp/virtual/temp1_input"]);
+
+        // the pwm found again is still ours
+        assert_eq!(fs::read_to_string(&enable_path).unwrap(), "1");
+        let control = bridge.hardware().controls[0].clone();
+        let temp = bridge.hardware().temps[2].clone();
+        assert_eq!(bridge.get_sensor_value(&temp).unwrap(), 40.0);
+        bridge.set_value(&control, 100).unwrap();
+
+        // and is still restored to the state it had at startup
+        drop(bridge);
+        assert_eq!(fs::read_to_string(&enable_path).unwrap(), "5");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_fan_target() {
+        let root = fixture("target");
+        let chip_path = root.join("class/hwmon/hwmon1");
+        fs::create_dir_all(&chip_path).unwrap();
+        fs::write(chip_path.join("name"), "applesmc\n").unwrap();
+        fs::write(chip_path.join("fan1_input"), "2000\n").unwrap();
+        fs::write(chip_path.join("fan1_label"), "Exhaust\n").unwrap();
+        fs::write(chip_path.join("fan1_min"), "1200\n").unwrap();
+        fs::write(chip_path.join("fan1_max"), "6200\n").unwrap();
+        fs::write(chip_path.join("fan1_target"), "2000\n").unwrap();
+        fs::write(chip_path.join("fan1_manual"), "0\n").unwrap();
+        let read = |attribute: &str| fs::read_to_string(chip_path.join(attribute)).unwrap();
+
+        let bridge = SysfsBridge::with_root(&root);
+        let control = bridge
+            .hardware()
+            .controls
+            .iter()
+            .find(|control| control.kind == ControlKind::FanTarget)
+            .unwrap()
+            .clone();
+        assert_eq!(control.hardware_id, "applesmc/virtual/fan1_target");
+        assert_eq!(
+            control.range,
+            ControlRange {
+                min: 1200,
+                max: 6200,
+                step: 1
+            }
+        );
+
+        // nothing is written back to a fan we didn't touch
+        fs::write(chip_path.join("fan1_target"), "2500\n").unwrap();
+        drop(bridge);
+        assert_eq!(read("fan1_target"), "2500\n");
+        fs::write(chip_path.join("fan1_target"), "2000\n").unwrap();
+
+        let mut bridge = SysfsBridge::with_root(&root);
+        assert!(bridge.set_mode(&control, &Mode::Specific(2)).is_err());
+
+        bridge.set_mode(&control, &Mode::Manual).unwrap();
+        assert_eq!(read("fan1_manual"), "1");
+        bridge.set_value(&control, 3000).unwrap();
+        assert_eq!(bridge.get_control_value(&control).unwrap(), 3000);
+
+        // the original target is restored in auto, and the firmware takes the fan back
+        bridge.set_mode(&control, &Mode::Auto).unwrap();
+        assert_eq!(bridge.get_control_value(&control).unwrap(), 2000);
+        assert_eq!(read("fan1_manual"), "0");
+
+        bridge.set_mode(&control, &Mode::Manual).unwrap();
+        bridge.set_value(&control, 4000).unwrap();
+        drop(bridge);
+        assert_eq!(read("fan1_target"), "2000");
+        assert_eq!(read("fan1_manual"), "0");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_other_sensors() {
+        let root = fixture("other");
+        let chip_path = root.join("class/hwmon/hwmon1");
+        fs::create_dir_all(&chip_path).unwrap();
+        fs::write(chip_path.join("name"), "amdgpu\n").unwrap();
+        fs::write(chip_path.join("in0_input"), "1205\n").unwrap();
+        fs::write(chip_path.join("in0_label"), "vddgfx\n").unwrap();
+        fs::write(chip_path.join("in0_max"), "1500\n").unwrap();
+        fs::write(chip_path.join("curr1_input"), "2500\n").unwrap();
+        fs::write(chip_path.join("power1_average"), "45250000\n").unwrap();
+        fs::write(chip_path.join("power1_cap"), "200000000\n").unwrap();
+        fs::write(chip_path.join("energy1_input"), "3600000000\n").unwrap();
+        fs::write(chip_path.join("humidity1_input"), "45500\n").unwrap();
+
+        let mut bridge = SysfsBridge::with_root(&root);
+        let sensors = bridge.hardware().sensors.clone();
+        assert_eq!(
+            sensors
+                .iter()
+                .map(|sensor| (sensor.hardware_id.as_str(), sensor.kind.unwrap()))
+                .collect::<Vec<_>>(),
+            [
+                ("amdgpu/virtual/in0_input", SensorKind::Voltage),
+                ("amdgpu/virtual/curr1_input", SensorKind::Current),
+                ("amdgpu/virtual/power1_average", SensorKind::Power),
+                ("amdgpu/virtual/energy1_input", SensorKind::Energy),
+                ("amdgpu/virtual/humidity1_input", SensorKind::Humidity),
+            ]
+        );
+        assert_eq!(sensors[0].name, "vddgfx amdgpu-virtual-0");
+        assert_eq!(sensors[0].limits.max, Some(1.5));
+
+        let values = sensors
+            .iter()
+            .map(|sensor| bridge.get_sensor_value(sensor).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(values, [1.205, 2.5, 45.25, 3600.0, 45.5]);
+
+        // fans and temps are not mixed with them
+        assert!(
+            bridge
+                .hardware()
+                .temps
+                .iter()
+                .all(|temp| temp.kind.is_none())
+        );
+        assert_eq!(bridge.hardware().temps.len(), 3);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_thermal_zones() {
+        let root = fixture("thermal");
+        let write = |path: &str, content: &str| {
+            let path = root.join("class/thermal").join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write("thermal_zone0/type", "acpitz\n");
+        write("thermal_zone0/temp", "48000\n");
+        write("thermal_zone0/trip_point_0_type", "passive\n");
+        write("thermal_zone0/trip_point_0_temp", "90000\n");
+        write("thermal_zone0/trip_point_1_type", "critical\n");
+        write("thermal_zone0/trip_point_1_temp", "105000\n");
+        write("thermal_zone1/type", "x86_pkg_temp\n");
+        write("thermal_zone1/temp", "52500\n");
+        write("thermal_zone2/type", "acpitz\n");
+        write("thermal_zone2/temp", "30000\n");
+        // already read from its hwmon chip
+        write("thermal_zone3/type", "iwlwifi_1\n");
+        write("thermal_zone3/temp", "41000\n");
+        write("thermal_zone3/hwmon4/name", "iwlwifi_1\n");
+        write("thermal_zone3/hwmon4/temp1_input", "41000\n");
+        // cooling devices live in the same class
+        write("cooling_device0/type", "Processor\n");
+
+        let mut bridge = SysfsBridge::with_root(&root);
+        let zones = bridge.hardware().temps[3..].to_vec();
+
+        let ids = zones
+            .iter()
+            .map(|t| (t.name.as_str(), t.hardware_id.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [
+                ("acpitz", "acpitz-thermal-0"),
+                ("x86_pkg_temp", "x86_pkg_temp-thermal-0"),
+                ("acpitz 1", "acpitz-thermal-1"),
+            ]
+        );
+
+        assert!(zones[0].info.ends_with("/thermal_zone0\ntype: acpitz"));
+        assert_eq!(zones[0].limits.crit, Some(105.0));
+        assert_eq!(zones[0].limits.max, None);
+
+        assert_eq!(bridge.get_sensor_value(&zones[1]).unwrap(), 52.5);
+        assert_eq!(bridge.get_sensor_value(&zones[2]).unwrap(), 30.0);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     net::TcpStream,
     path::PathBuf,
     process::{self},
-    rc::Rc,
+    sync::Arc,
     thread,
     time::Duration,
 };
@@ -11,7 +11,10 @@ use std::{
 use serde::Deserialize;
 use thiserror::Error;
 
-use crate::{HControl, HSensor, Hardware, HardwareBridge, Mode, Value};
+use crate::{
+    Capabilities, ControlKind, ControlRange, HControl, HSensor, Hardware, HardwareBridge, Mode,
+    SensorValue, Value,
+};
 
 use self::packet::{Packet, command::Command, i32::I32};
 
@@ -163,22 +166,30 @@ fn read_hardware(stream: &TcpStream) -> Result<Hardware> {
 
     for base_hardware in base_hardware_list {
         match base_hardware.hardware_type {
-            HardwareType::Control => hardware.controls.push(Rc::new(HControl {
+            HardwareType::Control => hardware.controls.push(Arc::new(HControl {
                 name: base_hardware.name,
                 hardware_id: base_hardware.id,
                 info: String::new(),
+                kind: ControlKind::Pwm,
+                // LibreHardwareMonitor take a percentage
+                range: ControlRange::default(),
+                capabilities: Capabilities::default(),
                 internal_index: base_hardware.index,
             })),
-            HardwareType::Fan => hardware.fans.push(Rc::new(HSensor {
+            HardwareType::Fan => hardware.fans.push(Arc::new(HSensor {
                 name: base_hardware.name,
                 hardware_id: base_hardware.id,
                 info: String::new(),
+                limits: Default::default(),
+                kind: None,
                 internal_index: base_hardware.index,
             })),
-            HardwareType::Temp => hardware.temps.push(Rc::new(HSensor {
+            HardwareType::Temp => hardware.temps.push(Arc::new(HSensor {
                 name: base_hardware.name,
                 hardware_id: base_hardware.id,
                 info: String::new(),
+                limits: Default::default(),
+                kind: None,
                 internal_index: base_hardware.index,
             })),
         }
@@ -315,12 +326,12 @@ impl HardwareBridge for WindowsBridge {
         &self.hardware
     }
 
-    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<Value> {
+    fn get_sensor_value(&mut self, sensor: &HSensor) -> crate::Result<SensorValue> {
         self.send(Command::GetValue)?;
         self.send(I32::from(sensor.internal_index))?;
 
         let value = self.read::<I32>()?;
-        Ok(value.0)
+        Ok(SensorValue::from(value.0))
     }
 
     fn get_control_value(&mut self, control: &HControl) -> crate::Result<Value> {
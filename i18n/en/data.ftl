@@ -2,15 +2,24 @@ none = None
 average = Average
 max = Max
 min = Min
+exponential = Exponential
+percentile = Percentile
+sum = Sum
+weighted_sum = Weighted sum
 
 default_control = Control
 default_fan = Fan
 default_temp = Temp
+default_sensor = Sensor
+default_command_temp = Command temp
 default_custom_temp = Custom temp
+default_smooth = Smooth
 default_graph = Graph
 default_flat = Flat
 default_linear = Linear
 default_target = Target
+default_pid = PID
+default_mix = Mix
 
 system_theme = System
 dark_theme = Dark
@@ -21,4 +30,16 @@ hight_contrast_light_theme = HighContrastLight
 value_celsius = { $value } °C
 value_percentage = { $value } %
 value_rpm = { $value } RPM
-no_value = No value
\ No newline at end of file
+value_volt = { $value } V
+value_ampere = { $value } A
+value_watt = { $value } W
+value_joule = { $value } J
+value_humidity = { $value } %RH
+no_value = No value
+limit_min = min { $value }
+limit_max = max { $value }
+limit_crit = crit { $value }
+calibration_max = full speed
+calibration_stop = stopping the fan
+calibration_start = starting the fan
+calibration_lower = slowing down
\ No newline at end of file
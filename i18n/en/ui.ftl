@@ -13,6 +13,16 @@ idle_temp = idle temp
 idle_speed = idle speed
 load_temp = load temp
 load_speed = load speed
+deadband = deadband
+falling_offset = falling offset
+window = window
+percentile = percentile
+setpoint = setpoint
+kp = Kp
+ki = Ki
+kd = Kd
+weight = weight
+behavior_selection = Behavior selection
 launch_graph_window = Add coordinates
 config_saved = Configuration successfully saved
 repository = Repository
@@ -31,7 +41,9 @@ start_minimized = Start minimized
 add_item = Add an item
 add_fan = Monitor a fan sensor
 add_temp = Monitor a temp sensor
+add_sensor = Monitor a voltage, current, power, energy or humidity sensor
 add_custom_temp = Define logic between values (Max, Averrage, ...)
+add_smooth = Smooth a temp over a time window, to ignore short spikes
 add_control = Assigns a certain behavior to a certain hardware component
 add_flat = Returns a fixed value
 add_linear = Take 5 variables:
@@ -48,12 +60,26 @@ add_target = Take 5 variables:
     If the sensor > trigger temperature, trigger speed is set
     until this sensor is < ideal temperature
 add_graph = Graph
+add_pid = Hold a sensor at a setpoint temperature, with a PID controller
+add_mix = Combine several behaviors, like the max of a CPU and a GPU curve
 
 # Config
 no_config = No configuration
 rename_config = Rename configuration
 config_name = Configuration name
 save_config = Save this configuration
+rescan_hardware = Look for new hardware
+hardware_rescanned = Hardware rescanned: { $added } added, { $removed } removed
+calibrate = Calibrate
+detect_fan = Detect
+detecting_fan = Detecting the fan of this control
+fan_detected = { $control } drives { $fan }
+no_fan_detected = No fan reacted to { $control }
+abort_calibration = Abort
+calibrating = Calibrating: { $phase } ({ $value })
+calibrated = Spins from { $stay } to { $max }, starts at { $start }
+calibration_done = { $name } calibrated
+calibration_failed = Calibration of { $name } failed: { $error }
 delete_config = Delete configuration
 create_config = Create configuration
 create = Create
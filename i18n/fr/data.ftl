@@ -2,15 +2,24 @@ none = Aucun
 average = Moyenne
 max = Max
 min = Min
+exponential = Exponentielle
+percentile = Centile
+sum = Somme
+weighted_sum = Somme pondérée
 
 default_control = Control
 default_fan = Ventilateur
 default_temp = Temp
+default_sensor = Capteur
+default_command_temp = Temp commande
 default_custom_temp = Temp custom
+default_smooth = Lissage
 default_graph = Graph
 default_flat = Plat
 default_linear = Lineaire
 default_target = Cible
+default_pid = PID
+default_mix = Mélange
 
 system_theme = Système
 dark_theme = Sombre
@@ -19,4 +28,16 @@ light_theme = Clair
 value_celsius = { $value } °C
 value_percentage = { $value } %
 value_rpm = { $value } RPM
-no_value = Aucune valeur
\ No newline at end of file
+value_volt = { $value } V
+value_ampere = { $value } A
+value_watt = { $value } W
+value_joule = { $value } J
+value_humidity = { $value } %RH
+no_value = Aucune valeur
+limit_min = min { $value }
+limit_max = max { $value }
+limit_crit = crit { $value }
+calibration_max = pleine vitesse
+calibration_stop = arrêt du ventilateur
+calibration_start = démarrage du ventilateur
+calibration_lower = ralentissement
\ No newline at end of file
@@ -16,6 +16,16 @@ idle_temp = idle temp
 idle_speed = idle speed
 load_temp = load temp
 load_speed = load speed
+deadband = zone morte
+falling_offset = décalage en baisse
+window = fenêtre
+percentile = centile
+setpoint = consigne
+kp = Kp
+ki = Ki
+kd = Kd
+weight = poids
+behavior_selection = Sélection du comportement
 launch_graph_window = Ajout de coordonnées
 config_saved = Configuration enregistrée avec succès
 
@@ -23,7 +33,9 @@ config_saved = Configuration enregistrée avec succès
 add_item = Ajouter un item
 add_fan = Monitore un ventilateur
 add_temp = Monitore un capteur de temperature
+add_sensor = Monitore un capteur de tension, courant, puissance, énergie ou humidité
 add_custom_temp = Defini une logique entre des valeurs (Max, Moyenne, ...)
+add_smooth = Lisse une temp sur une fenêtre de temps, pour ignorer les pics courts
 add_control = Applique un certain comportement a un ventilateur
 add_flat = Retourne une valeur fixe
 add_linear = Prend 5 variables :
@@ -40,10 +52,24 @@ add_target = Prend 5 variables :
     Si le capteur > température de déclenchement, la vitesse de déclenchement est définie
     jusqu'à ce que ce capteur < température idéale
 add_graph = Graphe
+add_pid = Maintient un capteur à une température de consigne, avec un régulateur PID
+add_mix = Combine plusieurs comportements, comme le max d'une courbe CPU et d'une courbe GPU
 
 # Config
 config_name = Nom de la configuration
 save_config = Enregistrer/renommer cette configuration
+rescan_hardware = Rechercher du nouveau matériel
+hardware_rescanned = Matériel recherché : { $added } ajouté(s), { $removed } retiré(s)
+calibrate = Calibrer
+detect_fan = Détecter
+detecting_fan = Détection du ventilateur de ce contrôle
+fan_detected = { $control } pilote { $fan }
+no_fan_detected = Aucun ventilateur n'a réagi à { $control }
+abort_calibration = Annuler
+calibrating = Calibration : { $phase } ({ $value })
+calibrated = Tourne de { $stay } à { $max }, démarre à { $start }
+calibration_done = { $name } calibré
+calibration_failed = La calibration de { $name } a échoué : { $error }
 delete_config = Supprimer la configuration
 create_config = Créer une configuration
 
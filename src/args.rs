@@ -67,4 +67,37 @@ pub struct Args {
         help = "Write hardware file in the config dir"
     )]
     pub serialize_hardware: bool,
+
+    #[arg(
+        long = "record",
+        value_hint = ValueHint::FilePath,
+        value_names = ["PATH"],
+        conflicts_with = "replay",
+        help = "Record every hardware call to a file, to replay it later"
+    )]
+    pub record: Option<PathBuf>,
+
+    #[arg(
+        long = "replay",
+        value_hint = ValueHint::FilePath,
+        value_names = ["PATH"],
+        help = "Replay a recording instead of using the real hardware"
+    )]
+    pub replay: Option<PathBuf>,
+
+    #[arg(
+        long = "agent",
+        value_names = ["ADDRESS"],
+        conflicts_with_all = ["remote", "replay"],
+        help = "Serve the hardware to a remote fan-control on this address, like 0.0.0.0:7878. There is no authentication"
+    )]
+    pub agent: Option<String>,
+
+    #[arg(
+        long = "remote",
+        value_names = ["ADDRESS"],
+        conflicts_with = "replay",
+        help = "Drive the hardware of an agent instead of the local one"
+    )]
+    pub remote: Option<String>,
 }
@@ -8,12 +8,19 @@ use std::{
 };
 
 use crossterm::event::{self, Event, KeyCode, KeyEvent};
-use data::{AppState, settings::Settings};
+use data::{
+    AppState,
+    config::Config,
+    engine::{Command, Event as EngineEvent, Frontend},
+    id::Id,
+    node::NodeType,
+    settings::Settings,
+};
 use hardware::HardwareBridge;
 
-pub fn run_cli<H: HardwareBridge>(mut app_state: AppState<H>) {
+pub fn run_cli<H: HardwareBridge + 'static>(app_state: AppState<H>) {
     let current_config = match &app_state.dir_manager.settings().current_config {
-        Some(current_config) => current_config,
+        Some(current_config) => current_config.clone(),
         None => {
             println!("There is no active configuration defined");
             println!(
@@ -27,33 +34,74 @@ pub fn run_cli<H: HardwareBridge>(mut app_state: AppState<H>) {
 
     let (tx, rx) = mpsc::channel::<UserAction>();
     start_listening(tx);
-    display_info(app_state.dir_manager.settings(), current_config);
+    display_info(app_state.dir_manager.settings(), &current_config);
 
-    loop {
-        if let Err(e) = app_state.bridge.update() {
-            error!("{e}");
-            break;
-        }
+    let mut frontend = Frontend::new(app_state, false);
 
-        if let Err(e) = app_state.update.optimized(
-            &mut app_state.app_graph.nodes,
-            &app_state.app_graph.root_nodes,
-            &mut app_state.bridge,
-            app_state.dir_manager.settings().inactive,
-        ) {
-            error!("{e}");
+    loop {
+        for event in frontend.receive() {
+            match event {
+                EngineEvent::Rescanned { diff, .. } => println!(
+                    "hardware rescanned: {} added, {} removed",
+                    diff.added.len(),
+                    diff.removed.len()
+                ),
+                EngineEvent::CalibrationStarted { name, .. } => {
+                    println!("calibrating {name}, other fans are left as they are (abort: a)")
+                }
+                EngineEvent::Calibrated { name, result, .. } => match result {
+                    Ok(calibration) => {
+                        println!(
+                            "{name} calibrated: spins from {} to {}, starts at {}",
+                            calibration.min_stay, calibration.max_effective, calibration.min_start
+                        );
+                        save_config(&mut frontend, &current_config);
+                    }
+                    Err(e) => println!("can't calibrate {name}: {e}"),
+                },
+                EngineEvent::FansDetected(pairs) => {
+                    for pair in pairs {
+                        match pair.fan {
+                            Some(fan) => println!("{} drives {fan}", pair.control),
+                            None => println!("no fan reacted to {}", pair.control),
+                        }
+                    }
+                    save_config(&mut frontend, &current_config);
+                }
+            }
         }
 
-        let settings_update_delay =
-            Duration::from_millis(app_state.dir_manager.settings().update_delay);
-        let final_delay = std::cmp::max(settings_update_delay, Duration::from_millis(50));
-
-        match rx.recv_timeout(final_delay) {
+        // the engine runs on its own, only the user is waited for
+        match rx.recv_timeout(Duration::from_millis(50)) {
             Ok(action) => match action {
                 UserAction::Quit => {
                     println!("quit requested");
                     break;
                 }
+                UserAction::Calibrate => {
+                    if frontend.job.is_running() {
+                        println!("a calibration is already running");
+                        continue;
+                    }
+                    let controls = bound_controls(&frontend).collect();
+                    frontend.engine.send(Command::Calibrate(controls));
+                }
+                UserAction::DetectFans => {
+                    if frontend.job.is_running() {
+                        println!("a calibration is already running");
+                        continue;
+                    }
+                    println!("detecting the fan of each control (abort: a)");
+                    let controls = bound_controls(&frontend).collect();
+                    frontend.engine.send(Command::DetectFans(controls));
+                }
+                UserAction::AbortCalibration => {
+                    if frontend.job.is_running() {
+                        println!("calibration aborted");
+                    }
+                    frontend.engine.send(Command::AbortCalibration);
+                }
+                UserAction::Rescan => frontend.engine.send(Command::Rescan),
             },
 
             Err(RecvTimeoutError::Disconnected) => {
@@ -61,19 +109,40 @@ pub fn run_cli<H: HardwareBridge>(mut app_state: AppState<H>) {
                 break;
             }
 
-            Err(RecvTimeoutError::Timeout) => {
-                // new update cycle
-            }
+            Err(RecvTimeoutError::Timeout) => {}
         }
     }
 
-    if let Err(e) = app_state.bridge.shutdown() {
-        error!("shutdown hardware: {e}");
+    frontend.engine.stop();
+}
+
+/// Controls of the config bound to a hardware.
+fn bound_controls<H>(frontend: &Frontend<H>) -> impl Iterator<Item = Id> + '_ {
+    frontend
+        .app_graph
+        .nodes
+        .values()
+        .filter(|node| match &node.node_type {
+            NodeType::Control(control) => control.control_h.is_some(),
+            _ => false,
+        })
+        .map(|node| node.id)
+}
+
+/// Write what the calibration found in the current config.
+fn save_config<H>(frontend: &mut Frontend<H>, current_config: &str) {
+    let config = Config::from_app_graph(&frontend.app_graph);
+    if let Err(e) = frontend.dir_manager.save_config(current_config, &config) {
+        println!("can't save the config: {e}");
     }
 }
 
 enum UserAction {
     Quit,
+    Rescan,
+    Calibrate,
+    DetectFans,
+    AbortCalibration,
 }
 
 #[allow(clippy::single_match)]
@@ -91,6 +160,42 @@ fn start_listening(tx: Sender<UserAction>) {
                             break;
                         }
                     }
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Char('r'),
+                        ..
+                    }) => {
+                        if let Err(e) = tx.send(UserAction::Rescan) {
+                            error!("can't send user action to app: {e}");
+                            break;
+                        }
+                    }
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Char('c'),
+                        ..
+                    }) => {
+                        if let Err(e) = tx.send(UserAction::Calibrate) {
+                            error!("can't send user action to app: {e}");
+                            break;
+                        }
+                    }
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Char('f'),
+                        ..
+                    }) => {
+                        if let Err(e) = tx.send(UserAction::DetectFans) {
+                            error!("can't send user action to app: {e}");
+                            break;
+                        }
+                    }
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Char('a'),
+                        ..
+                    }) => {
+                        if let Err(e) = tx.send(UserAction::AbortCalibration) {
+                            error!("can't send user action to app: {e}");
+                            break;
+                        }
+                    }
                     _ => {}
                 },
                 Err(e) => {
@@ -108,5 +213,8 @@ fn display_info(settings: &Settings, current_config: &String) {
     println!();
     println!("Available options:");
     println!("quit: q");
+    println!("rescan hardware: r");
+    println!("calibrate the controls: c");
+    println!("detect the fan of each control: f");
     println!();
 }
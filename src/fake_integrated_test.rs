@@ -1,13 +1,38 @@
-use std::path::PathBuf;
-use std::thread;
-use std::time::Duration;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use crate::args::Args;
 use crate::integrated_test::init_test_logging;
 use data::app_graph::AppGraph;
+use data::calibration::Calibrator;
+use data::clock::ManualClock;
+use data::config::{
+    Config,
+    control::{Calibration, Control, ControlUnit},
+    flat::Flat,
+    graph::{Coord, Graph},
+    hysteresis::Hysteresis,
+    linear::Linear,
+    mix::{Mix, MixKind},
+    pid::Pid,
+    sensor::Sensor,
+    smooth::{Smooth, SmoothKind},
+    target::Target,
+    temp::Temp,
+};
 use data::dir_manager::DirManager;
+use data::engine::{Command, Engine, EngineSettings, Event, Job, Snapshot};
+use data::node::{IsValid, NodeType};
+use data::pairing::Pairing;
 use data::{AppState, update::Update};
-use hardware::HardwareBridge;
+use hardware::fake_hardware::{Call, FakeHardwareBridge, Scenario, ScenarioSensor, Timeline};
+use hardware::record::{Record, RecordingBridge, ReplayBridge};
+use hardware::simulated_hardware::{SimulatedHardwareBridge, Simulation};
+use hardware::{
+    ControlKind, ControlRange, HardwareBridge, Mode, OutputMode, SensorKind, SensorLimits,
+};
 
 #[test]
 fn test_config() {
@@ -21,24 +46,611 @@ fn test_config() {
 
     let dir_manager = DirManager::new(&args.config_dir_path, &args.config_name);
 
-    let bridge = hardware::fake_hardware::FakeHardwareBridge::new().unwrap();
+    let bridge =
+        FakeHardwareBridge::from_file(Path::new("./hardware/scenarios/fake.toml")).unwrap();
 
     let config = dir_manager.get_config().unwrap();
 
     let app_graph = AppGraph::from_config(config, bridge.hardware());
 
-    let app_state = AppState {
+    let mut app_state = AppState {
         dir_manager,
         app_graph,
         update: Update::new(),
         bridge,
     };
 
-    run(app_state)
+    run(&mut app_state, 20);
+
+    // control1 = linear1(average(temp1, temp2))
+    // temp1 ramps from 30 to 70 and temp2 from 40 to 50, in 10 seconds
+    let mut expected = vec![51, 55, 59, 63, 66, 70, 74, 78, 81, 85];
+    expected.resize(20, 85);
+    assert_eq!(app_state.bridge.values_set("control1"), expected);
+
+    // control2 is not active
+    assert!(app_state.bridge.values_set("control2").is_empty());
+
+    let modes = app_state
+        .bridge
+        .calls()
+        .iter()
+        .filter(|call| matches!(call, Call::SetMode { .. }))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        modes,
+        [&Call::SetMode {
+            tick: 1,
+            hardware_id: "control1".into(),
+            mode: Mode::Manual
+        }]
+    );
+}
+
+#[test]
+fn test_record_replay() {
+    init_test_logging();
+
+    let path = std::env::temp_dir().join(format!("fan-control-record-{}", std::process::id()));
+
+    fn app_state<H: HardwareBridge>(bridge: H) -> AppState<H> {
+        let dir_manager = DirManager::new(
+            &Some(PathBuf::from("./configs-examples")),
+            &Some("fake".into()),
+        );
+        let config = dir_manager.get_config().unwrap();
+        let app_graph = AppGraph::from_config(config, bridge.hardware());
+        AppState {
+            dir_manager,
+            app_graph,
+            update: Update::new(),
+            bridge,
+        }
+    }
+
+    let bridge =
+        FakeHardwareBridge::from_file(Path::new("./hardware/scenarios/fake.toml")).unwrap();
+    let mut recording = app_state(RecordingBridge::create(bridge, &path).unwrap());
+    run(&mut recording, 20);
+    let expected = recording.bridge.into_inner().values_set("control1");
+
+    let mut replay = app_state(ReplayBridge::from_file(&path).unwrap());
+    run(&mut replay, 20);
+    std::fs::remove_file(&path).unwrap();
+
+    let values = replay
+        .bridge
+        .calls()
+        .iter()
+        .filter_map(|call| match call {
+            Record::SetValue { id, value, .. } if id == "control1" => Some(*value),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(values, expected);
+    assert_eq!(
+        replay.bridge.calls().len(),
+        replay.bridge.recorded_calls().len()
+    );
+}
+
+/// Run like the graphical interface: control values are read back from the hardware.
+fn run_all<H: HardwareBridge>(app_state: &mut AppState<H>, iterations: usize) {
+    for _ in 0..iterations {
+        app_state.bridge.update().unwrap();
+        app_state
+            .update
+            .nodes_which_update_can_change(&mut app_state.app_graph.nodes, &mut app_state.bridge)
+            .unwrap();
+        app_state
+            .update
+            .all(&mut app_state.app_graph.nodes, &mut app_state.bridge, false)
+            .unwrap();
+    }
+}
+
+/// control1 take the value of a flat behavior
+fn flat_state(
+    kind: ControlKind,
+    range: ControlRange,
+    unit: ControlUnit,
+    value: u16,
+) -> AppState<FakeHardwareBridge> {
+    let mut scenario = Scenario::default();
+    scenario.controls[0].kind = kind;
+    scenario.controls[0].range = range;
+    let bridge = FakeHardwareBridge::from_scenario(scenario);
+
+    let mut control = Control::new(
+        "control1".into(),
+        Some("control1".into()),
+        Some("flat".into()),
+        true,
+        None,
+    );
+    control.unit = unit;
+    let config = Config {
+        controls: vec![control],
+        flats: vec![Flat {
+            name: "flat".into(),
+            value,
+        }],
+        ..Default::default()
+    };
+
+    let dir_manager = DirManager::new(&Some(PathBuf::from("./configs-examples")), &None);
+    let app_graph = AppGraph::from_config(config, bridge.hardware());
+
+    AppState {
+        dir_manager,
+        app_graph,
+        update: Update::new(),
+        bridge,
+    }
+}
+
+#[test]
+fn test_pwm_range() {
+    init_test_logging();
+
+    // the percentage is converted once, then the value read back
+    // from the hardware match the input, so it is not set again
+    let mut app_state = flat_state(
+        ControlKind::Pwm,
+        ControlRange::PWM,
+        ControlUnit::Percent,
+        50,
+    );
+    run_all(&mut app_state, 5);
+    assert_eq!(app_state.bridge.values_set("control1"), [128]);
+
+    let mut app_state = flat_state(ControlKind::Pwm, ControlRange::PWM, ControlUnit::Raw, 20);
+    run_all(&mut app_state, 5);
+    assert_eq!(app_state.bridge.values_set("control1"), [20]);
+}
+
+#[test]
+fn test_fan_target() {
+    init_test_logging();
+
+    let range = ControlRange {
+        min: 1000,
+        max: 3000,
+        step: 1,
+    };
+
+    // a percentage is spread over the speed range
+    let mut app_state = flat_state(ControlKind::FanTarget, range, ControlUnit::Percent, 50);
+    run_all(&mut app_state, 5);
+    assert_eq!(app_state.bridge.values_set("control1"), [2000]);
+
+    let mut app_state = flat_state(ControlKind::FanTarget, range, ControlUnit::Percent, 100);
+    run_all(&mut app_state, 5);
+    assert_eq!(app_state.bridge.values_set("control1"), [3000]);
+}
+
+#[test]
+fn test_power_sensor() {
+    init_test_logging();
+
+    let mut scenario = Scenario::default();
+    scenario.sensors.push(ScenarioSensor {
+        id: "power1".into(),
+        name: Some("Package".into()),
+        timeline: Timeline::Constant { value: 60.0 },
+        limits: Default::default(),
+        kind: Some(SensorKind::Power),
+        unplugged: Vec::new(),
+    });
+    scenario.sensors.push(ScenarioSensor {
+        id: "energy1".into(),
+        name: None,
+        timeline: Timeline::Constant { value: 3600.0 },
+        limits: Default::default(),
+        kind: Some(SensorKind::Energy),
+        unplugged: Vec::new(),
+    });
+    let bridge = FakeHardwareBridge::from_scenario(scenario);
+
+    // the default graph shows every sensor
+    let app_graph = AppGraph::default(bridge.hardware());
+    assert!(app_graph.nodes.values().any(|node| matches!(
+        &node.node_type,
+        NodeType::Sensor(sensor) if sensor.kind() == Some(SensorKind::Power)
+    )));
+
+    let config = Config {
+        controls: vec![Control::new(
+            "control1".into(),
+            Some("control1".into()),
+            Some("linear".into()),
+            true,
+            None,
+        )],
+        sensors: vec![
+            Sensor {
+                name: "package".into(),
+                hardware_id: Some("power1".into()),
+                sensor_h: None,
+            },
+            Sensor {
+                name: "energy".into(),
+                hardware_id: Some("energy1".into()),
+                sensor_h: None,
+            },
+        ],
+        linears: vec![
+            Linear {
+                name: "linear".into(),
+                min_temp: 20.0,
+                min_speed: 10,
+                max_temp: 100.0,
+                max_speed: 100,
+                input: Some("package".into()),
+                hysteresis: Default::default(),
+            },
+            // a counter, not a rate
+            Linear {
+                name: "energy linear".into(),
+                min_temp: 20.0,
+                min_speed: 10,
+                max_temp: 100.0,
+                max_speed: 100,
+                input: Some("energy".into()),
+                hysteresis: Default::default(),
+            },
+        ],
+        ..Default::default()
+    };
+
+    let dir_manager = DirManager::new(&Some(PathBuf::from("./configs-examples")), &None);
+    let app_graph = AppGraph::from_config(config, bridge.hardware());
+    let mut app_state = AppState {
+        dir_manager,
+        app_graph,
+        update: Update::new(),
+        bridge,
+    };
+    run_all(&mut app_state, 3);
+
+    let value = |name: &str| {
+        app_state
+            .app_graph
+            .nodes
+            .values()
+            .find(|node| node.name() == name)
+            .unwrap()
+            .value
+    };
+    assert_eq!(value("package"), Some(60.0));
+    assert_eq!(value("linear"), Some(55.0));
+    assert_eq!(value("energy"), Some(3600.0));
+    assert_eq!(value("energy linear"), None);
+    assert_eq!(app_state.bridge.values_set("control1"), [55]);
 }
 
-fn run<H: HardwareBridge>(mut app_state: AppState<H>) {
-    for _ in 0..20 {
+#[test]
+fn test_graph_fit_on_load() {
+    init_test_logging();
+
+    let scenario = Scenario {
+        temps: vec![ScenarioSensor {
+            id: "temp1".into(),
+            name: None,
+            timeline: Timeline::Constant { value: 50.0 },
+            limits: SensorLimits {
+                crit: Some(90.0),
+                ..Default::default()
+            },
+            kind: None,
+            unplugged: Vec::new(),
+        }],
+        ..Default::default()
+    };
+    let bridge = FakeHardwareBridge::from_scenario(scenario);
+
+    let edited = Graph {
+        name: "edited".into(),
+        coords: [
+            Coord {
+                temp: 30.0,
+                percent: 20,
+            },
+            Coord {
+                temp: 60.0,
+                percent: 100,
+            },
+        ]
+        .into(),
+        input: Some("temp1".into()),
+        hysteresis: Default::default(),
+    };
+    let config = Config {
+        temps: vec![Temp {
+            name: "temp1".into(),
+            hardware_id: Some("temp1".into()),
+            temp_h: None,
+        }],
+        graphs: vec![
+            Graph {
+                name: "default".into(),
+                input: Some("temp1".into()),
+                ..Default::default()
+            },
+            edited.clone(),
+        ],
+        ..Default::default()
+    };
+
+    let app_graph = AppGraph::from_config(config, bridge.hardware());
+    let graph = |name: &str| {
+        app_graph
+            .nodes
+            .values()
+            .find(|node| node.name() == name)
+            .unwrap()
+            .node_type
+            .clone()
+            .unwrap_graph()
+    };
+
+    // the default curve tops out at the crit temperature, the edited one is kept
+    assert_eq!(graph("default").coords.last().unwrap().temp, 90.0);
+    assert_eq!(graph("edited"), edited);
+}
+
+#[test]
+fn test_fractional_temp() {
+    init_test_logging();
+
+    let mut scenario = Scenario::default();
+    scenario.temps[0].timeline = Timeline::Constant { value: 45.5 };
+    let bridge = FakeHardwareBridge::from_scenario(scenario);
+
+    let config = Config {
+        controls: vec![Control::new(
+            "control1".into(),
+            Some("control1".into()),
+            Some("linear".into()),
+            true,
+            None,
+        )],
+        temps: vec![Temp {
+            name: "temp1".into(),
+            hardware_id: Some("temp1".into()),
+            temp_h: None,
+        }],
+        linears: vec![Linear {
+            name: "linear".into(),
+            min_temp: 40.0,
+            min_speed: 0,
+            max_temp: 50.0,
+            max_speed: 100,
+            input: Some("temp1".into()),
+            hysteresis: Default::default(),
+        }],
+        ..Default::default()
+    };
+
+    let dir_manager = DirManager::new(&Some(PathBuf::from("./configs-examples")), &None);
+    let app_graph = AppGraph::from_config(config, bridge.hardware());
+    let mut app_state = AppState {
+        dir_manager,
+        app_graph,
+        update: Update::new(),
+        bridge,
+    };
+    run_all(&mut app_state, 3);
+
+    let value = |name: &str| {
+        app_state
+            .app_graph
+            .nodes
+            .values()
+            .find(|node| node.name() == name)
+            .unwrap()
+            .value
+    };
+    // not truncated to 45 °C
+    assert_eq!(value("temp1"), Some(45.5));
+    assert_eq!(value("linear"), Some(55.0));
+    assert_eq!(app_state.bridge.values_set("control1"), [55]);
+}
+
+#[test]
+fn test_smooth_spike() {
+    init_test_logging();
+
+    let max_value_set = |input: &str| {
+        let mut scenario = Scenario::default();
+        // a spike of 90 °C, lasting about one second
+        scenario.temps[0].timeline = Timeline::Samples {
+            path: PathBuf::new(),
+            samples: vec![(0.0, 40.0), (4.0, 40.0), (5.0, 90.0), (6.0, 40.0)],
+        };
+        let bridge = FakeHardwareBridge::from_scenario(scenario);
+
+        let config = Config {
+            controls: vec![Control::new(
+                "control1".into(),
+                Some("control1".into()),
+                Some("linear".into()),
+                true,
+                None,
+            )],
+            temps: vec![Temp {
+                name: "temp1".into(),
+                hardware_id: Some("temp1".into()),
+                temp_h: None,
+            }],
+            smooths: vec![Smooth::new(
+                "smooth".into(),
+                SmoothKind::Exponential,
+                5.0,
+                Some("temp1".into()),
+            )],
+            linears: vec![Linear {
+                name: "linear".into(),
+                min_temp: 40.0,
+                min_speed: 0,
+                max_temp: 90.0,
+                max_speed: 100,
+                input: Some(input.into()),
+                hysteresis: Default::default(),
+            }],
+            ..Default::default()
+        };
+
+        let clock = ManualClock::default();
+        let dir_manager = DirManager::new(&Some(PathBuf::from("./configs-examples")), &None);
+        let app_graph = AppGraph::from_config(config, bridge.hardware());
+        let mut app_state = AppState {
+            dir_manager,
+            app_graph,
+            update: Update::with_clock(clock.clone()),
+            bridge,
+        };
+        for _ in 0..10 {
+            run_all(&mut app_state, 1);
+            // the default step of the scenario
+            clock.advance(Duration::from_secs(1));
+        }
+
+        app_state
+            .bridge
+            .values_set("control1")
+            .into_iter()
+            .max()
+            .unwrap()
+    };
+
+    assert_eq!(max_value_set("temp1"), 100);
+    // alpha = 1 - e^(-1/5), so the spike only moves the average by about 9 °C
+    assert_eq!(max_value_set("smooth"), 18);
+}
+
+#[test]
+fn test_mix() {
+    init_test_logging();
+
+    let mut scenario = Scenario::default();
+    scenario.temps[0].timeline = Timeline::Step {
+        before: 40.0,
+        after: 60.0,
+        at: 2.5,
+    };
+    let bridge = FakeHardwareBridge::from_scenario(scenario);
+
+    let config = Config {
+        controls: vec![Control::new(
+            "control1".into(),
+            Some("control1".into()),
+            Some("mix".into()),
+            true,
+            None,
+        )],
+        temps: vec![Temp {
+            name: "temp1".into(),
+            hardware_id: Some("temp1".into()),
+            temp_h: None,
+        }],
+        flats: vec![Flat {
+            name: "flat".into(),
+            value: 30,
+        }],
+        linears: vec![Linear {
+            name: "linear".into(),
+            min_temp: 40.0,
+            min_speed: 0,
+            max_temp: 80.0,
+            max_speed: 100,
+            input: Some("temp1".into()),
+            hysteresis: Default::default(),
+        }],
+        mixes: vec![Mix::new(
+            "mix".into(),
+            MixKind::Max,
+            // a temperature can't be mixed
+            vec!["linear".into(), "flat".into(), "temp1".into()],
+        )],
+        ..Default::default()
+    };
+
+    let dir_manager = DirManager::new(&Some(PathBuf::from("./configs-examples")), &None);
+    let app_graph = AppGraph::from_config(config, bridge.hardware());
+    let mix = app_graph
+        .nodes
+        .values()
+        .find(|node| node.name() == "mix")
+        .unwrap();
+    assert_eq!(mix.inputs.len(), 2);
+
+    let mut app_state = AppState {
+        dir_manager,
+        app_graph,
+        update: Update::new(),
+        bridge,
+    };
+    run_all(&mut app_state, 4);
+
+    // the flat, then the linear once above it
+    assert_eq!(app_state.bridge.values_set("control1"), [30, 50]);
+}
+
+#[test]
+fn test_pwm_settings() {
+    init_test_logging();
+
+    let mut app_state = flat_state(
+        ControlKind::Pwm,
+        ControlRange::PWM,
+        ControlUnit::Percent,
+        50,
+    );
+    let mut scenario = Scenario::default();
+    scenario.controls[0].frequency = Some(25000);
+    scenario.controls[0].output_mode = Some(OutputMode::Pwm);
+    app_state.bridge = FakeHardwareBridge::from_scenario(scenario);
+    app_state.app_graph = {
+        let mut config = Config::from_app_graph(&app_state.app_graph);
+        config.controls[0].frequency = Some(22500);
+        config.controls[0].output_mode = Some(OutputMode::Dc);
+        AppGraph::from_config(config, app_state.bridge.hardware())
+    };
+
+    run_all(&mut app_state, 3);
+
+    // applied once, when the control takes over
+    let settings = app_state
+        .bridge
+        .calls()
+        .iter()
+        .filter(|call| !matches!(call, Call::SetValue { .. }))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        settings,
+        [
+            &Call::SetMode {
+                tick: 1,
+                hardware_id: "control1".into(),
+                mode: Mode::Manual
+            },
+            &Call::SetFrequency {
+                tick: 1,
+                hardware_id: "control1".into(),
+                frequency: 22500
+            },
+            &Call::SetOutputMode {
+                tick: 1,
+                hardware_id: "control1".into(),
+                mode: OutputMode::Dc
+            },
+        ]
+    );
+}
+
+fn run<H: HardwareBridge>(app_state: &mut AppState<H>, iterations: usize) {
+    for _ in 0..iterations {
         if let Err(e) = app_state.bridge.update() {
             error!("{e}");
             break;
@@ -54,6 +666,726 @@ fn run<H: HardwareBridge>(mut app_state: AppState<H>) {
             )
             .unwrap();
         debug!("\n");
-        thread::sleep(Duration::from_millis(50));
     }
 }
+
+/// Run the graph against the thermal simulation and return the
+/// temperature of the heat source after each update.
+fn run_simulation(app_state: &mut AppState<SimulatedHardwareBridge>, ticks: usize) -> Vec<f64> {
+    let mut temps = Vec::new();
+    for _ in 0..ticks {
+        app_state.bridge.update().unwrap();
+        app_state
+            .update
+            .optimized(
+                &mut app_state.app_graph.nodes,
+                &app_state.app_graph.root_nodes,
+                &mut app_state.bridge,
+                false,
+            )
+            .unwrap();
+        temps.push(app_state.bridge.temperature("temp1").unwrap());
+    }
+    temps
+}
+
+fn simulation_state(behavior: NodeType) -> AppState<SimulatedHardwareBridge> {
+    let mut simulation = Simulation::default();
+    simulation.sources[0].load = Timeline::Constant { value: 100.0 };
+    let bridge = SimulatedHardwareBridge::from_simulation(simulation);
+
+    let mut config = Config {
+        controls: vec![Control::new(
+            "control1".into(),
+            Some("control1".into()),
+            Some(behavior.name().clone()),
+            true,
+            None,
+        )],
+        temps: vec![Temp {
+            name: "temp1".into(),
+            hardware_id: Some("temp1".into()),
+            temp_h: None,
+        }],
+        ..Default::default()
+    };
+    match behavior {
+        NodeType::Graph(graph) => config.graphs.push(graph),
+        NodeType::Target(target) => config.targets.push(target),
+        NodeType::Pid(pid) => config.pids.push(pid),
+        _ => unreachable!(),
+    }
+
+    let dir_manager = DirManager::new(&Some(PathBuf::from("./configs-examples")), &None);
+    let app_graph = AppGraph::from_config(config, bridge.hardware());
+
+    AppState {
+        dir_manager,
+        app_graph,
+        update: Update::new(),
+        bridge,
+    }
+}
+
+#[test]
+fn test_simulation_graph_converges() {
+    init_test_logging();
+
+    let graph = Graph {
+        name: "graph".into(),
+        coords: [(30.0, 20), (60.0, 100)]
+            .into_iter()
+            .map(|(temp, percent)| Coord { temp, percent })
+            .collect(),
+        input: Some("temp1".into()),
+        hysteresis: Default::default(),
+    };
+    let mut app_state = simulation_state(NodeType::Graph(graph));
+
+    let temps = run_simulation(&mut app_state, 900);
+
+    // the last minute is stable, between the equilibrium at 20% and 100%
+    let last = &temps[temps.len() - 60..];
+    let min = last.iter().cloned().fold(f64::MAX, f64::min);
+    let max = last.iter().cloned().fold(f64::MIN, f64::max);
+    assert!(max - min < 1.0, "temperature still moves: {min}..{max}");
+    assert!(min > 47.2 && max < 83.8);
+
+    // the speed only moves by the slope of the curve for 1°C (80% / 30°C)
+    let values = app_state.bridge.values_set("control1");
+    let last = &values[values.len() - 60..];
+    assert!(last.iter().max().unwrap() - last.iter().min().unwrap() <= 3);
+}
+
+#[test]
+fn test_simulation_graph_hysteresis() {
+    init_test_logging();
+
+    let graph = |hysteresis| Graph {
+        name: "graph".into(),
+        coords: [(30.0, 20), (60.0, 100)]
+            .into_iter()
+            .map(|(temp, percent)| Coord { temp, percent })
+            .collect(),
+        input: Some("temp1".into()),
+        hysteresis,
+    };
+
+    let changes = |hysteresis| {
+        let mut app_state = simulation_state(NodeType::Graph(graph(hysteresis)));
+        let temps = run_simulation(&mut app_state, 900);
+        let values = app_state.bridge.values_set("control1");
+        let changes = values.windows(2).filter(|w| w[0] != w[1]).count();
+        (temps, changes)
+    };
+
+    let (_, without) = changes(Hysteresis::default());
+    let (temps, with) = changes(Hysteresis::new(1.0, 2.0));
+
+    // still regulated, with less hunting
+    let last = &temps[temps.len() - 60..];
+    assert!(last.iter().all(|t| (47.2..83.8).contains(t)));
+    assert!(with < without / 2, "{with} changes against {without}");
+}
+
+#[test]
+fn test_simulation_target_hysteresis() {
+    init_test_logging();
+
+    let target = Target {
+        name: "target".into(),
+        idle_temp: 50.0,
+        idle_speed: 30,
+        load_temp: 60.0,
+        load_speed: 100,
+        input: Some("temp1".into()),
+        idle_has_been_reatch: false,
+    };
+    let mut app_state = simulation_state(NodeType::Target(target));
+
+    let temps = run_simulation(&mut app_state, 1800);
+
+    // the temperature cycles between idle and load temp
+    let steady = &temps[600..];
+    assert!(steady.iter().all(|t| (48.0..62.0).contains(t)));
+
+    // the fans don't hunt: the speed changes rarely
+    let values = app_state.bridge.values_set("control1");
+    let changes = values.windows(2).filter(|w| w[0] != w[1]).count();
+    assert!(changes > 2);
+    assert!(changes < values.len() / 20);
+}
+
+#[test]
+fn test_simulation_pid() {
+    init_test_logging();
+
+    let pid = Pid {
+        name: "pid".into(),
+        setpoint: 60.0,
+        input: Some("temp1".into()),
+        ..Default::default()
+    };
+    let mut app_state = simulation_state(NodeType::Pid(pid));
+    // the time of the simulation
+    let clock = ManualClock::default();
+    app_state.update = Update::with_clock(clock.clone());
+
+    let mut temps = Vec::new();
+    for _ in 0..1800 {
+        clock.advance(Duration::from_secs(1));
+        temps.extend(run_simulation(&mut app_state, 1));
+    }
+
+    // held at the setpoint, without the hunting of a target behavior
+    let steady = &temps[temps.len() - 300..];
+    assert!(
+        steady.iter().all(|t| (59.5..60.5).contains(t)),
+        "{steady:?}"
+    );
+
+    // without input, the integral is forgotten
+    let pid_node = app_state
+        .app_graph
+        .nodes
+        .values_mut()
+        .find(|node| node.name() == "pid")
+        .unwrap();
+    pid_node.node_type.set_inputs(Vec::new());
+    pid_node.inputs.clear();
+    run_simulation(&mut app_state, 1);
+
+    let pid_node = app_state
+        .app_graph
+        .nodes
+        .values()
+        .find(|node| node.name() == "pid")
+        .unwrap();
+    assert!(pid_node.node_type.unwrap_pid_ref().state.is_none());
+    assert_eq!(pid_node.value, None);
+}
+
+#[test]
+fn test_edit_keeps_state() {
+    init_test_logging();
+
+    // the output is the integral
+    let pid = Pid {
+        name: "pid".into(),
+        setpoint: 0.0,
+        kp: 0.0,
+        ki: 1.0,
+        input: Some("temp1".into()),
+        ..Default::default()
+    };
+    let mut app_state = simulation_state(NodeType::Pid(pid));
+    // slow enough to lag behind the temperature
+    let mut config = Config::from_app_graph(&app_state.app_graph);
+    config.smooths.push(Smooth::new(
+        "smooth".into(),
+        SmoothKind::Exponential,
+        1000.0,
+        Some("temp1".into()),
+    ));
+    // always above the idle temperature: idle only if it has been reached before
+    config.targets.push(Target {
+        name: "target".into(),
+        idle_temp: 0.0,
+        idle_speed: 10,
+        load_temp: 1000.0,
+        load_speed: 90,
+        input: Some("temp1".into()),
+        ..Default::default()
+    });
+    app_state.app_graph = AppGraph::from_config(config, app_state.bridge.hardware());
+
+    let mut app_graph = app_state.app_graph.clone();
+    let id_of = |name: &str| {
+        app_graph
+            .nodes
+            .values()
+            .find(|node| node.name() == name)
+            .unwrap()
+            .id
+    };
+    let control1 = id_of("control1");
+    let pid = id_of("pid");
+    let smooth = id_of("smooth");
+    let target = id_of("target");
+    app_state
+        .app_graph
+        .get_mut(&target)
+        .node_type
+        .unwrap_target_mut()
+        .idle_has_been_reatch = true;
+
+    let settings = EngineSettings {
+        update_delay: Duration::ZERO,
+        inactive: false,
+        rescan_interval: None,
+        update_all: true,
+    };
+    let clock = ManualClock::default();
+    let mut engine = Engine::spawn(
+        app_state.bridge,
+        app_state.app_graph,
+        Update::with_clock(clock.clone()),
+        settings,
+    );
+    // a cycle fully run after what happened before
+    let wait_cycle = |engine: &Engine<SimulatedHardwareBridge>| {
+        let mut snapshot = None;
+        for _ in 0..3 {
+            snapshot = engine.wait_snapshot(Duration::from_secs(5));
+        }
+        snapshot.unwrap()
+    };
+    wait_cycle(&engine);
+    clock.advance(Duration::from_secs(1));
+    let snapshot = wait_cycle(&engine);
+    let integral = snapshot.values[&pid];
+    let smoothed = snapshot.values[&smooth];
+    assert!(integral.is_some_and(|integral| integral > 0.0));
+    assert_eq!(snapshot.values[&target], Some(10.0));
+
+    // editing another node doesn't reset the integral, the smoothing, nor the target
+    app_graph
+        .get_mut(&control1)
+        .node_type
+        .unwrap_control_mut()
+        .ramp_up = Some(50.0);
+    engine.send(Command::Graph(app_graph.clone()));
+    let snapshot = wait_cycle(&engine);
+    assert_eq!(snapshot.values[&pid], integral);
+    assert_eq!(snapshot.values[&smooth], smoothed);
+    assert_eq!(snapshot.values[&target], Some(10.0));
+
+    // editing them does
+    app_graph.get_mut(&pid).node_type.unwrap_pid_mut().ki = 2.0;
+    app_graph
+        .get_mut(&target)
+        .node_type
+        .unwrap_target_mut()
+        .load_speed = 80;
+    engine.send(Command::Graph(app_graph));
+    let snapshot = wait_cycle(&engine);
+    assert_eq!(snapshot.values[&pid], Some(0.0));
+    assert_eq!(snapshot.values[&target], Some(80.0));
+
+    engine.stop().unwrap();
+}
+
+#[test]
+fn test_calibration() {
+    init_test_logging();
+
+    let graph = Graph {
+        name: "graph".into(),
+        coords: [(30.0, 0), (60.0, 100)]
+            .into_iter()
+            .map(|(temp, percent)| Coord { temp, percent })
+            .collect(),
+        input: Some("temp1".into()),
+        hysteresis: Default::default(),
+    };
+    let mut app_state = simulation_state(NodeType::Graph(graph));
+    let control = app_state.app_graph.root_nodes[0];
+
+    let mut calibrator =
+        Calibrator::start(&mut app_state.app_graph, &mut app_state.bridge, control, 8).unwrap();
+    assert!(!app_state.app_graph.get(&control).node_type.is_valid());
+
+    let calibration = loop {
+        app_state.bridge.update().unwrap();
+        if let Some(result) = calibrator.tick(&mut app_state.app_graph, &mut app_state.bridge) {
+            break result.unwrap();
+        }
+    };
+
+    // thresholds of the default simulated fan
+    assert_eq!(
+        calibration,
+        Calibration {
+            min_start: 30,
+            min_stay: 20,
+            max_effective: 100,
+        }
+    );
+    assert!(matches!(
+        app_state.bridge.calls().last(),
+        Some(Call::SetMode {
+            mode: Mode::Auto,
+            ..
+        })
+    ));
+
+    let NodeType::Control(control_node) = &app_state.app_graph.get(&control).node_type else {
+        panic!("not a control");
+    };
+    assert_eq!(control_node.calibration, Some(calibration));
+    assert!(control_node.is_valid());
+
+    // the fan never stalls on a value too low to keep it spinning
+    let calibration_calls = app_state.bridge.values_set("control1").len();
+    run_simulation(&mut app_state, 600);
+    let values = app_state.bridge.values_set("control1");
+    assert!(
+        values[calibration_calls..]
+            .iter()
+            .all(|value| *value == 0 || *value >= 20)
+    );
+    assert!(values[calibration_calls..].iter().any(|value| *value > 20));
+}
+
+#[test]
+fn test_pairing() {
+    init_test_logging();
+
+    // the fans are not listed in the order of their control
+    let mut simulation = Simulation::default();
+    let mut fan2 = simulation.fans[0].clone();
+    fan2.id = "fan2".into();
+    simulation.fans[0].control = "control2".into();
+    fan2.control = "control1".into();
+    simulation.fans.push(fan2);
+    let bridge = SimulatedHardwareBridge::from_simulation(simulation);
+
+    let config = Config {
+        controls: ["control1", "control2"]
+            .into_iter()
+            .map(|id| Control::new(id.into(), Some(id.into()), None, true, None))
+            .collect(),
+        ..Default::default()
+    };
+    let mut app_state = AppState {
+        dir_manager: DirManager::new(&Some(PathBuf::from("./configs-examples")), &None),
+        app_graph: AppGraph::from_config(config, bridge.hardware()),
+        update: Update::new(),
+        bridge,
+    };
+    let controls = app_state.app_graph.root_nodes.clone();
+
+    let mut pairing = Pairing::start(
+        &mut app_state.app_graph,
+        &mut app_state.bridge,
+        controls.clone(),
+        8,
+    );
+    let pairs = loop {
+        app_state.bridge.update().unwrap();
+        if let Some(pairs) = pairing.tick(&mut app_state.app_graph, &mut app_state.bridge) {
+            break pairs;
+        }
+    };
+
+    let mut pairs = pairs
+        .into_iter()
+        .map(|pair| (pair.control, pair.fan.unwrap()))
+        .collect::<Vec<_>>();
+    pairs.sort();
+    assert_eq!(
+        pairs,
+        vec![
+            ("control1".to_string(), "fan2".to_string()),
+            ("control2".to_string(), "fan1".to_string())
+        ]
+    );
+
+    for id in &controls {
+        let NodeType::Control(control) = &app_state.app_graph.get(id).node_type else {
+            panic!("not a control");
+        };
+        assert!(control.control_h.is_some());
+        let expected = if control.name == "control1" {
+            "fan2"
+        } else {
+            "fan1"
+        };
+        assert_eq!(control.fan.as_deref(), Some(expected));
+    }
+
+    // every control is back in auto mode
+    for id in ["control1", "control2"] {
+        let last_mode = app_state
+            .bridge
+            .calls()
+            .iter()
+            .rev()
+            .find_map(|call| match call {
+                Call::SetMode {
+                    hardware_id, mode, ..
+                } if hardware_id == id => Some(mode.clone()),
+                _ => None,
+            });
+        assert_eq!(last_mode, Some(Mode::Auto));
+    }
+}
+
+#[test]
+fn test_hotplug() {
+    init_test_logging();
+
+    let mut app_state = flat_state(
+        ControlKind::Pwm,
+        ControlRange::default(),
+        ControlUnit::Percent,
+        50,
+    );
+    // control1 is unplugged during the ticks 2 and 3
+    let mut scenario = Scenario::default();
+    scenario.controls[0].unplugged = vec![[2.0, 4.0]];
+    app_state.bridge = FakeHardwareBridge::from_scenario(scenario);
+    app_state.app_graph = AppGraph::from_config(
+        Config::from_app_graph(&app_state.app_graph),
+        app_state.bridge.hardware(),
+    );
+
+    let rescan_and_run = |app_state: &mut AppState<FakeHardwareBridge>| {
+        app_state.bridge.update().unwrap();
+        app_state.rescan().unwrap();
+        app_state
+            .update
+            .all(&mut app_state.app_graph.nodes, &mut app_state.bridge, false)
+            .unwrap();
+    };
+
+    rescan_and_run(&mut app_state);
+    let control = app_state.app_graph.root_nodes[0];
+    assert!(app_state.app_graph.get(&control).node_type.is_valid());
+
+    // the node keeps its hardware id while the control is gone
+    rescan_and_run(&mut app_state);
+    let node = app_state.app_graph.get(&control);
+    assert!(!node.node_type.is_valid());
+    assert_eq!(node.hardware_id().as_deref(), Some("control1"));
+    rescan_and_run(&mut app_state);
+
+    // bound again, and taken over like at the start
+    rescan_and_run(&mut app_state);
+    assert!(app_state.app_graph.get(&control).node_type.is_valid());
+
+    let modes = app_state
+        .bridge
+        .calls()
+        .iter()
+        .filter_map(|call| match call {
+            Call::SetMode { tick, mode, .. } => Some((*tick, mode.clone())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(modes, [(1, Mode::Manual), (4, Mode::Manual)]);
+}
+
+#[test]
+fn test_engine() {
+    init_test_logging();
+
+    let dir_manager = DirManager::new(
+        &Some(PathBuf::from("./configs-examples")),
+        &Some("fake".into()),
+    );
+    let bridge =
+        FakeHardwareBridge::from_file(Path::new("./hardware/scenarios/fake.toml")).unwrap();
+    let mut app_graph = AppGraph::from_config(dir_manager.get_config().unwrap(), bridge.hardware());
+    let id_of = |name: &str| {
+        app_graph
+            .nodes
+            .values()
+            .find(|node| node.name() == name)
+            .unwrap()
+            .id
+    };
+    let control1 = id_of("control1");
+    let temp1 = id_of("temp1");
+
+    let settings = EngineSettings {
+        update_delay: Duration::ZERO,
+        inactive: false,
+        rescan_interval: None,
+        update_all: true,
+    };
+    let mut engine = Engine::spawn(bridge, app_graph.clone(), Update::new(), settings);
+    let timeout = Duration::from_secs(5);
+
+    // the loop runs without anybody asking for it
+    let mut snapshot = Snapshot::default();
+    for _ in 0..3 {
+        snapshot = engine.wait_snapshot(timeout).unwrap();
+    }
+    assert!(snapshot.values[&control1].is_some());
+    // nodes which don't drive a control are updated too
+    assert!(snapshot.values[&temp1].is_some());
+    assert_eq!(snapshot.job, Job::Idle);
+
+    // the edited graph replaces the one of the engine
+    app_graph
+        .get_mut(&control1)
+        .node_type
+        .unwrap_control_mut()
+        .active = false;
+    engine.send(Command::Graph(app_graph));
+    for _ in 0..3 {
+        engine.wait_snapshot(timeout).unwrap();
+    }
+
+    let bridge = engine.stop().unwrap();
+    let calls = bridge.calls();
+
+    let modes = calls
+        .iter()
+        .filter_map(|call| match call {
+            Call::SetMode { mode, .. } => Some(mode.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(modes, [Mode::Manual, Mode::Auto]);
+
+    // nothing is written once the control went back to auto
+    assert!(matches!(
+        calls.last(),
+        Some(Call::SetMode {
+            mode: Mode::Auto,
+            ..
+        })
+    ));
+    assert!(!bridge.values_set("control1").is_empty());
+}
+
+#[test]
+fn test_rescan_during_calibration() {
+    init_test_logging();
+
+    let graph = Graph {
+        name: "graph".into(),
+        coords: [Coord {
+            temp: 30.0,
+            percent: 50,
+        }]
+        .into(),
+        input: Some("temp1".into()),
+        hysteresis: Default::default(),
+    };
+    let app_state = simulation_state(NodeType::Graph(graph));
+    let control = app_state.app_graph.root_nodes[0];
+
+    let settings = EngineSettings {
+        update_delay: Duration::ZERO,
+        inactive: false,
+        rescan_interval: Some(Duration::ZERO),
+        update_all: true,
+    };
+    let mut engine = Engine::spawn(
+        app_state.bridge,
+        app_state.app_graph,
+        Update::new(),
+        settings,
+    );
+    let timeout = Duration::from_secs(5);
+    let rescanned = |engine: &Engine<SimulatedHardwareBridge>| {
+        engine
+            .events()
+            .iter()
+            .any(|event| matches!(event, Event::Rescanned { .. }))
+    };
+    engine.wait_snapshot(timeout).unwrap();
+    assert!(rescanned(&engine));
+
+    engine.send(Command::Calibrate(vec![control]));
+    engine.send(Command::Rescan);
+
+    // neither the periodic rescans nor the asked one run during the calibration
+    for _ in 0..5 {
+        let snapshot = engine.wait_snapshot(timeout).unwrap();
+        assert!(snapshot.job.is_running());
+        assert!(!rescanned(&engine));
+    }
+
+    engine.send(Command::AbortCalibration);
+    for _ in 0..3 {
+        engine.wait_snapshot(timeout).unwrap();
+    }
+    assert!(rescanned(&engine));
+    engine.stop().unwrap();
+}
+
+#[test]
+fn test_ramp() {
+    init_test_logging();
+
+    let bridge = FakeHardwareBridge::from_scenario(Scenario::default());
+    let mut control = Control::new(
+        "control1".into(),
+        Some("control1".into()),
+        Some("flat".into()),
+        true,
+        None,
+    );
+    control.ramp_up = Some(10.0);
+    let config = Config {
+        controls: vec![control],
+        flats: vec![Flat {
+            name: "flat".into(),
+            value: 0,
+        }],
+        ..Default::default()
+    };
+    let mut app_graph = AppGraph::from_config(config, bridge.hardware());
+    let id_of = |app_graph: &AppGraph, name: &str| {
+        app_graph
+            .nodes
+            .values()
+            .find(|node| node.name() == name)
+            .unwrap()
+            .id
+    };
+    let control1 = id_of(&app_graph, "control1");
+    let flat = id_of(&app_graph, "flat");
+
+    let settings = EngineSettings {
+        update_delay: Duration::ZERO,
+        inactive: false,
+        rescan_interval: None,
+        update_all: true,
+    };
+    let clock = ManualClock::default();
+    let mut engine = Engine::spawn(
+        bridge,
+        app_graph.clone(),
+        Update::with_clock(clock.clone()),
+        settings,
+    );
+    // a cycle fully run after what happened before
+    let wait_cycle = |engine: &Engine<FakeHardwareBridge>| {
+        for _ in 0..3 {
+            engine.wait_snapshot(Duration::from_secs(5)).unwrap();
+        }
+    };
+    wait_cycle(&engine);
+
+    // editing another node doesn't restart the ramp
+    app_graph.get_mut(&flat).node_type.unwrap_flat_mut().value = 100;
+    engine.send(Command::Graph(app_graph.clone()));
+    wait_cycle(&engine);
+    for _ in 0..3 {
+        clock.advance(Duration::from_secs(1));
+        wait_cycle(&engine);
+    }
+
+    // back to auto, the ramp starts again from the next value
+    let set_active = |app_graph: &mut AppGraph, active| {
+        app_graph
+            .get_mut(&control1)
+            .node_type
+            .unwrap_control_mut()
+            .active = active;
+    };
+    set_active(&mut app_graph, false);
+    engine.send(Command::Graph(app_graph.clone()));
+    wait_cycle(&engine);
+    set_active(&mut app_graph, true);
+    engine.send(Command::Graph(app_graph));
+    wait_cycle(&engine);
+
+    let bridge = engine.stop().unwrap();
+    assert_eq!(bridge.values_set("control1"), [0, 10, 20, 30, 100]);
+}
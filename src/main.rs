@@ -2,12 +2,21 @@
 // because it blocks all logs, from C# AND Rust
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::{env, fs};
+use std::{
+    env, fs,
+    sync::{Arc, atomic::AtomicBool},
+};
 
 use args::Args;
 use clap::Parser;
 use data::{AppState, app_graph::AppGraph, dir_manager::DirManager, update::Update};
-use hardware::{self, HardwareBridge};
+use hardware::{
+    self, HardwareBridge,
+    composite::CompositeBridge,
+    file::FileBridge,
+    record::{RecordingBridge, ReplayBridge},
+    remote::{Agent, RemoteBridge},
+};
 use log::LevelFilter;
 use thiserror::Error;
 
@@ -103,7 +112,64 @@ fn try_run() -> Result<()> {
     ui::localize::localize();
     data::localize::localize();
 
-    let bridge = hardware::new()?;
+    if let Some(path) = &args.replay {
+        let bridge = ReplayBridge::from_file(path)?;
+        return run(args, dir_manager, bridge);
+    }
+
+    if let Some(address) = &args.remote {
+        let bridge = RemoteBridge::connect(address.as_str())?;
+        return record_and_run(args, dir_manager, bridge);
+    }
+
+    let mut bridge = CompositeBridge::default();
+    match hardware::new() {
+        Ok(native) => bridge.push(None, native)?,
+        Err(e) => error!("Can't use the hardware: {e}."),
+    }
+    if let Err(e) = bridge.push(
+        Some("file"),
+        FileBridge::from_endpoints(dir_manager.file_endpoints()),
+    ) {
+        error!("Can't use the files declared next to the configs: {e}.");
+    }
+
+    if let Some(address) = &args.agent {
+        let stop = Arc::new(AtomicBool::new(false));
+        for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+            if let Err(e) = signal_hook::flag::register(signal, Arc::clone(&stop)) {
+                warn!("can't handle the signal {signal}: {e}");
+            }
+        }
+
+        let mut agent = Agent::bind(bridge, address.as_str())?;
+        agent.serve(&stop)?;
+
+        let mut bridge = agent.into_inner();
+        if let Err(e) = bridge.shutdown() {
+            error!("Can't shutdown hardware: {e}.");
+        }
+        return Ok(());
+    }
+
+    record_and_run(args, dir_manager, bridge)
+}
+
+fn record_and_run<H: HardwareBridge + 'static>(
+    args: Args,
+    dir_manager: DirManager,
+    bridge: H,
+) -> Result<()> {
+    match &args.record {
+        Some(path) => {
+            let bridge = RecordingBridge::create(bridge, path)?;
+            run(args, dir_manager, bridge)
+        }
+        None => run(args, dir_manager, bridge),
+    }
+}
+
+fn run<H: HardwareBridge + 'static>(args: Args, dir_manager: DirManager, bridge: H) -> Result<()> {
     let hardware = bridge.hardware();
 
     debug!("sensors found: {hardware:?}");
@@ -113,6 +179,8 @@ fn try_run() -> Result<()> {
         return Ok(());
     }
 
+    dir_manager.migrate_hardware_ids(hardware);
+
     let app_graph = match dir_manager
         .get_config_cached()
         .or_else(|| dir_manager.get_config())
@@ -15,6 +15,7 @@ use data::{
 use crate::{
     icon::window_icon,
     icon_button,
+    item::hysteresis_view,
     message::{AppMsg, GraphMsg, ModifNodeMsg},
     node_cache::GraphC,
     pick_list_utils::{self, MyOption},
@@ -23,7 +24,7 @@ use crate::{
 pub fn graph_view<'a>(
     node: &'a Node,
     graph: &'a Graph,
-    _graph_c: &'a GraphC,
+    graph_c: &'a GraphC,
     nodes: &'a Nodes,
 ) -> Element<'a, AppMsg> {
     let input_options =
@@ -64,6 +65,7 @@ pub fn graph_view<'a>(
         launch_window,
         Text::new(node.value_text(&ValueKind::Porcentage)).into(),
         coords,
+        hysteresis_view(node, &graph.hysteresis, &graph_c.hysteresis),
     ];
 
     Column::with_children(content).into()
@@ -1,6 +1,5 @@
 use cosmic::{Element, iced_core::Length, iced_widget::text, widget::tooltip};
-use data::AppState;
-use hardware::HardwareBridge;
+use data::dir_manager::DirManager;
 
 use crate::{AppMsg, ToogleMsg, icon_button};
 
@@ -19,8 +18,7 @@ pub fn header_start<'a>() -> Vec<Element<'a, AppMsg>> {
     elems
 }
 
-pub fn header_end<'a, H: HardwareBridge>(app_state: &'a AppState<H>) -> Vec<Element<'a, AppMsg>> {
-    let dir_manager = &app_state.dir_manager;
+pub fn header_end(dir_manager: &DirManager) -> Vec<Element<'_, AppMsg>> {
     let settings = dir_manager.settings();
 
     let mut elems = vec![];
@@ -40,6 +38,18 @@ pub fn header_end<'a, H: HardwareBridge>(app_state: &'a AppState<H>) -> Vec<Elem
         );
     }
 
+    elems.push(
+        tooltip(
+            icon_button!("refresh/40")
+                .height(ICON_LENGTH)
+                .width(ICON_LENGTH)
+                .on_press(AppMsg::Rescan),
+            text(fl!("rescan_hardware")),
+            tooltip::Position::Bottom,
+        )
+        .into(),
+    );
+
     let settings_button = icon_button!("settings/40")
         .on_press(AppMsg::Toggle(ToogleMsg::Settings))
         .height(ICON_LENGTH)
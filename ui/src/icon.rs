@@ -42,11 +42,16 @@ macro_rules! node_icon_handle {
             NodeTypeLight::Control => icon_handle!("speed/24"),
             NodeTypeLight::Fan => icon_handle!("toys_fan/24"),
             NodeTypeLight::Temp => icon_handle!("thermometer/24"),
+            NodeTypeLight::Sensor => icon_handle!("bolt/24"),
+            NodeTypeLight::CommandTemp => icon_handle!("thermometer/24"),
             NodeTypeLight::CustomTemp => icon_handle!("thermostat/24"),
+            NodeTypeLight::Smooth => icon_handle!("thermostat/24"),
             NodeTypeLight::Graph => icon_handle!("psychology/24"),
             NodeTypeLight::Flat => icon_handle!("horizontal_rule/24"),
             NodeTypeLight::Linear => icon_handle!("linear/24"),
             NodeTypeLight::Target => icon_handle!("my_location/24"),
+            NodeTypeLight::Pid => icon_handle!("thermostat/24"),
+            NodeTypeLight::Mix => icon_handle!("psychology/24"),
         }
     }};
 }
@@ -24,10 +24,25 @@ impl MyFrom<&str> for Option<u8> {
     }
 }
 
+impl MyFrom<i32> for f64 {
+    fn from(value: i32) -> Self {
+        value.into()
+    }
+}
+
+impl MyFrom<&str> for Option<f64> {
+    fn from(value: &str) -> Self {
+        value.parse::<f64>().ok().filter(|value| value.is_finite())
+    }
+}
+
 #[derive(PartialEq, Eq)]
 pub enum InputLineUnit {
     Celcius,
     Porcentage,
+    Second,
+    /// For a coefficient
+    None,
 }
 
 pub fn input_line<'a, V, F>(
@@ -89,6 +104,8 @@ where
     let unit_text = match unit {
         InputLineUnit::Celcius => " °C",
         InputLineUnit::Porcentage => " %",
+        InputLineUnit::Second => " s",
+        InputLineUnit::None => "",
     };
 
     let icon_lenght = Length::Fixed(30.0);
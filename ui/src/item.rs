@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use cosmic::{
     Element,
@@ -13,13 +13,19 @@ use cosmic::{
 use data::{
     app_graph::Nodes,
     config::{
-        control::Control,
+        command_temp::CommandTemp,
+        control::{Control, ControlUnit},
         custom_temp::{CustomTemp, CustomTempKind},
         flat::Flat,
+        hysteresis::Hysteresis,
         linear::Linear,
+        mix::{Mix, MixKind},
+        pid::Pid,
+        sensor::Sensor,
+        smooth::{Smooth, SmoothKind},
         target::Target,
     },
-    node::{Input, Node, NodeTypeLight, ValueKind},
+    node::{Input, Node, NodeTypeLight, ValueKind, is_control_bound},
 };
 use hardware::{HItem, Hardware};
 
@@ -29,21 +35,26 @@ use crate::{
     icon_button,
     input_line::{InputLineUnit, input_line},
     message::{
-        AppMsg, ControlMsg, CustomTempMsg, FlatMsg, LinearMsg, ModifNodeMsg, TargetMsg, ToogleMsg,
+        AppMsg, ControlMsg, CustomTempMsg, FlatMsg, HysteresisMsg, LinearMsg, MixMsg, ModifNodeMsg,
+        PidMsg, SmoothMsg, TargetMsg, ToogleMsg,
     },
     my_widgets::{self, drop_down::DropDown, offset::Offset},
-    node_cache::{LinearC, NodeC, NodesC, TargetC},
+    node_cache::{HysteresisC, LinearC, MixC, NodeC, NodesC, PidC, SmoothC, TargetC},
     node_icon_handle,
     pick_list_utils::{self, MyOption},
     utils::ApplyMaybe,
 };
-use data::settings::Settings;
+use data::{engine::Job, settings::Settings};
+
+/// Lowest temperature a threshold can take, in °C.
+const ABSOLUTE_ZERO: f64 = -273.0;
 
 pub fn items_view<'a>(
     nodes: &'a Nodes,
     nodes_c: &'a NodesC,
     hardware: &'a Hardware,
     settings: &'a Settings,
+    job: &'a Job,
 ) -> Element<'a, AppMsg> {
     let mut controls = Vec::new();
     let mut behaviors = Vec::new();
@@ -53,17 +64,21 @@ pub fn items_view<'a>(
 
     for node in nodes.values() {
         let node_c = nodes_c.get(&node.id);
-        let content = item_view(node, node_c, nodes, hardware, settings);
+        let content = item_view(node, node_c, nodes, hardware, settings, job);
 
         match node.node_type.to_light() {
             NodeTypeLight::Control => controls.push(content),
             NodeTypeLight::Fan => fans.push(content),
-            NodeTypeLight::Temp => temps.push(content),
+            NodeTypeLight::Temp | NodeTypeLight::CommandTemp | NodeTypeLight::Sensor => {
+                temps.push(content)
+            }
             NodeTypeLight::Graph
             | NodeTypeLight::Flat
             | NodeTypeLight::Linear
-            | NodeTypeLight::Target => behaviors.push(content),
-            NodeTypeLight::CustomTemp => custom_temps.push(content),
+            | NodeTypeLight::Target
+            | NodeTypeLight::Pid
+            | NodeTypeLight::Mix => behaviors.push(content),
+            NodeTypeLight::CustomTemp | NodeTypeLight::Smooth => custom_temps.push(content),
         }
     }
 
@@ -112,6 +127,7 @@ fn item_view<'a>(
     nodes: &'a Nodes,
     hardware: &'a Hardware,
     settings: &'a Settings,
+    job: &'a Job,
 ) -> Element<'a, AppMsg> {
     let item_icon = icon_from_handle(node_icon_handle!(&node.node_type.to_light()));
 
@@ -156,11 +172,16 @@ fn item_view<'a>(
 
     let node_specific_content = match &node.node_type {
         data::node::NodeType::Control(control) => {
-            control_view(node, control, nodes, hardware, settings)
+            control_view(node, control, nodes, hardware, settings, job)
         }
         data::node::NodeType::Fan(_fan) => fan_view(node, hardware),
         data::node::NodeType::Temp(_temp) => temp_view(node, hardware),
+        data::node::NodeType::Sensor(sensor) => sensor_view(node, sensor, hardware),
+        data::node::NodeType::CommandTemp(command_temp) => command_temp_view(node, command_temp),
         data::node::NodeType::CustomTemp(custom_temp) => custom_temp_view(node, custom_temp, nodes),
+        data::node::NodeType::Smooth(smooth) => {
+            smooth_view(node, smooth, node_c.node_type_c.unwrap_smooth_ref(), nodes)
+        }
         data::node::NodeType::Graph(graph) => {
             graph_view(node, graph, node_c.node_type_c.unwrap_graph_ref(), nodes)
         }
@@ -171,6 +192,12 @@ fn item_view<'a>(
         data::node::NodeType::Target(target) => {
             target_view(node, target, node_c.node_type_c.unwrap_target_ref(), nodes)
         }
+        data::node::NodeType::Pid(pid) => {
+            pid_view(node, pid, node_c.node_type_c.unwrap_pid_ref(), nodes)
+        }
+        data::node::NodeType::Mix(mix) => {
+            mix_view(node, mix, node_c.node_type_c.unwrap_mix_ref(), nodes)
+        }
     };
 
     let content = Column::new()
@@ -188,12 +215,14 @@ fn item_view<'a>(
 
 fn pick_hardware<'a, H: HItem>(
     node: &'a Node,
-    hardwares: &'a [Rc<H>],
-    one_ref: bool,
+    hardwares: &'a [Arc<H>],
+    bound_controls: Option<&'a Nodes>,
 ) -> Element<'a, AppMsg> {
     let hardware_id = node.hardware_id().clone();
     let (selected_hardware_info, input_hardware) =
-        pick_list_utils::hardware::availlable_hardware(&hardware_id, hardwares, one_ref);
+        pick_list_utils::hardware::availlable_hardware(&hardware_id, hardwares, |id| {
+            bound_controls.is_some_and(|nodes| is_control_bound(nodes, id))
+        });
 
     PickList::new(input_hardware, Some(selected_hardware_info), |selected| {
         let message_content = match selected {
@@ -213,22 +242,39 @@ fn control_view<'a>(
     nodes: &'a Nodes,
     hardware: &'a Hardware,
     settings: &'a Settings,
+    job: &'a Job,
 ) -> Element<'a, AppMsg> {
     let input_options =
         pick_list_utils::input::optional_availlable_inputs(nodes, node, control.input.is_some());
     let current_input: MyOption<Input> = control.input.clone().into();
 
+    let value_kind = match control.unit {
+        ControlUnit::Percent => ValueKind::Porcentage,
+        ControlUnit::Raw => ValueKind::Raw,
+    };
+
     let pick_input = PickList::new(input_options, Some(current_input), |input| {
         ModifNodeMsg::ReplaceInput(input.into()).to_app(node.id)
     })
     .width(Length::Fill)
     .into();
 
-    let content = vec![
-        pick_hardware(node, &hardware.controls, true),
+    let (selected_fan, fan_options) =
+        pick_list_utils::hardware::availlable_hardware(&control.fan, &hardware.fans, |_| false);
+    let pick_fan = PickList::new(fan_options, Some(selected_fan), |selected| {
+        let fan = match selected {
+            MyOption::Some(selected) => Some(selected.id),
+            MyOption::None => None,
+        };
+        ModifNodeMsg::Control(ControlMsg::Fan(fan)).to_app(node.id)
+    })
+    .width(Length::Fill);
+
+    let mut content = vec![
+        pick_hardware(node, &hardware.controls, Some(nodes)),
         pick_input,
         Row::new()
-            .push(Text::new(node.value_text(&ValueKind::Porcentage)))
+            .push(Text::new(node.value_text(&value_kind)))
             .push(Space::new(Length::Fill, Length::Fixed(0.0)))
             .push(
                 Toggler::new(control.active).apply_maybe(!settings.inactive, |toggler| {
@@ -242,21 +288,110 @@ fn control_view<'a>(
             .into(),
     ];
 
+    if let Some(calibration) = &control.calibration {
+        content.push(
+            Text::new(fl!(
+                "calibrated",
+                stay = calibration.min_stay,
+                max = calibration.max_effective,
+                start = calibration.min_start
+            ))
+            .into(),
+        );
+    }
+
+    let status = match *job {
+        Job::Calibration { id, phase, value } if id == node.id => {
+            Some(fl!("calibrating", phase = phase.to_string(), value = value))
+        }
+        Job::Pairing(Some(id)) if id == node.id => Some(fl!("detecting_fan")),
+        _ => None,
+    };
+
+    match status {
+        Some(status) => content.push(
+            Row::new()
+                .push(Text::new(status))
+                .push(Space::new(Length::Fill, Length::Fixed(0.0)))
+                .push(
+                    Button::new(Text::new(fl!("abort_calibration")))
+                        .on_press(AppMsg::AbortCalibration),
+                )
+                .align_y(Alignment::Center)
+                .width(Length::Fill)
+                .into(),
+        ),
+        None => {
+            let can_run = !job.is_running() && control.control_h.is_some();
+
+            content.push(
+                Row::new()
+                    .push(pick_fan)
+                    .push(
+                        Button::new(Text::new(fl!("detect_fan")))
+                            .on_press_maybe(can_run.then_some(AppMsg::DetectFan(node.id))),
+                    )
+                    .align_y(Alignment::Center)
+                    .spacing(5)
+                    .width(Length::Fill)
+                    .into(),
+            );
+            content.push(
+                Button::new(Text::new(fl!("calibrate")))
+                    .on_press_maybe(can_run.then_some(AppMsg::Calibrate(node.id)))
+                    .into(),
+            );
+        }
+    }
+
     Column::with_children(content).into()
 }
 
 fn fan_view<'a>(node: &'a Node, hardware: &'a Hardware) -> Element<'a, AppMsg> {
-    let content = vec![
-        pick_hardware(node, &hardware.fans, false),
+    let mut content = vec![
+        pick_hardware(node, &hardware.fans, None),
         Text::new(node.value_text(&ValueKind::RPM)).into(),
     ];
 
+    if let Some(limits) = node.limits_text(&ValueKind::RPM) {
+        content.push(Text::new(limits).into());
+    }
+
     Column::with_children(content).into()
 }
 
 fn temp_view<'a>(node: &'a Node, hardware: &'a Hardware) -> Element<'a, AppMsg> {
+    let mut content = vec![
+        pick_hardware(node, &hardware.temps, None),
+        Text::new(node.value_text(&ValueKind::Celsius)).into(),
+    ];
+
+    if let Some(limits) = node.limits_text(&ValueKind::Celsius) {
+        content.push(Text::new(limits).into());
+    }
+
+    Column::with_children(content).into()
+}
+
+fn sensor_view<'a>(node: &'a Node, sensor: &Sensor, hardware: &'a Hardware) -> Element<'a, AppMsg> {
+    let mut content = vec![pick_hardware(node, &hardware.sensors, None)];
+
+    // the unit is unknown until the node is bound
+    if let Some(kind) = sensor.kind() {
+        let value_kind = ValueKind::from(kind);
+        content.push(Text::new(node.value_text(&value_kind)).into());
+
+        if let Some(limits) = node.limits_text(&value_kind) {
+            content.push(Text::new(limits).into());
+        }
+    }
+
+    Column::with_children(content).into()
+}
+
+fn command_temp_view<'a>(node: &'a Node, command_temp: &'a CommandTemp) -> Element<'a, AppMsg> {
     let content = vec![
-        pick_hardware(node, &hardware.temps, false),
+        Text::new(command_temp.command.join(" ")).into(),
         Text::new(node.value_text(&ValueKind::Celsius)).into(),
     ];
 
@@ -316,6 +451,66 @@ fn custom_temp_view<'a>(
     Column::with_children(content).into()
 }
 
+fn smooth_view<'a>(
+    node: &'a Node,
+    smooth: &'a Smooth,
+    smooth_c: &'a SmoothC,
+    nodes: &'a Nodes,
+) -> Element<'a, AppMsg> {
+    let kind_options = SmoothKind::VALUES
+        .iter()
+        .filter(|k| &smooth.kind != *k)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let pick_kind = PickList::new(kind_options, Some(smooth.kind), |k| {
+        ModifNodeMsg::Smooth(SmoothMsg::Kind(k)).to_app(node.id)
+    })
+    .width(Length::Fill)
+    .into();
+
+    let input_options =
+        pick_list_utils::input::optional_availlable_inputs(nodes, node, smooth.input.is_some());
+    let current_input: MyOption<Input> = smooth.input.clone().into();
+    let pick_input = PickList::new(input_options, Some(current_input), |input| {
+        ModifNodeMsg::ReplaceInput(input.into()).to_app(node.id)
+    })
+    .width(Length::Fill)
+    .into();
+
+    let mut content = vec![
+        pick_kind,
+        pick_input,
+        input_line(
+            fl!("window"),
+            &smooth.window,
+            &smooth_c.window,
+            InputLineUnit::Second,
+            0.0..3600.0,
+            |val, cached_val| ModifNodeMsg::Smooth(SmoothMsg::Window(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+    ];
+
+    if smooth.kind == SmoothKind::Percentile {
+        content.push(
+            input_line(
+                fl!("percentile"),
+                &smooth.percentile,
+                &smooth_c.percentile,
+                InputLineUnit::Porcentage,
+                0.0..100.0,
+                |val, cached_val| ModifNodeMsg::Smooth(SmoothMsg::Percentile(val, cached_val)),
+            )
+            .map(|m| m.to_app(node.id)),
+        );
+    }
+
+    content.push(Text::new(node.value_text(&ValueKind::Celsius)).into());
+
+    Column::with_children(content).into()
+}
+
 fn flat_view<'a>(node: &'a Node, flat: &'a Flat) -> Element<'a, AppMsg> {
     let mut sub_button = icon_button!("remove/24");
     if flat.value > 0 {
@@ -374,7 +569,7 @@ fn linear_view<'a>(
             &linear.min_temp,
             &linear_c.min_temp,
             InputLineUnit::Celcius,
-            0..linear.max_temp,
+            ABSOLUTE_ZERO..linear.max_temp,
             |val, cached_val| ModifNodeMsg::Linear(LinearMsg::MinTemp(val, cached_val)),
         )
         .map(|m| m.to_app(node.id)),
@@ -392,7 +587,7 @@ fn linear_view<'a>(
             &linear.max_temp,
             &linear_c.max_temp,
             InputLineUnit::Celcius,
-            linear.min_temp..255,
+            linear.min_temp..255.0,
             |val, cached_val| ModifNodeMsg::Linear(LinearMsg::MaxTemp(val, cached_val)),
         )
         .map(|m| m.to_app(node.id)),
@@ -405,11 +600,48 @@ fn linear_view<'a>(
             |val, cached_val| ModifNodeMsg::Linear(LinearMsg::MaxSpeed(val, cached_val)),
         )
         .map(|m| m.to_app(node.id)),
+        hysteresis_view(node, &linear.hysteresis, &linear_c.hysteresis),
     ];
 
     Column::with_children(content).into()
 }
 
+/// Deadband and falling offset, of a graph or a linear behavior.
+pub fn hysteresis_view<'a>(
+    node: &'a Node,
+    hysteresis: &'a Hysteresis,
+    hysteresis_c: &'a HysteresisC,
+) -> Element<'a, AppMsg> {
+    Column::new()
+        .push(
+            input_line(
+                fl!("deadband"),
+                &hysteresis.deadband,
+                &hysteresis_c.deadband,
+                InputLineUnit::Celcius,
+                0.0..50.0,
+                |val, cached_val| {
+                    ModifNodeMsg::Hysteresis(HysteresisMsg::Deadband(val, cached_val))
+                },
+            )
+            .map(|m| m.to_app(node.id)),
+        )
+        .push(
+            input_line(
+                fl!("falling_offset"),
+                &hysteresis.falling_offset,
+                &hysteresis_c.falling_offset,
+                InputLineUnit::Celcius,
+                0.0..50.0,
+                |val, cached_val| {
+                    ModifNodeMsg::Hysteresis(HysteresisMsg::FallingOffset(val, cached_val))
+                },
+            )
+            .map(|m| m.to_app(node.id)),
+        )
+        .into()
+}
+
 fn target_view<'a>(
     node: &'a Node,
     target: &'a Target,
@@ -433,7 +665,7 @@ fn target_view<'a>(
             &target.idle_temp,
             &target_c.idle_temp,
             InputLineUnit::Celcius,
-            0..255,
+            ABSOLUTE_ZERO..255.0,
             |val, cached_val| ModifNodeMsg::Target(TargetMsg::IdleTemp(val, cached_val)),
         )
         .map(|m| m.to_app(node.id)),
@@ -451,7 +683,7 @@ fn target_view<'a>(
             &target.load_temp,
             &target_c.load_temp,
             InputLineUnit::Celcius,
-            0..255,
+            ABSOLUTE_ZERO..255.0,
             |val, cached_val| ModifNodeMsg::Target(TargetMsg::LoadTemp(val, cached_val)),
         )
         .map(|m| m.to_app(node.id)),
@@ -468,3 +700,154 @@ fn target_view<'a>(
 
     Column::with_children(content).into()
 }
+
+fn pid_view<'a>(
+    node: &'a Node,
+    pid: &'a Pid,
+    pid_c: &'a PidC,
+    nodes: &'a Nodes,
+) -> Element<'a, AppMsg> {
+    let input_options =
+        pick_list_utils::input::optional_availlable_inputs(nodes, node, pid.input.is_some());
+    let current_input: MyOption<Input> = pid.input.clone().into();
+    let pick_input = PickList::new(input_options, Some(current_input), |input| {
+        ModifNodeMsg::ReplaceInput(input.into()).to_app(node.id)
+    })
+    .width(Length::Fill)
+    .into();
+
+    let content = vec![
+        pick_input,
+        Text::new(node.value_text(&ValueKind::Porcentage)).into(),
+        input_line(
+            fl!("setpoint"),
+            &pid.setpoint,
+            &pid_c.setpoint,
+            InputLineUnit::Celcius,
+            ABSOLUTE_ZERO..255.0,
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::Setpoint(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        input_line(
+            fl!("kp"),
+            &pid.kp,
+            &pid_c.kp,
+            InputLineUnit::None,
+            0.0..1000.0,
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::Kp(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        input_line(
+            fl!("ki"),
+            &pid.ki,
+            &pid_c.ki,
+            InputLineUnit::None,
+            0.0..1000.0,
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::Ki(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        input_line(
+            fl!("kd"),
+            &pid.kd,
+            &pid_c.kd,
+            InputLineUnit::None,
+            0.0..1000.0,
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::Kd(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        input_line(
+            fl!("min_speed"),
+            &pid.min_speed,
+            &pid_c.min_speed,
+            InputLineUnit::Porcentage,
+            0..pid.max_speed,
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::MinSpeed(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+        input_line(
+            fl!("max_speed"),
+            &pid.max_speed,
+            &pid_c.max_speed,
+            InputLineUnit::Porcentage,
+            pid.min_speed..101,
+            |val, cached_val| ModifNodeMsg::Pid(PidMsg::MaxSpeed(val, cached_val)),
+        )
+        .map(|m| m.to_app(node.id)),
+    ];
+
+    Column::with_children(content).into()
+}
+
+fn mix_view<'a>(
+    node: &'a Node,
+    mix: &'a Mix,
+    mix_c: &'a MixC,
+    nodes: &'a Nodes,
+) -> Element<'a, AppMsg> {
+    let kind_options = MixKind::VALUES
+        .iter()
+        .filter(|k| &mix.kind != *k)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let pick_kind = PickList::new(kind_options, Some(mix.kind), |k| {
+        ModifNodeMsg::Mix(MixMsg::Kind(k)).to_app(node.id)
+    })
+    .width(Length::Fill)
+    .into();
+
+    let inputs = node.inputs.iter().enumerate().map(|(index, input)| {
+        let row = Row::new()
+            .push(Text::new(input.name.clone()).width(Length::Fixed(100.0)))
+            .push(Space::new(Length::Fill, Length::Fixed(0.0)))
+            .push(
+                icon_button!("close/20")
+                    .on_press(ModifNodeMsg::RemoveInput(input.clone()).to_app(node.id)),
+            )
+            .align_y(Alignment::Center);
+
+        let mut column = Column::new().push(row);
+
+        // the cache is rebuilt when an input is added or removed
+        if let (MixKind::WeightedSum, Some(weight_c)) = (mix.kind, mix_c.weights.get(index)) {
+            column = column.push(
+                input_line(
+                    fl!("weight"),
+                    mix.weights.get(index).unwrap_or(&1.0),
+                    weight_c,
+                    InputLineUnit::None,
+                    -100.0..100.0,
+                    move |val, cached_val| {
+                        ModifNodeMsg::Mix(MixMsg::Weight(index, val, cached_val))
+                    },
+                )
+                .map(|m| m.to_app(node.id)),
+            );
+        }
+
+        column.into()
+    });
+
+    let input_options: Vec<Input> =
+        pick_list_utils::input::availlable_inputs(nodes, node).collect();
+
+    let current_input = Input {
+        id: Default::default(),
+        name: fl!("behavior_selection"),
+    };
+
+    let pick_input = PickList::new(input_options, Some(current_input), |input| {
+        ModifNodeMsg::AddInput(input).to_app(node.id)
+    })
+    .width(Length::Fill)
+    .into();
+
+    let content = vec![
+        pick_kind,
+        pick_input,
+        Column::with_children(inputs).into(),
+        Text::new(node.value_text(&ValueKind::Porcentage)).into(),
+    ];
+
+    Column::with_children(content).into()
+}
@@ -3,16 +3,17 @@ use std::{collections::HashMap, path::PathBuf, time::Duration};
 use data::{
     AppState,
     config::Config,
-    node::{IsValid, NodeType, validate_name},
+    engine::{Command, Event, Frontend},
+    node::{NodeType, validate_name},
     settings::AppTheme,
     utils::RemoveElem,
 };
 use drawer::{Drawer, about};
 use graph::GraphWindow;
-use hardware::{HardwareBridge, Mode};
+use hardware::HardwareBridge;
 use item::items_view;
 use message::{ModifNodeMsg, SettingsMsg, ToogleMsg};
-use node_cache::{NodeC, NodesC};
+use node_cache::{NodeC, NodeTypeC, NodesC};
 
 use crate::{drawer::settings_drawer, graph::graph_window_view, message::NavBarContextMenuMsg};
 
@@ -36,7 +37,10 @@ use cosmic::{
     },
 };
 
-use crate::message::{AppMsg, ControlMsg, CustomTempMsg, FlatMsg, LinearMsg, TargetMsg};
+use crate::message::{
+    AppMsg, ControlMsg, CustomTempMsg, FlatMsg, HysteresisMsg, LinearMsg, MixMsg, PidMsg,
+    SmoothMsg, TargetMsg,
+};
 
 use crate::add_node::add_node_button_view;
 use crate::config_dialogs::{
@@ -122,7 +126,7 @@ enum NavModelData {
 
 struct Ui<H: HardwareBridge> {
     core: Core,
-    app_state: AppState<H>,
+    frontend: Frontend<H>,
     create_button_expanded: bool,
     nodes_c: NodesC,
     graph_window: Option<GraphWindow>,
@@ -133,6 +137,8 @@ struct Ui<H: HardwareBridge> {
     #[cfg(not(target_os = "linux"))]
     tray: Option<(tray::SystemTray, tray::SystemTrayStream)>,
     main_window: Option<window::Id>,
+    /// Show the result of the next rescan, even when nothing changed
+    rescan_requested: bool,
 }
 
 impl<H: HardwareBridge> Ui<H> {
@@ -155,19 +161,23 @@ impl<H: HardwareBridge> Ui<H> {
         Task::batch(commands)
     }
 
+    /// Stop the calibration or the fan detection, if one is running.
+    fn abort_calibration(&mut self) {
+        self.frontend.engine.send(Command::AbortCalibration);
+    }
+
     fn on_exit(&mut self) {
-        if let Err(e) = self.app_state.bridge.shutdown() {
-            error!("shutdown hardware: {e}");
-        }
+        // the calibration is aborted too
+        self.frontend.engine.stop();
 
-        let runtime_config = Config::from_app_graph(&self.app_state.app_graph);
+        let runtime_config = Config::from_app_graph(&self.frontend.app_graph);
 
-        if match self.app_state.dir_manager.get_config() {
+        if match self.frontend.dir_manager.get_config() {
             Some(saved_config) => saved_config != runtime_config,
             None => true,
         } {
             if let Err(err) = self
-                .app_state
+                .frontend
                 .dir_manager
                 .save_config_cached(&runtime_config)
             {
@@ -175,7 +185,7 @@ impl<H: HardwareBridge> Ui<H> {
             } else {
                 info!("cached config saved successfully");
             }
-        } else if let Err(err) = self.app_state.dir_manager.remove_config_cached() {
+        } else if let Err(err) = self.frontend.dir_manager.remove_config_cached() {
             error!("{err}")
         }
     }
@@ -197,10 +207,10 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
     }
 
     fn init(core: Core, flags: Self::Flags) -> (Self, Task<Self::Message>) {
-        let app_state = flags.app_state;
+        let frontend = Frontend::new(flags.app_state, true);
 
         let dialog = if cfg!(FAN_CONTROL_FORMAT = "flatpak")
-            && app_state.dir_manager.state().show_flatpak_dialog
+            && frontend.dir_manager.state().show_flatpak_dialog
         {
             Some(Dialog::Udev)
         } else {
@@ -208,8 +218,8 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
         };
 
         let mut ui_state = Ui {
-            nodes_c: NodesC::new(app_state.app_graph.nodes.values()),
-            app_state,
+            nodes_c: NodesC::new(frontend.app_graph.nodes.values()),
+            frontend,
             core,
             create_button_expanded: false,
             graph_window: None,
@@ -226,6 +236,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                 }
             },
             main_window: None,
+            rescan_requested: false,
         };
 
         ui_state.reload_nav_bar_model();
@@ -234,7 +245,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
         let mut commands = vec![];
         commands.push(cosmic::task::message(AppMsg::Tick));
 
-        if !ui_state.app_state.dir_manager.settings().start_minimized {
+        if !ui_state.frontend.dir_manager.settings().start_minimized {
             commands.push(ui_state.open_main_window());
         }
 
@@ -242,32 +253,58 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
     }
 
     fn update(&mut self, message: Self::Message) -> Task<Self::Message> {
-        let dir_manager = &mut self.app_state.dir_manager;
+        let dir_manager = &mut self.frontend.dir_manager;
 
         match message {
             AppMsg::Tick => {
-                self.update_hardware();
+                let mut messages = Vec::new();
+
+                for event in self.frontend.receive() {
+                    match event {
+                        Event::Rescanned { diff, .. } => {
+                            self.nodes_c = NodesC::new(self.frontend.app_graph.nodes.values());
+                            if self.rescan_requested || !diff.is_empty() {
+                                messages.push(fl!(
+                                    "hardware_rescanned",
+                                    added = diff.added.len(),
+                                    removed = diff.removed.len()
+                                ));
+                            }
+                            self.rescan_requested = false;
+                        }
+                        Event::CalibrationStarted { .. } => {}
+                        Event::Calibrated { name, result, .. } => messages.push(match result {
+                            Ok(_) => fl!("calibration_done", name = name),
+                            Err(e) => fl!("calibration_failed", name = name, error = e.to_string()),
+                        }),
+                        Event::FansDetected(pairs) => {
+                            messages.extend(pairs.into_iter().map(|pair| match pair.fan {
+                                Some(fan) => fl!("fan_detected", control = pair.control, fan = fan),
+                                None => fl!("no_fan_detected", control = pair.control),
+                            }))
+                        }
+                    }
+                }
+
+                let tasks = messages.into_iter().map(|message| {
+                    self.toasts
+                        .push(Toast::new(message))
+                        .map(cosmic::action::app)
+                });
+                return Task::batch(tasks.collect::<Vec<_>>());
             }
             AppMsg::ModifNode(id, modif_node_msg) => {
-                let node = self.app_state.app_graph.get_mut(&id);
+                let node = self.frontend.app_graph.get_mut(&id);
                 match modif_node_msg {
                     ModifNodeMsg::ChangeHardware(hardware_id) => {
-                        let bridge = &mut self.app_state.bridge;
+                        // the engine sets the previous control back to auto
+                        let hardware = &self.frontend.hardware;
 
                         match &mut node.node_type {
                             NodeType::Control(i) => {
-                                if i.is_valid()
-                                    && let Err(e) = i.set_mode(Mode::Auto, bridge)
-                                {
-                                    error!(
-                                        "Can't set control to auto when removing his hardware ref: {e}."
-                                    );
-                                }
-
                                 i.hardware_id = hardware_id;
                                 i.control_h = match &i.hardware_id {
-                                    Some(hardware_id) => bridge
-                                        .hardware()
+                                    Some(hardware_id) => hardware
                                         .controls
                                         .iter()
                                         .find(|h| &h.hardware_id == hardware_id)
@@ -279,8 +316,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             NodeType::Fan(i) => {
                                 i.hardware_id = hardware_id;
                                 i.fan_h = match &i.hardware_id {
-                                    Some(hardware_id) => bridge
-                                        .hardware()
+                                    Some(hardware_id) => hardware
                                         .fans
                                         .iter()
                                         .find(|h| &h.hardware_id == hardware_id)
@@ -292,8 +328,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             NodeType::Temp(i) => {
                                 i.hardware_id = hardware_id;
                                 i.temp_h = match &i.hardware_id {
-                                    Some(hardware_id) => bridge
-                                        .hardware()
+                                    Some(hardware_id) => hardware
                                         .temps
                                         .iter()
                                         .find(|h| &h.hardware_id == hardware_id)
@@ -302,6 +337,18 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                                     None => None,
                                 }
                             }
+                            NodeType::Sensor(i) => {
+                                i.hardware_id = hardware_id;
+                                i.sensor_h = match &i.hardware_id {
+                                    Some(hardware_id) => hardware
+                                        .sensors
+                                        .iter()
+                                        .find(|h| &h.hardware_id == hardware_id)
+                                        .cloned(),
+
+                                    None => None,
+                                }
+                            }
                             _ => panic!("node have no hardware id"),
                         }
                     }
@@ -318,17 +365,28 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                         };
                         match &mut node.node_type {
                             NodeType::Control(i) => i.input = optional_name,
+                            NodeType::Smooth(i) => i.input = optional_name,
                             NodeType::Graph(i) => i.input = optional_name,
                             NodeType::Linear(i, ..) => i.input = optional_name,
                             NodeType::Target(i, ..) => i.input = optional_name,
+                            NodeType::Pid(i) => i.input = optional_name,
                             _ => panic!("node have not exactly one input"),
                         }
+
+                        self.frontend.app_graph.fit_graph_to_input(&id);
                     }
                     ModifNodeMsg::AddInput(input) => {
                         node.inputs.push(input.clone());
 
                         match &mut node.node_type {
                             NodeType::CustomTemp(i) => i.inputs.push(input.name),
+                            NodeType::Mix(i) => {
+                                let mut inputs = i.inputs.clone();
+                                inputs.push(input.name);
+                                i.set_inputs(inputs);
+                                self.nodes_c.get_mut(&id).node_type_c =
+                                    NodeTypeC::new(&node.node_type);
+                            }
                             _ => panic!("node have not multiple inputs"),
                         }
                     }
@@ -339,6 +397,13 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             NodeType::CustomTemp(i) => {
                                 i.inputs.remove_elem(|n| n == &input.name);
                             }
+                            NodeType::Mix(i) => {
+                                let mut inputs = i.inputs.clone();
+                                inputs.remove_elem(|n| n == &input.name);
+                                i.set_inputs(inputs);
+                                self.nodes_c.get_mut(&id).node_type_c =
+                                    NodeTypeC::new(&node.node_type);
+                            }
                             _ => panic!("node have not multiple inputs"),
                         }
                     }
@@ -347,6 +412,10 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             let control = node.node_type.unwrap_control_mut();
                             control.active = is_active;
                         }
+                        ControlMsg::Fan(fan) => {
+                            let control = node.node_type.unwrap_control_mut();
+                            control.fan = fan;
+                        }
                     },
                     ModifNodeMsg::CustomTemp(custom_temp_msg) => match custom_temp_msg {
                         CustomTempMsg::Kind(kind) => {
@@ -354,6 +423,24 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             custom_temp.kind = kind;
                         }
                     },
+                    ModifNodeMsg::Smooth(smooth_msg) => {
+                        let smooth = node.node_type.unwrap_smooth_mut();
+                        let smooth_c = self.nodes_c.get_mut(&id).node_type_c.unwrap_smooth_mut();
+
+                        match smooth_msg {
+                            SmoothMsg::Kind(kind) => {
+                                smooth.kind = kind;
+                            }
+                            SmoothMsg::Window(window, cached_value) => {
+                                smooth.window = window;
+                                smooth_c.window = cached_value;
+                            }
+                            SmoothMsg::Percentile(percentile, cached_value) => {
+                                smooth.percentile = percentile;
+                                smooth_c.percentile = cached_value;
+                            }
+                        }
+                    }
                     ModifNodeMsg::Flat(flat_msg) => match flat_msg {
                         FlatMsg::Value(value) => {
                             let flat = node.node_type.unwrap_flat_mut();
@@ -407,21 +494,83 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             }
                         }
                     }
-                    ModifNodeMsg::Delete => {
-                        match self.app_state.app_graph.remove_node(id) {
-                            Some(mut node) => {
-                                if let NodeType::Control(control) = &mut node.node_type
-                                    && let Err(e) =
-                                        control.set_mode(Mode::Auto, &mut self.app_state.bridge)
-                                {
-                                    error!("can't set unactive when removing a control: {e}");
+                    ModifNodeMsg::Mix(mix_msg) => {
+                        let mix = node.node_type.unwrap_mix_mut();
+                        let mix_c = self.nodes_c.get_mut(&id).node_type_c.unwrap_mix_mut();
+
+                        match mix_msg {
+                            MixMsg::Kind(kind) => {
+                                mix.kind = kind;
+                            }
+                            MixMsg::Weight(index, weight, cached_value) => {
+                                mix.set_weight(index, weight);
+                                if let Some(weight_c) = mix_c.weights.get_mut(index) {
+                                    *weight_c = cached_value;
                                 }
                             }
-                            None => error!("Node was not found when trying to remove it"),
+                        }
+                    }
+                    ModifNodeMsg::Pid(pid_msg) => {
+                        let pid = node.node_type.unwrap_pid_mut();
+                        let pid_c = self.nodes_c.get_mut(&id).node_type_c.unwrap_pid_mut();
+
+                        match pid_msg {
+                            PidMsg::Setpoint(setpoint, cached_value) => {
+                                pid.setpoint = setpoint;
+                                pid_c.setpoint = cached_value;
+                            }
+                            PidMsg::Kp(kp, cached_value) => {
+                                pid.kp = kp;
+                                pid_c.kp = cached_value;
+                            }
+                            PidMsg::Ki(ki, cached_value) => {
+                                pid.ki = ki;
+                                pid_c.ki = cached_value;
+                            }
+                            PidMsg::Kd(kd, cached_value) => {
+                                pid.kd = kd;
+                                pid_c.kd = cached_value;
+                            }
+                            PidMsg::MinSpeed(min_speed, cached_value) => {
+                                pid.min_speed = min_speed;
+                                pid_c.min_speed = cached_value;
+                            }
+                            PidMsg::MaxSpeed(max_speed, cached_value) => {
+                                pid.max_speed = max_speed;
+                                pid_c.max_speed = cached_value;
+                            }
+                        }
+                    }
+                    ModifNodeMsg::Hysteresis(hysteresis_msg) => {
+                        let hysteresis = match &mut node.node_type {
+                            NodeType::Graph(graph) => &mut graph.hysteresis,
+                            NodeType::Linear(linear) => &mut linear.hysteresis,
+                            _ => panic!("node have no hysteresis"),
+                        };
+                        let hysteresis_c = match &mut self.nodes_c.get_mut(&id).node_type_c {
+                            NodeTypeC::Graph(graph_c) => &mut graph_c.hysteresis,
+                            NodeTypeC::Linear(linear_c) => &mut linear_c.hysteresis,
+                            _ => panic!("node cache have no hysteresis"),
+                        };
+
+                        match hysteresis_msg {
+                            HysteresisMsg::Deadband(deadband, cached_value) => {
+                                hysteresis.deadband = deadband;
+                                hysteresis_c.deadband = cached_value;
+                            }
+                            HysteresisMsg::FallingOffset(falling_offset, cached_value) => {
+                                hysteresis.falling_offset = falling_offset;
+                                hysteresis_c.falling_offset = cached_value;
+                            }
+                        }
+                    }
+                    ModifNodeMsg::Delete => {
+                        if self.frontend.app_graph.remove_node(id).is_none() {
+                            error!("Node was not found when trying to remove it");
                         }
 
                         self.nodes_c.remove(&id);
-                        self.app_state.app_graph.sanitize_inputs(false)
+                        self.frontend.app_graph.sanitize_inputs(false)
                     }
                     ModifNodeMsg::Graph(graph_msg) => {
                         let graph = node.node_type.unwrap_graph_mut();
@@ -441,11 +590,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                     }
                 }
 
-                self.app_state.update.set_invalid_root_nodes_to_auto(
-                    &mut self.app_state.app_graph.nodes,
-                    &self.app_state.app_graph.root_nodes,
-                    &mut self.app_state.bridge,
-                );
+                self.frontend.send_graph();
             }
             AppMsg::Settings(settings_msg) => match settings_msg {
                 SettingsMsg::Theme(theme) => {
@@ -455,11 +600,14 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
 
                     return cosmic::command::set_theme(to_cosmic_theme(&theme));
                 }
-                SettingsMsg::UpdateDelay(update_delay) => dir_manager.update_settings(|settings| {
-                    settings.update_delay = update_delay;
-                }),
+                SettingsMsg::UpdateDelay(update_delay) => {
+                    dir_manager.update_settings(|settings| {
+                        settings.update_delay = update_delay;
+                    });
+                    self.frontend.send_settings();
+                }
                 SettingsMsg::StartAtLogin(start_at_login) => {
-                    start_at_login::start_at_login(start_at_login, &mut self.app_state.dir_manager);
+                    start_at_login::start_at_login(start_at_login, &mut self.frontend.dir_manager);
                 }
                 SettingsMsg::Inactive(inactive) => self.set_inactive(inactive),
                 SettingsMsg::StartMinimized(start_minimized) => {
@@ -469,10 +617,11 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                 }
             },
             AppMsg::NewNode(node_type_light) => {
-                let node = self.app_state.app_graph.create_new_node(node_type_light);
+                let node = self.frontend.app_graph.create_new_node(node_type_light);
                 let node_c = NodeC::new(&node);
                 self.nodes_c.insert(node.id, node_c);
-                self.app_state.app_graph.insert_node(node);
+                self.frontend.app_graph.insert_node(node);
+                self.frontend.send_graph();
             }
             AppMsg::Toggle(ui_msg) => match ui_msg {
                 ToogleMsg::CreateButton(expanded) => self.create_button_expanded = expanded,
@@ -506,10 +655,25 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                 }
             },
             AppMsg::SaveConfig(name) => return self.save_config(&name),
+            AppMsg::Calibrate(id) => {
+                if !self.frontend.job.is_running() {
+                    self.frontend.engine.send(Command::Calibrate(vec![id]));
+                }
+            }
+            AppMsg::DetectFan(id) => {
+                if !self.frontend.job.is_running() {
+                    self.frontend.engine.send(Command::DetectFans(vec![id]));
+                }
+            }
+            AppMsg::AbortCalibration => self.abort_calibration(),
+            AppMsg::Rescan => {
+                self.rescan_requested = true;
+                self.frontend.engine.send(Command::Rescan);
+            }
             AppMsg::Rename(id, name) => {
-                let name_is_valid = validate_name(&self.app_state.app_graph.nodes, &id, &name);
+                let name_is_valid = validate_name(&self.frontend.app_graph.nodes, &id, &name);
 
-                let node = self.app_state.app_graph.get_mut(&id);
+                let node = self.frontend.app_graph.get_mut(&id);
                 let node_c = self.nodes_c.get_mut(&id);
 
                 node_c.name.clone_from(&name);
@@ -522,7 +686,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
 
                     // find nodes that depend on node.id
                     // change the name in input and item.input
-                    for n in self.app_state.app_graph.nodes.values_mut() {
+                    for n in self.frontend.app_graph.nodes.values_mut() {
                         if let Some(node_input) = n
                             .inputs
                             .iter_mut()
@@ -544,6 +708,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                             }
                         }
                     }
+                    self.frontend.send_graph();
                 } else {
                     node_c.is_error_name = true;
                 }
@@ -673,7 +838,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
                     self.change_config(Some(name));
                 }
                 SystemTrayMsg::Inactive => {
-                    self.set_inactive(!self.app_state.dir_manager.settings().inactive);
+                    self.set_inactive(!self.frontend.dir_manager.settings().inactive);
                 }
                 SystemTrayMsg::Exit => {
                     self.on_exit();
@@ -708,14 +873,14 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
         if let Some(main_window) = &self.main_window
             && main_window == &id
         {
-            let app_state = &self.app_state;
-            let app_graph = &app_state.app_graph;
+            let frontend = &self.frontend;
 
             let content = items_view(
-                &app_graph.nodes,
+                &frontend.app_graph.nodes,
                 &self.nodes_c,
-                app_state.bridge.hardware(),
-                app_state.dir_manager.settings(),
+                &frontend.hardware,
+                frontend.dir_manager.settings(),
+                &frontend.job,
             );
 
             let floating_button = Column::new()
@@ -731,7 +896,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
             && graph_window.window_id == id
         {
             let graph = self
-                .app_state
+                .frontend
                 .app_graph
                 .get(&graph_window.node_id)
                 .node_type
@@ -748,7 +913,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
     }
 
     fn header_end(&self) -> Vec<Element<'_, Self::Message>> {
-        headers::header_end(&self.app_state)
+        headers::header_end(&self.frontend.dir_manager)
     }
 
     fn nav_model(&self) -> Option<&nav_bar::Model> {
@@ -805,7 +970,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
     fn context_drawer(&self) -> Option<ContextDrawer<'_, Self::Message>> {
         self.drawer.as_ref().map(|drawer| match drawer {
             Drawer::Settings => context_drawer(
-                settings_drawer(&self.app_state.dir_manager),
+                settings_drawer(&self.frontend.dir_manager),
                 AppMsg::Toggle(ToogleMsg::CloseDrawer),
             )
             .title(fl!("settings")),
@@ -821,7 +986,7 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
 
         subscriptions.push(
             time::every(Duration::from_millis(
-                self.app_state.dir_manager.settings().update_delay,
+                self.frontend.dir_manager.settings().update_delay,
             ))
             .map(|_| AppMsg::Tick),
         );
@@ -863,8 +1028,8 @@ impl<H: HardwareBridge + 'static> cosmic::Application for Ui<H> {
         self.dialog.as_ref().map(|dialog| {
             scrollable(match dialog {
                 Dialog::Udev => udev_dialog::view(),
-                Dialog::CreateConfig(dialog) => dialog.view(&self.app_state.dir_manager),
-                Dialog::RenameConfig(dialog) => dialog.view(&self.app_state.dir_manager),
+                Dialog::CreateConfig(dialog) => dialog.view(&self.frontend.dir_manager),
+                Dialog::RenameConfig(dialog) => dialog.view(&self.frontend.dir_manager),
             })
             .apply(Element::from)
             .map(AppMsg::Dialog)
@@ -890,7 +1055,7 @@ impl<H: HardwareBridge> Ui<H> {
     fn update_tray_state(&self) {
         #[cfg(not(target_os = "linux"))]
         if let Some((tray, _)) = &self.tray {
-            let dir_manager = &self.app_state.dir_manager;
+            let dir_manager = &self.frontend.dir_manager;
 
             if let Err(e) = tray.update_menu_state(
                 &dir_manager.config_names.data,
@@ -903,9 +1068,9 @@ impl<H: HardwareBridge> Ui<H> {
     }
 
     fn create_config(&mut self, new_name: String) {
-        let config = Config::from_app_graph(&self.app_state.app_graph);
+        let config = Config::from_app_graph(&self.frontend.app_graph);
 
-        if let Err(e) = self.app_state.dir_manager.create_config(&new_name, &config) {
+        if let Err(e) = self.frontend.dir_manager.create_config(&new_name, &config) {
             error!("can't create config: {e}");
         }
 
@@ -914,7 +1079,7 @@ impl<H: HardwareBridge> Ui<H> {
     }
 
     fn rename_config(&mut self, prev: &str, new: &str) {
-        if let Err(e) = self.app_state.dir_manager.rename_config(prev, new) {
+        if let Err(e) = self.frontend.dir_manager.rename_config(prev, new) {
             error!("can't rename config: {e}");
         }
 
@@ -923,23 +1088,19 @@ impl<H: HardwareBridge> Ui<H> {
     }
 
     fn change_config(&mut self, selected: Option<String>) {
-        if selected.is_some() {
-            self.app_state.update.set_valid_root_nodes_to_auto(
-                &mut self.app_state.app_graph.nodes,
-                &self.app_state.app_graph.root_nodes,
-                &mut self.app_state.bridge,
-            );
-        }
+        // the calibration gives the hardware back to a node of the current graph
+        self.abort_calibration();
 
-        match self.app_state.dir_manager.change_config(selected) {
+        match self.frontend.dir_manager.change_config(selected) {
             Ok(config) => {
                 if let Some((_, config)) = config {
-                    self.app_state
+                    self.frontend
                         .app_graph
-                        .apply_config(config, self.app_state.bridge.hardware());
-                    self.nodes_c = NodesC::new(self.app_state.app_graph.nodes.values());
+                        .apply_config(config, &self.frontend.hardware);
+                    self.nodes_c = NodesC::new(self.frontend.app_graph.nodes.values());
 
-                    self.update_hardware();
+                    // the engine sets the controls which are not used anymore to auto
+                    self.frontend.send_graph();
                 }
             }
             Err(e) => {
@@ -952,9 +1113,9 @@ impl<H: HardwareBridge> Ui<H> {
     }
 
     fn save_config(&mut self, name: &str) -> Task<AppMsg> {
-        let config = Config::from_app_graph(&self.app_state.app_graph);
+        let config = Config::from_app_graph(&self.frontend.app_graph);
 
-        if let Err(e) = self.app_state.dir_manager.save_config(name, &config) {
+        if let Err(e) = self.frontend.dir_manager.save_config(name, &config) {
             error!("can't save config: {e}");
             Task::none()
         } else {
@@ -973,7 +1134,7 @@ impl<H: HardwareBridge> Ui<H> {
             .data(NavModelData::NoConfig);
 
         for (index, config) in self
-            .app_state
+            .frontend
             .dir_manager
             .config_names
             .names()
@@ -987,9 +1148,9 @@ impl<H: HardwareBridge> Ui<H> {
                 .divider_above(index == 0);
         }
 
-        match &self.app_state.dir_manager.settings().current_config {
+        match &self.frontend.dir_manager.settings().current_config {
             Some(name) => {
-                if let Some(index) = self.app_state.dir_manager.config_names.index_of(name) {
+                if let Some(index) = self.frontend.dir_manager.config_names.index_of(name) {
                     self.nav_bar_model.activate_position((index + 1) as u16);
                 }
             }
@@ -1036,46 +1197,12 @@ fn to_cosmic_theme(theme: &AppTheme) -> theme::Theme {
 }
 
 impl<H: HardwareBridge> Ui<H> {
-    fn update_hardware(&mut self) {
-        if let Err(e) = self.app_state.bridge.update() {
-            error!("{e}");
-            return;
-        }
-        if let Err(e) = self.app_state.update.all(
-            &mut self.app_state.app_graph.nodes,
-            &mut self.app_state.bridge,
-            self.app_state.dir_manager.settings().inactive,
-        ) {
-            error!("{e}");
-            return;
-        }
-
-        if let Err(e) = self.app_state.update.nodes_which_update_can_change(
-            &mut self.app_state.app_graph.nodes,
-            &mut self.app_state.bridge,
-        ) {
-            error!("{e}");
-        }
-    }
-
+    /// The engine sets the controls to auto when inactive.
     fn set_inactive(&mut self, inactive: bool) {
-        self.app_state.dir_manager.update_settings(|settings| {
+        self.frontend.dir_manager.update_settings(|settings| {
             settings.inactive = inactive;
         });
-
-        if inactive {
-            self.app_state.update.set_valid_root_nodes_to_auto(
-                &mut self.app_state.app_graph.nodes,
-                &self.app_state.app_graph.root_nodes,
-                &mut self.app_state.bridge,
-            );
-        } else if let Err(e) = self.app_state.update.all(
-            &mut self.app_state.app_graph.nodes,
-            &mut self.app_state.bridge,
-            inactive,
-        ) {
-            error!("{e}");
-        }
+        self.frontend.send_settings();
         self.update_tray_state();
     }
 }
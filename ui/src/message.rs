@@ -1,6 +1,6 @@
 use cosmic::widget::{ToastId, menu::action::MenuAction, nav_bar};
 use data::{
-    config::{custom_temp::CustomTempKind, graph::Coord},
+    config::{custom_temp::CustomTempKind, graph::Coord, mix::MixKind, smooth::SmoothKind},
     id::Id,
     node::{Input, NodeTypeLight},
     settings::AppTheme,
@@ -28,6 +28,10 @@ pub enum AppMsg {
     OpenUrl(String),
     NavBarContextMenu(NavBarContextMenuMsg),
     SaveConfig(String),
+    Rescan,
+    Calibrate(Id),
+    DetectFan(Id),
+    AbortCalibration,
     #[cfg(not(target_os = "linux"))]
     SystemTray(crate::tray::SystemTrayMsg),
     #[cfg(not(target_os = "linux"))]
@@ -64,15 +68,20 @@ pub enum ModifNodeMsg {
 
     Control(ControlMsg),
     CustomTemp(CustomTempMsg),
+    Smooth(SmoothMsg),
     Flat(FlatMsg),
     Linear(LinearMsg),
     Target(TargetMsg),
+    Pid(PidMsg),
+    Mix(MixMsg),
     Graph(GraphMsg),
+    Hysteresis(HysteresisMsg),
 }
 
 #[derive(Debug, Clone)]
 pub enum ControlMsg {
     Active(bool),
+    Fan(Option<String>),
 }
 
 #[derive(Debug, Clone)]
@@ -80,6 +89,13 @@ pub enum CustomTempMsg {
     Kind(CustomTempKind),
 }
 
+#[derive(Debug, Clone)]
+pub enum SmoothMsg {
+    Kind(SmoothKind),
+    Window(f64, String),
+    Percentile(f64, String),
+}
+
 #[derive(Debug, Clone)]
 pub enum FlatMsg {
     Value(u16),
@@ -87,20 +103,44 @@ pub enum FlatMsg {
 
 #[derive(Debug, Clone)]
 pub enum LinearMsg {
-    MinTemp(u8, String),
+    MinTemp(f64, String),
     MinSpeed(u8, String),
-    MaxTemp(u8, String),
+    MaxTemp(f64, String),
     MaxSpeed(u8, String),
 }
 
 #[derive(Debug, Clone)]
 pub enum TargetMsg {
-    IdleTemp(u8, String),
+    IdleTemp(f64, String),
     IdleSpeed(u8, String),
-    LoadTemp(u8, String),
+    LoadTemp(f64, String),
     LoadSpeed(u8, String),
 }
 
+#[derive(Debug, Clone)]
+pub enum PidMsg {
+    Setpoint(f64, String),
+    Kp(f64, String),
+    Ki(f64, String),
+    Kd(f64, String),
+    MinSpeed(u8, String),
+    MaxSpeed(u8, String),
+}
+
+#[derive(Debug, Clone)]
+pub enum MixMsg {
+    Kind(MixKind),
+    /// Index of the input
+    Weight(usize, f64, String),
+}
+
+/// For graph and linear behaviors
+#[derive(Debug, Clone)]
+pub enum HysteresisMsg {
+    Deadband(f64, String),
+    FallingOffset(f64, String),
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone)]
 pub enum GraphMsg {
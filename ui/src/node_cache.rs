@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use data::{
+    config::hysteresis::Hysteresis,
     id::Id,
     node::{Node, NodeType},
 };
@@ -24,11 +25,16 @@ pub enum NodeTypeC {
     Control(ControlC),
     Fan(FanC),
     Temp(TempC),
+    Sensor(SensorC),
+    CommandTemp(CommandTempC),
     CustomTemp(CustomTempC),
+    Smooth(SmoothC),
     Graph(GraphC),
     Flat(FlatC),
     Linear(LinearC),
     Target(TargetC),
+    Pid(PidC),
+    Mix(MixC),
 }
 
 #[derive(Debug, Clone)]
@@ -40,11 +46,25 @@ pub struct FanC {}
 #[derive(Debug, Clone)]
 pub struct TempC {}
 
+#[derive(Debug, Clone)]
+pub struct SensorC {}
+
+#[derive(Debug, Clone)]
+pub struct CommandTempC {}
+
 #[derive(Debug, Clone)]
 pub struct CustomTempC {}
 
 #[derive(Debug, Clone)]
-pub struct GraphC {}
+pub struct SmoothC {
+    pub window: String,
+    pub percentile: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphC {
+    pub hysteresis: HysteresisC,
+}
 
 #[derive(Debug, Clone)]
 pub struct FlatC {}
@@ -55,6 +75,22 @@ pub struct LinearC {
     pub min_speed: String,
     pub max_temp: String,
     pub max_speed: String,
+    pub hysteresis: HysteresisC,
+}
+
+#[derive(Debug, Clone)]
+pub struct HysteresisC {
+    pub deadband: String,
+    pub falling_offset: String,
+}
+
+impl HysteresisC {
+    fn new(hysteresis: &Hysteresis) -> Self {
+        Self {
+            deadband: hysteresis.deadband.to_string(),
+            falling_offset: hysteresis.falling_offset.to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -65,6 +101,22 @@ pub struct TargetC {
     pub load_speed: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct MixC {
+    /// One by input
+    pub weights: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PidC {
+    pub setpoint: String,
+    pub kp: String,
+    pub ki: String,
+    pub kd: String,
+    pub min_speed: String,
+    pub max_speed: String,
+}
+
 impl NodesC {
     pub fn new<'a>(nodes: impl Iterator<Item = &'a Node>) -> Self {
         let mut data = HashMap::new();
@@ -114,14 +166,23 @@ impl NodeTypeC {
             data::node::NodeType::Control(_) => NodeTypeC::Control(ControlC {}),
             data::node::NodeType::Fan(_) => NodeTypeC::Fan(FanC {}),
             data::node::NodeType::Temp(_) => NodeTypeC::Temp(TempC {}),
+            data::node::NodeType::Sensor(_) => NodeTypeC::Sensor(SensorC {}),
+            data::node::NodeType::CommandTemp(_) => NodeTypeC::CommandTemp(CommandTempC {}),
             data::node::NodeType::CustomTemp(_) => NodeTypeC::CustomTemp(CustomTempC {}),
-            data::node::NodeType::Graph(_) => NodeTypeC::Graph(GraphC {}),
+            data::node::NodeType::Smooth(smooth) => NodeTypeC::Smooth(SmoothC {
+                window: smooth.window.to_string(),
+                percentile: smooth.percentile.to_string(),
+            }),
+            data::node::NodeType::Graph(graph) => NodeTypeC::Graph(GraphC {
+                hysteresis: HysteresisC::new(&graph.hysteresis),
+            }),
             data::node::NodeType::Flat(_) => NodeTypeC::Flat(FlatC {}),
             data::node::NodeType::Linear(linear) => NodeTypeC::Linear(LinearC {
                 min_temp: linear.min_temp.to_string(),
                 min_speed: linear.min_speed.to_string(),
                 max_temp: linear.max_temp.to_string(),
                 max_speed: linear.max_speed.to_string(),
+                hysteresis: HysteresisC::new(&linear.hysteresis),
             }),
             data::node::NodeType::Target(target) => NodeTypeC::Target(TargetC {
                 idle_temp: target.idle_temp.to_string(),
@@ -129,6 +190,19 @@ impl NodeTypeC {
                 load_temp: target.load_temp.to_string(),
                 load_speed: target.load_speed.to_string(),
             }),
+            data::node::NodeType::Pid(pid) => NodeTypeC::Pid(PidC {
+                setpoint: pid.setpoint.to_string(),
+                kp: pid.kp.to_string(),
+                ki: pid.ki.to_string(),
+                kd: pid.kd.to_string(),
+                min_speed: pid.min_speed.to_string(),
+                max_speed: pid.max_speed.to_string(),
+            }),
+            data::node::NodeType::Mix(mix) => NodeTypeC::Mix(MixC {
+                weights: (0..mix.inputs.len())
+                    .map(|index| mix.weight(index).to_string())
+                    .collect(),
+            }),
         }
     }
 }
@@ -72,9 +72,7 @@ pub mod input {
         nodes
             .values()
             .filter(|n| {
-                node.node_type
-                    .allowed_dep()
-                    .contains(&n.node_type.to_light())
+                node.node_type.accepts_input(&n.node_type)
                     && !node
                         .inputs
                         .iter()
@@ -102,9 +100,7 @@ pub mod input {
         let values = nodes
             .values()
             .filter(|n| {
-                node.node_type
-                    .allowed_dep()
-                    .contains(&n.node_type.to_light())
+                node.node_type.accepts_input(&n.node_type)
                     && !node
                         .inputs
                         .iter()
@@ -128,7 +124,7 @@ pub mod hardware {
 
     use hardware::HItem;
     use std::fmt::Display;
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     use super::MyOption;
 
@@ -150,8 +146,8 @@ pub mod hardware {
         }
     }
 
-    impl<H: HItem> From<&Rc<H>> for HardwarePickListOption {
-        fn from(value: &Rc<H>) -> Self {
+    impl<H: HItem> From<&Arc<H>> for HardwarePickListOption {
+        fn from(value: &Arc<H>) -> Self {
             Self {
                 name: value.name().clone(),
                 id: value.id().clone(),
@@ -160,11 +156,11 @@ pub mod hardware {
     }
 
     /// Return hardware info about `hardware_id` and a vec of
-    /// availlable hardware
+    /// availlable hardware, without the ones `in_use`
     pub fn availlable_hardware<'a, H: HItem>(
         hardware_id: &'a Option<String>,
-        hardwares: &'a [Rc<H>],
-        one_ref: bool,
+        hardwares: &'a [Arc<H>],
+        in_use: impl Fn(&str) -> bool,
     ) -> (
         MyOption<HardwarePickListOption>,
         Vec<MyOption<HardwarePickListOption>>,
@@ -182,12 +178,8 @@ pub mod hardware {
                     _ => false,
                 };
 
-                if one_ref {
-                    // we leverage rc to know if this specific hardware
-                    // is already in use by one node
-                    if Rc::strong_count(h) > 1 {
-                        return None;
-                    }
+                if in_use(h.id()) {
+                    return None;
                 }
 
                 // we only add if hardware_id != h
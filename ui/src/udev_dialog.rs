@@ -28,7 +28,7 @@ pub fn update<H: HardwareBridge>(
         UdevDialogMsg::CopyToClipboard(data) => return clipboard::write(data),
         UdevDialogMsg::CloseAndDontShowAgain => {
             app.dialog = None;
-            app.app_state.dir_manager.update_state(|state| {
+            app.frontend.dir_manager.update_state(|state| {
                 state.show_flatpak_dialog = false;
             });
         }